@@ -9,9 +9,9 @@ async fn main() {
     let ctx = Context::new().unwrap();
 
     let name = cstr!("ca:test:aao");
-    let mut channel = Channel::new(&ctx, name).unwrap();
+    let channel = Channel::new(&ctx, name).unwrap();
     channel.connected().await;
-    let mut channel = channel.into_typed::<[i32]>().unwrap();
+    let channel = channel.into_typed::<[i32]>().unwrap();
     println!("Connected to {:?}", name);
 
     {
@@ -6,7 +6,7 @@ async fn main() {
     let ctx = Context::new().unwrap();
 
     let name = cstr!("ca:test:aao");
-    let mut channel = ctx.connect::<[i32]>(name).await.unwrap();
+    let channel = ctx.connect::<[i32]>(name).await.unwrap();
     println!("Connected to {:?}", name);
 
     {
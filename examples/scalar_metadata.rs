@@ -10,9 +10,9 @@ async fn main() {
     let ctx = Context::new().unwrap();
 
     let name = cstr!("ca:test:ao");
-    let mut channel = Channel::new(&ctx, name).unwrap();
+    let channel = Channel::new(&ctx, name).unwrap();
     channel.connected().await;
-    let mut channel = channel.into_typed::<f64>().unwrap();
+    let channel = channel.into_typed::<f64>().unwrap();
     println!("Connected to {:?}", name);
 
     {
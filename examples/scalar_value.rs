@@ -7,7 +7,7 @@ async fn main() {
     let ctx = Context::new().unwrap();
 
     let name = cstr!("ca:test:ao");
-    let mut channel = ctx.connect::<f64>(name).await.unwrap();
+    let channel = ctx.connect::<f64>(name).await.unwrap();
     println!("Connected to {:?}", name);
 
     {
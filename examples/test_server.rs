@@ -0,0 +1,13 @@
+//! Serves the same PVs as test IOC from `ioc/`, so tests can be run without `epics-base`.
+
+use epics_ca::server::{Server, DEFAULT_PORT};
+
+fn main() {
+    let server = Server::bind(DEFAULT_PORT).unwrap();
+    server.add_test_records();
+
+    println!("Serving test PVs on port {}", server.port());
+    loop {
+        std::thread::park();
+    }
+}
@@ -0,0 +1,92 @@
+//! Channel Access functions the crate is built on.
+//!
+//! They are taken from pure-Rust protocol implementation (`pure-rust` feature),
+//! EPICS CA library (`libca` feature) or from in-memory database (`mock` feature).
+
+#[cfg(all(
+    feature = "libca",
+    not(any(feature = "pure-rust", feature = "mock")),
+    feature = "tracing"
+))]
+pub(crate) use sys::ca_replace_printf_handler;
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+pub(crate) use sys::{
+    ca_add_exception_event, ca_array_get_callback, ca_array_put, ca_array_put_callback,
+    ca_attach_context, ca_clear_channel, ca_clear_subscription, ca_client_status,
+    ca_context_destroy, ca_create_subscription, ca_current_context, ca_detach_context,
+    ca_element_count, ca_field_type, ca_flush_io, ca_host_name, ca_name, ca_pend_event, ca_puser,
+    ca_read_access, ca_replace_access_rights_event, ca_write_access,
+};
+
+#[cfg(all(feature = "mock", feature = "tracing"))]
+pub(crate) use crate::mock::raw::ca_replace_printf_handler;
+#[cfg(feature = "mock")]
+pub(crate) use crate::mock::raw::{
+    ca_add_exception_event, ca_array_get_callback, ca_array_put, ca_array_put_callback,
+    ca_attach_context, ca_clear_channel, ca_clear_subscription, ca_client_status,
+    ca_context_destroy, ca_create_channel, ca_create_subscription, ca_current_context,
+    ca_detach_context, ca_element_count, ca_field_type, ca_flush_io, ca_host_name, ca_name,
+    ca_pend_event, ca_puser, ca_read_access, ca_replace_access_rights_event, ca_write_access,
+};
+
+#[cfg(all(feature = "pure-rust", not(feature = "mock"), feature = "tracing"))]
+pub(crate) use crate::pure::raw::ca_replace_printf_handler;
+#[cfg(all(feature = "pure-rust", not(feature = "mock")))]
+pub(crate) use crate::pure::raw::{
+    ca_add_exception_event, ca_array_get_callback, ca_array_put, ca_array_put_callback,
+    ca_attach_context, ca_clear_channel, ca_clear_subscription, ca_client_status,
+    ca_context_destroy, ca_create_channel, ca_create_subscription, ca_current_context,
+    ca_detach_context, ca_element_count, ca_field_type, ca_flush_io, ca_host_name, ca_name,
+    ca_pend_event, ca_puser, ca_read_access, ca_replace_access_rights_event, ca_write_access,
+};
+
+/// Create context for the current thread with specific settings.
+///
+/// EPICS CA library reads settings from environment only, so they are set during the call.
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+pub(crate) unsafe fn context_create(
+    select: sys::ca_preemptive_callback_select,
+    config: &crate::context::ContextConfig,
+) -> std::os::raw::c_int {
+    config.with_env(|| sys::ca_context_create(select))
+}
+
+/// Create channel in the current context with settings of the context.
+///
+/// Search settings are read when the first channel of the context is created.
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+pub(crate) unsafe fn create_channel(
+    config: &crate::context::ContextConfig,
+    name: *const std::os::raw::c_char,
+    func: sys::caCh,
+    puser: *mut std::ffi::c_void,
+    priority: sys::capri,
+    chid: *mut sys::chid,
+) -> std::os::raw::c_int {
+    config.with_env(|| sys::ca_create_channel(name, func, puser, priority, chid))
+}
+
+/// Create context for the current thread, in-memory database has no settings.
+#[cfg(feature = "mock")]
+pub(crate) unsafe fn context_create(
+    select: sys::ca_preemptive_callback_select,
+    _config: &crate::context::ContextConfig,
+) -> std::os::raw::c_int {
+    crate::mock::raw::ca_context_create(select)
+}
+
+#[cfg(all(feature = "pure-rust", not(feature = "mock")))]
+pub(crate) use crate::pure::raw::context_create;
+
+/// Create channel in the current context, settings are already applied to the context.
+#[cfg(any(feature = "pure-rust", feature = "mock"))]
+pub(crate) unsafe fn create_channel(
+    _config: &crate::context::ContextConfig,
+    name: *const std::os::raw::c_char,
+    func: sys::caCh,
+    puser: *mut std::ffi::c_void,
+    priority: sys::capri,
+    chid: *mut sys::chid,
+) -> std::os::raw::c_int {
+    ca_create_channel(name, func, puser, priority, chid)
+}
@@ -0,0 +1,200 @@
+use crate::{
+    channel::{get::Callback, Get, Timeout, TypedChannel},
+    error::Error,
+    request::{TypedRequest, WriteRequest},
+    types::Value,
+    Context,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{self, Poll},
+    time::Duration,
+};
+
+type MemberFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/// Reads and writes across many channels that are sent in one batch and awaited as a whole.
+///
+/// Requests are issued on the first poll inside [`Context::batch`], so they are sent with a single IO flush,
+/// and the future completes when all of them are done.
+/// Result of each request is available from its [`Member`] handle after that.
+///
+/// *Note that it isn't a synchronous group of EPICS CA library (`ca_sg_*`), requests are ordinary ones joined together.*
+#[must_use]
+pub struct BatchJoin<'a> {
+    context: Context,
+    members: Vec<Option<MemberFuture<'a>>>,
+    /// Error of the first failed member.
+    error: Option<(usize, Error)>,
+}
+
+/// Handle to the result of single request of [`BatchJoin`].
+pub struct Member<T> {
+    result: Arc<Mutex<Option<Result<T, Error>>>>,
+}
+
+impl<T> Member<T> {
+    /// Take the result of the request.
+    ///
+    /// Returns `None` if request isn't done yet or result is already taken.
+    pub fn take(&self) -> Option<Result<T, Error>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+impl Context {
+    /// Create empty [`BatchJoin`].
+    pub fn batch_join<'a>(&self) -> BatchJoin<'a> {
+        BatchJoin {
+            context: self.clone(),
+            members: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl<'a> BatchJoin<'a> {
+    fn add<T, F>(&mut self, future: F) -> Member<T>
+    where
+        T: Send + 'a,
+        F: Future<Output = Result<T, Error>> + Send + 'a,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let member = Member {
+            result: result.clone(),
+        };
+        self.members.push(Some(Box::pin(async move {
+            let output = future.await;
+            let status = output.as_ref().map(|_| ()).map_err(|err| *err);
+            *result.lock().unwrap() = Some(output);
+            status
+        })));
+        member
+    }
+
+    /// Add read request.
+    ///
+    /// Request must not be polled before, it is started together with other requests of the batch.
+    pub fn get<F>(&mut self, get: Get<'a, F>) -> Member<F::Output>
+    where
+        F: Callback + 'a,
+    {
+        self.add(get)
+    }
+
+    /// Add write request of `value` to `channel`.
+    pub fn put<V, R>(&mut self, channel: &'a TypedChannel<V>, value: &R) -> Member<()>
+    where
+        V: Value + Sync + ?Sized,
+        R: TypedRequest<Value = V> + WriteRequest + Send + ?Sized + 'a,
+    {
+        let value = value.clone_boxed();
+        self.add(async move { channel.put_ref(&*value)?.await })
+    }
+
+    /// Number of requests in the batch.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+    /// Whether the batch has no requests.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if requests aren't done in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+}
+
+impl<'a> Future for BatchJoin<'a> {
+    /// Error of the first failed request in order of addition.
+    type Output = Result<(), Error>;
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let context = this.context.clone();
+        let pending = context.batch(|| {
+            let mut pending = false;
+            for (index, slot) in this.members.iter_mut().enumerate() {
+                let member = match slot {
+                    Some(member) => member,
+                    None => continue,
+                };
+                match member.as_mut().poll(cx) {
+                    Poll::Ready(status) => {
+                        *slot = None;
+                        if let Err(err) = status {
+                            match this.error {
+                                Some((first, _)) if first < index => (),
+                                _ => this.error = Some((index, err)),
+                            }
+                        }
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+            pending
+        });
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(match this.error {
+                Some((_, err)) => Err(err),
+                None => Ok(()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error, Channel, Context, TypedChannel};
+    use async_std::test as async_test;
+    use cstr::cstr;
+    use serial_test::serial;
+
+    #[async_test]
+    #[serial]
+    async fn put_get() {
+        let ctx = Context::new().unwrap();
+        let ao = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+        let ai = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+        let aao = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+        let aai = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
+
+        let mut batch = ctx.batch_join();
+        let put_scalar = batch.put(&ao, &2.5);
+        let put_array = batch.put(&aao, &[1, 2, 3][..]);
+        assert_eq!(batch.len(), 2);
+        batch.await.unwrap();
+        assert_eq!(put_scalar.take(), Some(Ok(())));
+        assert_eq!(put_array.take(), Some(Ok(())));
+
+        let mut batch = ctx.batch_join();
+        let scalar = batch.get(ai.get());
+        let array = batch.get(aai.get_vec());
+        batch.await.unwrap();
+        assert_eq!(scalar.take(), Some(Ok(2.5)));
+        assert_eq!(array.take(), Some(Ok(vec![1, 2, 3])));
+        assert_eq!(array.take(), None);
+    }
+
+    #[async_test]
+    #[serial]
+    async fn member_error() {
+        let ctx = Context::new().unwrap();
+        let ai = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+        let missing = TypedChannel::<f64>::new_unchecked(
+            Channel::new(&ctx, cstr!("__nonexistent__")).unwrap(),
+        );
+
+        let mut batch = ctx.batch_join();
+        let value = batch.get(ai.get());
+        let failed = batch.put(&missing, &1.0);
+        assert_eq!(batch.await, Err(error::DISCONN));
+        assert!(value.take().unwrap().is_ok());
+        assert_eq!(failed.take(), Some(Err(error::DISCONN)));
+    }
+}
@@ -0,0 +1,421 @@
+//! Synchronous wrappers around asynchronous channels.
+//!
+//! Operations block current thread until they are done or timeout expires.
+//! No async runtime is required.
+//!
+//! *Don't use this API from inside of async code because it blocks executor thread.*
+
+use crate::{
+    channel::{
+        get::Callback,
+        subscribe::{BoundedFn, LastFn, Overflow, Queue, QueueFn},
+        Channel, Subscription, Timeout,
+    },
+    context::Context,
+    error::Error,
+    request::{ReadRequest, TypedRequest, WriteRequest},
+    types::{Field, Value},
+};
+use futures::{executor::block_on, StreamExt};
+use std::{
+    any::type_name,
+    ffi::CStr,
+    fmt::{self, Debug},
+    future::Future,
+    pin::Pin,
+    time::Duration,
+};
+
+fn wait<T, F: Future<Output = Result<T, Error>>>(
+    future: F,
+    timeout: Option<Duration>,
+) -> Result<T, Error> {
+    match timeout {
+        Some(timeout) => block_on(Timeout::new(future, timeout)),
+        None => block_on(future),
+    }
+}
+
+/// Create channel and wait for connection no longer than `timeout`.
+pub fn connect_base(ctx: &Context, name: &CStr, timeout: Duration) -> Result<Channel, Error> {
+    let chan = Channel::new(ctx, name)?;
+    block_on(chan.connected_timeout(timeout))?;
+    Ok(chan)
+}
+
+/// Create channel, wait for connection, and try to cast it to typed one.
+///
+/// The `timeout` is also used as default timeout for all operations on the channel.
+pub fn connect_typed<V: Value + ?Sized>(
+    ctx: &Context,
+    name: &CStr,
+    timeout: Duration,
+) -> Result<TypedChannel<V>, Error> {
+    let typed = connect_base(ctx, name, timeout)?
+        .into_typed::<V>()
+        .map_err(|(err, _)| err)?;
+    Ok(TypedChannel::new(typed).with_timeout(Some(timeout)))
+}
+
+/// Create channel, wait for connection, and try to cast it to value one.
+///
+/// The `timeout` is also used as default timeout for all operations on the channel.
+pub fn connect<V: Value + ?Sized>(
+    ctx: &Context,
+    name: &CStr,
+    timeout: Duration,
+) -> Result<ValueChannel<V>, Error> {
+    connect_typed::<V>(ctx, name, timeout).map(TypedChannel::into_value)
+}
+
+/// Blocking counterpart of [`crate::TypedChannel`].
+pub struct TypedChannel<V: Value + ?Sized> {
+    inner: crate::TypedChannel<V>,
+    timeout: Option<Duration>,
+}
+
+impl<V: Value + ?Sized> Debug for TypedChannel<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blocking::TypedChannel<{}>({:?})",
+            type_name::<V>(),
+            self.inner.raw()
+        )
+    }
+}
+
+impl<V: Value + ?Sized> TypedChannel<V> {
+    /// Wrap async channel. Operations don't have timeout by default.
+    pub fn new(inner: crate::TypedChannel<V>) -> Self {
+        Self {
+            inner,
+            timeout: None,
+        }
+    }
+    /// Set timeout for all subsequent operations, `None` means wait forever.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    /// Underlying async channel.
+    pub fn as_async(&self) -> &crate::TypedChannel<V> {
+        &self.inner
+    }
+    pub fn into_async(self) -> crate::TypedChannel<V> {
+        self.inner
+    }
+    pub fn into_value(self) -> ValueChannel<V> {
+        ValueChannel::new(self.inner.into_value()).with_timeout(self.timeout)
+    }
+
+    /// Make write request by reference and wait for it to complete.
+    pub fn put_ref<R>(&self, req: &R) -> Result<(), Error>
+    where
+        R: TypedRequest<Value = V> + WriteRequest + ?Sized,
+    {
+        wait(self.inner.put_ref::<R>(req)?, self.timeout)
+    }
+
+    /// Make read request and call closure when it's done, successfully or not.
+    pub fn get_with<R, F>(&self, func: F) -> Result<F::Output, Error>
+    where
+        R: TypedRequest<Value = V> + ReadRequest + ?Sized,
+        F: Callback<Request = R>,
+    {
+        wait(self.inner.get_with(func), self.timeout)
+    }
+
+    /// Subscribe to channel updates and call closure each time when update occured.
+    pub fn subscribe_with<F: Queue>(&self, func: F) -> Monitor<'_, F>
+    where
+        F::Request: TypedRequest<Value = V> + ReadRequest,
+    {
+        Monitor::new(self.inner.subscribe_with(func), self.timeout)
+    }
+}
+
+impl<T: Field> TypedChannel<[T]> {
+    /// Make read request and obtain boxed response.
+    pub fn get_boxed<R>(&self) -> Result<Box<R>, Error>
+    where
+        R: TypedRequest<Value = [T]> + ReadRequest + ?Sized,
+    {
+        wait(self.inner.get_boxed::<R>(), self.timeout)
+    }
+
+    /// Subscribe to channel updates and obtain iterator that provides boxed responses.
+    pub fn subscribe_boxed<R>(&self) -> Monitor<'_, LastFn<R, Box<R>>>
+    where
+        R: TypedRequest<Value = [T]> + ReadRequest + ?Sized,
+    {
+        Monitor::new(self.inner.subscribe_boxed::<R>(), self.timeout)
+    }
+}
+
+impl<T: Field> TypedChannel<T> {
+    /// Write scalar request.
+    pub fn put<R>(&self, req: R) -> Result<(), Error>
+    where
+        R: TypedRequest<Value = T> + WriteRequest,
+    {
+        self.put_ref::<R>(&req)
+    }
+
+    /// Get result of scalar read request.
+    pub fn get<R>(&self) -> Result<R, Error>
+    where
+        R: TypedRequest<Value = T> + ReadRequest + Copy,
+    {
+        wait(self.inner.get::<R>(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar channel.
+    ///
+    /// See [`crate::TypedChannel::subscribe`].
+    pub fn subscribe<R>(&self) -> Monitor<'_, LastFn<R, R>>
+    where
+        R: TypedRequest<Value = T> + ReadRequest + Copy,
+    {
+        Monitor::new(self.inner.subscribe::<R>(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar channel and store all updates.
+    ///
+    /// See [`crate::TypedChannel::subscribe_buffered`].
+    pub fn subscribe_buffered<R>(&self) -> Monitor<'_, QueueFn<R, R>>
+    where
+        R: TypedRequest<Value = T> + ReadRequest + Copy,
+    {
+        Monitor::new(self.inner.subscribe_buffered::<R>(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar channel and store no more than `capacity` updates.
+    ///
+    /// See [`crate::TypedChannel::subscribe_bounded`].
+    pub fn subscribe_bounded<R>(
+        &self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Monitor<'_, BoundedFn<R, R>>
+    where
+        R: TypedRequest<Value = T> + ReadRequest + Copy,
+    {
+        Monitor::new(
+            self.inner.subscribe_bounded::<R>(capacity, overflow),
+            self.timeout,
+        )
+    }
+}
+
+/// Blocking counterpart of [`crate::ValueChannel`].
+pub struct ValueChannel<V: Value + ?Sized> {
+    inner: crate::ValueChannel<V>,
+    timeout: Option<Duration>,
+}
+
+impl<V: Value + ?Sized> Debug for ValueChannel<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blocking::ValueChannel<{}>({:?})",
+            type_name::<V>(),
+            self.inner.raw()
+        )
+    }
+}
+
+impl<V: Value + ?Sized> ValueChannel<V> {
+    /// Wrap async channel. Operations don't have timeout by default.
+    pub fn new(inner: crate::ValueChannel<V>) -> Self {
+        Self {
+            inner,
+            timeout: None,
+        }
+    }
+    /// Set timeout for all subsequent operations, `None` means wait forever.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    /// Underlying async channel.
+    pub fn as_async(&self) -> &crate::ValueChannel<V> {
+        &self.inner
+    }
+    pub fn into_async(self) -> crate::ValueChannel<V> {
+        self.inner
+    }
+    pub fn into_typed(self) -> TypedChannel<V> {
+        TypedChannel::new(self.inner.into()).with_timeout(self.timeout)
+    }
+
+    /// Write value by reference to the channel.
+    pub fn put_ref(&self, data: &V) -> Result<(), Error> {
+        wait(self.inner.put_ref(data)?, self.timeout)
+    }
+
+    /// Request value from the channel and call callback when it's done.
+    pub fn get_with<F: Callback<Request = V>>(&self, func: F) -> Result<F::Output, Error> {
+        wait(self.inner.get_with(func), self.timeout)
+    }
+
+    /// Subscribe to value updates and call closure each time when update occured.
+    pub fn subscribe_with<F: Queue<Request = V>>(&self, func: F) -> Monitor<'_, F> {
+        Monitor::new(self.inner.subscribe_with(func), self.timeout)
+    }
+}
+
+impl<T: Field> ValueChannel<[T]> {
+    /// Request array value and store it in [`Vec`].
+    pub fn get_vec(&self) -> Result<Vec<T>, Error> {
+        wait(self.inner.get_vec(), self.timeout)
+    }
+
+    /// Write value to slice and return received value length (which may be greater than `dst` length).
+    pub fn get_to_slice(&self, dst: &mut [T]) -> Result<usize, Error> {
+        wait(self.inner.get_to_slice(dst), self.timeout)
+    }
+
+    /// Subscribe to array value updates and obtain [`Vec`] iterator.
+    pub fn subscribe_vec(&self) -> Monitor<'_, LastFn<[T], Vec<T>>> {
+        Monitor::new(self.inner.subscribe_vec(), self.timeout)
+    }
+}
+
+impl<T: Field> ValueChannel<T> {
+    /// Write scalar value.
+    pub fn put(&self, val: T) -> Result<(), Error> {
+        wait(self.inner.put(val)?, self.timeout)
+    }
+
+    /// Get scalar value.
+    pub fn get(&self) -> Result<T, Error> {
+        wait(self.inner.get(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar value.
+    pub fn subscribe(&self) -> Monitor<'_, LastFn<T, T>> {
+        Monitor::new(self.inner.subscribe(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar value and store all updates.
+    pub fn subscribe_buffered(&self) -> Monitor<'_, QueueFn<T, T>> {
+        Monitor::new(self.inner.subscribe_buffered(), self.timeout)
+    }
+
+    /// Subscribe to updates of scalar value and store no more than `capacity` updates.
+    pub fn subscribe_bounded(
+        &self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Monitor<'_, BoundedFn<T, T>> {
+        Monitor::new(
+            self.inner.subscribe_bounded(capacity, overflow),
+            self.timeout,
+        )
+    }
+}
+
+/// Blocking iterator over subscription updates.
+///
+/// Each call to [`Iterator::next`] waits for the next update.
+/// If it isn't received in timeout then [`TIMEOUT`](`crate::error::TIMEOUT`) error is returned,
+/// but monitor is still active and can be used further.
+#[must_use]
+pub struct Monitor<'a, F: Queue> {
+    subscription: Pin<Box<Subscription<'a, F>>>,
+    timeout: Option<Duration>,
+}
+
+impl<'a, F: Queue> Monitor<'a, F> {
+    fn new(subscription: Subscription<'a, F>, timeout: Option<Duration>) -> Self {
+        Self {
+            subscription: Box::pin(subscription),
+            timeout,
+        }
+    }
+    /// Set timeout for waiting of each update, `None` means wait forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+}
+
+impl<'a, F: Queue> Iterator for Monitor<'a, F> {
+    type Item = Result<F::Output, Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let subscription = &mut self.subscription;
+        wait(async { Ok(subscription.next().await) }, self.timeout)
+            .unwrap_or_else(|err| Some(Err(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::connect;
+    use crate::{error, Context};
+    use cstr::cstr;
+    use serial_test::serial;
+    use std::{f64::consts::PI, time::Duration};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    #[serial]
+    fn put_get() {
+        let ctx = Context::new().unwrap();
+        let output = connect::<f64>(&ctx, cstr!("ca:test:ao"), TIMEOUT).unwrap();
+        let input = connect::<f64>(&ctx, cstr!("ca:test:ai"), TIMEOUT).unwrap();
+
+        output.put(PI).unwrap();
+        assert_eq!(input.get().unwrap(), PI);
+    }
+
+    #[test]
+    #[serial]
+    fn connect_nonexistent() {
+        let ctx = Context::new().unwrap();
+        let res = connect::<f64>(
+            &ctx,
+            cstr!("ca:test:nonexistent"),
+            Duration::from_millis(100),
+        );
+        assert_eq!(res.unwrap_err(), error::TIMEOUT);
+    }
+
+    #[test]
+    #[serial]
+    fn monitor() {
+        let ctx = Context::new().unwrap();
+        let mut output = connect::<[i32]>(&ctx, cstr!("ca:test:aao"), TIMEOUT).unwrap();
+
+        output.put_ref(&[0]).unwrap();
+        let mut monitor = output.subscribe_vec();
+        assert_eq!(monitor.next().unwrap().unwrap(), [0]);
+        output.put_ref(&[1, 2]).unwrap();
+        assert_eq!(monitor.next().unwrap().unwrap(), [1, 2]);
+
+        monitor.set_timeout(Some(Duration::from_millis(100)));
+        assert_eq!(monitor.next().unwrap().unwrap_err(), error::TIMEOUT);
+        drop(monitor);
+
+        output.set_timeout(None);
+        let mut buffer = [0; 4];
+        assert_eq!(output.get_to_slice(&mut buffer).unwrap(), 2);
+        assert_eq!(buffer, [1, 2, 0, 0]);
+    }
+}
@@ -0,0 +1,91 @@
+use super::{base::UserData, connection::Listeners, Channel};
+use crate::{backend, context::ContextHandle, types::AccessRights};
+use futures::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+impl<C: ContextHandle> Channel<C> {
+    pub(crate) unsafe extern "C" fn access_rights_callback(args: sys::access_rights_handler_args) {
+        let user_data = &*(backend::ca_puser(args.chid) as *const UserData);
+        user_data
+            .access
+            .lock()
+            .unwrap()
+            .notify(AccessRights::from_raw(args.ar));
+    }
+
+    /// Current access rights of the channel.
+    ///
+    /// Disconnected channel has neither read nor write access.
+    pub fn access_rights(&self) -> AccessRights {
+        unsafe {
+            AccessRights::new(
+                backend::ca_read_access(self.raw()) != 0,
+                backend::ca_write_access(self.raw()) != 0,
+            )
+        }
+    }
+    /// Stream of access rights changes.
+    ///
+    /// Access rights are reported each time channel connects and each time they are changed by the server.
+    /// Only changes that occured after the stream was created are reported.
+    pub fn access_rights_events(&self) -> AccessRightsEvents<'_> {
+        AccessRightsEvents::new(self.user_data())
+    }
+}
+
+/// Stream of channel access rights changes.
+#[must_use]
+pub struct AccessRightsEvents<'a> {
+    data: &'a UserData,
+    id: usize,
+}
+
+impl<'a> AccessRightsEvents<'a> {
+    fn new(data: &'a UserData) -> Self {
+        let id = data.access.lock().unwrap().insert(true);
+        Self { data, id }
+    }
+}
+
+impl<'a> Stream for AccessRightsEvents<'a> {
+    type Item = AccessRights;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut access = self.data.access.lock().unwrap();
+        match access.pop(self.id) {
+            Some(rights) => Poll::Ready(Some(rights)),
+            None => {
+                access.register(self.id, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a> Drop for AccessRightsEvents<'a> {
+    fn drop(&mut self) {
+        self.data.access.lock().unwrap().remove(self.id);
+    }
+}
+
+/// Listeners of access rights changes.
+pub(crate) type AccessData = Listeners<AccessRights>;
+
+#[cfg(test)]
+mod tests {
+    use crate::{types::AccessRights, Channel, Context};
+    use async_std::test as async_test;
+    use cstr::cstr;
+    use serial_test::serial;
+
+    #[async_test]
+    #[serial]
+    async fn access_rights() {
+        let ctx = Context::new().unwrap();
+        let channel = Channel::new(&ctx, cstr!("ca:test:ao")).unwrap();
+        channel.connected().await;
+        assert_eq!(channel.access_rights(), AccessRights::new(true, true));
+    }
+}
@@ -1,107 +1,156 @@
-use super::{get::Callback, subscribe::Queue, Get, Put, Subscription};
+use super::{
+    access::AccessData, connection::ConnectionData, get::Callback, put, subscribe::Queue,
+    ChannelBuilder, Get, Put, Subscription,
+};
 use crate::{
-    context::Context,
-    error::{self, result_from_raw, Error},
+    backend,
+    context::{Context, ContextHandle},
+    error::{result_from_raw, Error},
     request::WriteRequest,
+    trace::event,
     types::FieldId,
     utils::Ptr,
 };
-use futures::{future::FusedFuture, task::AtomicWaker};
+use futures::task::AtomicWaker;
 use std::{
+    cell::UnsafeCell,
+    collections::{HashMap, HashSet},
     ffi::{c_void, CStr},
-    future::Future,
-    pin::Pin,
     ptr::{self, NonNull},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Mutex,
-    },
-    task::{Context as Cx, Poll},
+    sync::{atomic::AtomicBool, Mutex},
 };
 
 /// Basic channel.
 ///
 /// Channel is an entity that has a name and could be read, written or subscribed to.
+///
+/// Channel keeps its context alive, by default it is shared [`Context`].
 #[derive(Debug)]
-pub struct Channel {
-    ctx: Context,
+pub struct Channel<C: ContextHandle = Context> {
+    ctx: C,
     raw: <sys::chanId as Ptr>::NonNull,
+    priority: u8,
 }
 
-unsafe impl Send for Channel where Context: Send {}
+unsafe impl<C: ContextHandle + Send> Send for Channel<C> {}
+/// All operations that are available through shared reference are synchronized internally.
+unsafe impl<C: ContextHandle + Sync> Sync for Channel<C> {}
 
-impl Channel {
+impl<C: ContextHandle> Channel<C> {
     /// Create channel without waiting for connection.
-    pub fn new(ctx: &Context, name: &CStr) -> Result<Self, Error> {
+    ///
+    /// To set channel options use [`Self::builder`].
+    pub fn new(ctx: &C, name: &CStr) -> Result<Self, Error> {
+        Self::builder(ctx, name).build()
+    }
+    /// Make builder of the channel with specific options.
+    pub fn builder<'a>(ctx: &'a C, name: &'a CStr) -> ChannelBuilder<'a, C> {
+        ChannelBuilder::new(ctx, name)
+    }
+    pub(crate) fn create(ctx: &C, name: &CStr, priority: u8) -> Result<Self, Error> {
         ctx.clone().with(|| {
             let mut raw: sys::chanId = ptr::null_mut();
             let puser = Box::leak(Box::new(UserData::new())) as *mut UserData;
-            const DEFAULT_PRIORITY: u32 = 0;
 
             match result_from_raw(unsafe {
-                sys::ca_create_channel(
+                backend::create_channel(
+                    ctx.config(),
                     name.as_ptr(),
                     Some(Self::connect_callback),
                     puser as *mut c_void,
-                    DEFAULT_PRIORITY,
+                    priority as _,
                     &mut raw as *mut _,
                 )
             }) {
                 Ok(()) => {
-                    ctx.flush_io();
-                    Ok(Channel {
+                    let channel = Channel {
                         ctx: ctx.clone(),
                         raw: NonNull::new(raw).unwrap(),
-                    })
+                        priority,
+                    };
+                    // Called immediately if channel is already connected, so no changes are missed.
+                    result_from_raw(unsafe {
+                        backend::ca_replace_access_rights_event(
+                            channel.raw(),
+                            Some(Self::access_rights_callback),
+                        )
+                    })?;
+                    ctx.register_channel(channel.raw(), priority);
+                    ctx.auto_flush_io();
+                    event!(debug, channel = ?name, priority, "channel created");
+                    Ok(channel)
                 }
                 Err(e) => {
+                    event!(debug, channel = ?name, error = ?e, "channel creation failed");
                     drop(unsafe { Box::from_raw(puser) });
                     Err(e)
                 }
             }
         })
     }
-    /// Wait for channel become connected.
-    pub fn connected(&mut self) -> Connect<'_> {
-        Connect::new(self)
-    }
     /// Context of the channel.
-    pub fn context(&self) -> &Context {
+    pub fn context(&self) -> &C {
         &self.ctx
     }
+    /// Priority of the channel.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
     /// Raw channed identifier.
     pub fn raw(&self) -> sys::chanId {
         self.raw.as_ptr()
     }
     pub(crate) fn user_data(&self) -> &UserData {
-        unsafe { &*(sys::ca_puser(self.raw.as_ptr()) as *const UserData) }
+        unsafe { &*(backend::ca_puser(self.raw.as_ptr()) as *const UserData) }
     }
     /// Channel name.
     pub fn name(&self) -> &CStr {
-        unsafe { CStr::from_ptr(sys::ca_name(self.raw())) }
+        unsafe { CStr::from_ptr(backend::ca_name(self.raw())) }
     }
     /// Channel field type.
     pub fn field_type(&self) -> Result<FieldId, Error> {
-        let raw = unsafe { sys::ca_field_type(self.raw()) } as i32;
+        unsafe { raw::field_type(self.raw()) }
+    }
+    /// Number of elements in the channel.
+    pub fn element_count(&self) -> Result<usize, Error> {
+        unsafe { raw::element_count(self.raw()) }
+    }
+    /// Name of the host which serves the channel.
+    pub fn host_name(&self) -> Result<&CStr, Error> {
+        unsafe { raw::host_name(self.raw()) }
+    }
+}
+
+/// Channel properties obtained by raw identifier.
+pub(crate) mod raw {
+    use crate::{
+        backend,
+        error::{self, Error},
+        types::FieldId,
+    };
+    use std::ffi::CStr;
+
+    pub unsafe fn field_type(chan: sys::chanId) -> Result<FieldId, Error> {
+        let raw = backend::ca_field_type(chan) as i32;
         if raw == sys::TYPENOTCONN {
             return Err(error::DISCONN);
         }
         FieldId::try_from_raw(raw).ok_or(error::BADTYPE)
     }
-    /// Number of elements in the channel.
-    pub fn element_count(&self) -> Result<usize, Error> {
-        let count = unsafe { sys::ca_element_count(self.raw()) } as usize;
+
+    pub unsafe fn element_count(chan: sys::chanId) -> Result<usize, Error> {
+        let count = backend::ca_element_count(chan) as usize;
         if count == 0 {
             return Err(error::DISCONN);
         }
         Ok(count)
     }
-    /// Name of the host which serves the channel.
-    pub fn host_name(&self) -> Result<&CStr, Error> {
+
+    pub unsafe fn host_name<'a>(chan: sys::chanId) -> Result<&'a CStr, Error> {
         const DISCONN_HOST: &CStr =
             unsafe { CStr::from_bytes_with_nul_unchecked(b"<disconnected>\0") };
 
-        let str = unsafe { CStr::from_ptr(sys::ca_host_name(self.raw())) };
+        let str = CStr::from_ptr(backend::ca_host_name(chan));
         if str != DISCONN_HOST {
             Ok(str)
         } else {
@@ -110,19 +159,22 @@ impl Channel {
     }
 }
 
-impl Drop for Channel {
+impl<C: ContextHandle> Drop for Channel<C> {
     fn drop(&mut self) {
+        event!(debug, channel = ?self.name(), "channel cleared");
+        self.ctx.unregister_channel(self.raw());
         self.context().with(|| {
             let puser = self.user_data() as *const _ as *mut UserData;
-            result_from_raw(unsafe { sys::ca_clear_channel(self.raw()) }).unwrap();
+            result_from_raw(unsafe { backend::ca_clear_channel(self.raw()) }).unwrap();
             drop(unsafe { Box::from_raw(puser) });
         });
     }
 }
 
 pub(crate) struct UserData {
-    pub(crate) waker: AtomicWaker,
     pub(crate) connected: AtomicBool,
+    pub(crate) connection: Mutex<ConnectionData>,
+    pub(crate) access: Mutex<AccessData>,
     pub(crate) process: Mutex<ProcessData>,
 }
 
@@ -130,104 +182,109 @@ impl UserData {
     fn new() -> Self {
         Self {
             connected: AtomicBool::new(false),
-            waker: AtomicWaker::new(),
+            connection: Mutex::new(ConnectionData::new()),
+            access: Mutex::new(AccessData::new()),
             process: Mutex::new(ProcessData::new()),
         }
     }
 }
 
+/// Data of single operation shared with its callback.
+pub(crate) struct Slot<T> {
+    pub(crate) waker: AtomicWaker,
+    /// Must be locked by `UserData::process` mutex of the channel.
+    pub(crate) state: UnsafeCell<T>,
+}
+
+impl<T> Slot<T> {
+    pub fn new(state: T) -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+            state: UnsafeCell::new(state),
+        }
+    }
+}
+
+/// Bookkeeping of operations (reads, writes and subscriptions) currently running on the channel.
+///
+/// Each operation registers pointer to its own data and receives unique identifier
+/// that is passed to the callback as user argument.
+/// Callback looks up the data by this identifier and ignores it if the operation is already gone.
 pub(crate) struct ProcessData {
     id_counter: usize,
-    pub(crate) data: *mut u8,
-    pub(crate) put_res: Option<Result<(), Error>>,
+    slots: HashMap<usize, *const u8>,
+    /// Identifiers of operations that are subscriptions.
+    subscriptions: HashSet<usize>,
 }
 
 impl ProcessData {
     pub fn new() -> Self {
         Self {
             id_counter: 0,
-            data: ptr::null_mut(),
-            put_res: None,
+            slots: HashMap::new(),
+            subscriptions: HashSet::new(),
         }
     }
-    pub fn id(&self) -> usize {
-        self.id_counter
-    }
-    pub fn change_id(&mut self) {
+    /// Register operation data and obtain its identifier.
+    ///
+    /// Identifiers are never reused, so late callbacks of removed operations cannot reach new ones.
+    pub fn insert(&mut self, data: *const u8) -> usize {
         self.id_counter += 1;
+        let id = self.id_counter;
+        assert!(self.slots.insert(id, data).is_none());
+        id
     }
-}
-
-/// Future to wait for connection.
-#[must_use]
-pub struct Connect<'a> {
-    channel: Option<&'a mut Channel>,
-}
-
-impl<'a> Connect<'a> {
-    fn new(channel: &'a mut Channel) -> Self {
-        Connect {
-            channel: Some(channel),
-        }
+    /// Register subscription data, see [`Self::insert`].
+    pub fn insert_subscription(&mut self, data: *const u8) -> usize {
+        let id = self.insert(data);
+        self.subscriptions.insert(id);
+        id
     }
-}
-
-impl<'a> Future for Connect<'a> {
-    type Output = ();
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Cx<'_>) -> Poll<Self::Output> {
-        let channel = self.channel.take().unwrap();
-        channel.user_data().waker.register(cx.waker());
-        if channel.user_data().connected.load(Ordering::Acquire) {
-            Poll::Ready(())
-        } else {
-            self.channel.replace(channel);
-            Poll::Pending
-        }
+    /// Get data of the operation if it is still registered.
+    pub fn get(&self, id: usize) -> Option<*const u8> {
+        self.slots.get(&id).copied()
     }
-}
-
-impl<'a> FusedFuture for Connect<'a> {
-    fn is_terminated(&self) -> bool {
-        self.channel.is_none()
+    /// Unregister operation. Does nothing if the operation is already removed.
+    pub fn remove(&mut self, id: usize) {
+        self.slots.remove(&id);
+        self.subscriptions.remove(&id);
     }
-}
-
-impl Channel {
-    unsafe extern "C" fn connect_callback(args: sys::connection_handler_args) {
-        let user_data = &*(sys::ca_puser(args.chid) as *const UserData);
-        user_data.connected.store(
-            match args.op as _ {
-                sys::CA_OP_CONN_UP => true,
-                sys::CA_OP_CONN_DOWN => false,
-                _ => unreachable!(),
-            },
-            Ordering::Release,
-        );
-        user_data.waker.wake();
+    /// Number of registered subscriptions.
+    pub fn subscription_count(&self) -> usize {
+        self.subscriptions.len()
+    }
+    /// Number of reads and writes waiting for completion.
+    pub fn pending_count(&self) -> usize {
+        self.slots.len() - self.subscriptions.len()
     }
 }
 
-impl Channel {
+impl<C: ContextHandle> Channel<C> {
     /// Make write request by reference.
-    pub fn put_ref<R: WriteRequest + ?Sized>(&mut self, req: &R) -> Result<Put<'_>, Error> {
+    pub fn put_ref<R: WriteRequest + ?Sized>(&self, req: &R) -> Result<Put<'_, C>, Error> {
         Put::new(self, req)
     }
+    /// Make write request by reference and don't wait for it to be done.
+    ///
+    /// Server doesn't report whether the write is successful, so it is cheaper than [`Self::put_ref`].
+    pub fn put_nowait_ref<R: WriteRequest + ?Sized>(&self, req: &R) -> Result<(), Error> {
+        put::put_nowait(self, req)
+    }
     /// Make read request and call closure when it's done, successfully or not.
-    pub fn get_with<F: Callback>(&mut self, func: F) -> Get<'_, F> {
+    pub fn get_with<F: Callback>(&self, func: F) -> Get<'_, F, C> {
         Get::new(self, func)
     }
     /// Subscribe to channel updates and call closure each time when update occured.
-    pub fn subscribe_with<F: Queue>(&mut self, func: F) -> Subscription<'_, F> {
+    pub fn subscribe_with<F: Queue>(&self, func: F) -> Subscription<'_, F, C> {
         Subscription::new(self, func)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{context::UniqueContext, Channel, Context};
-    use async_std::{task::sleep, test as async_test};
+    use crate::{channel::MAX_PRIORITY, context::UniqueContext, error, Channel, Context};
+    use async_std::test as async_test;
     use cstr::cstr;
-    use futures::{select, FutureExt};
     use serial_test::serial;
     use std::{ptr, time::Duration};
 
@@ -241,20 +298,46 @@ mod tests {
             .await;
     }
 
+    #[async_test]
+    #[serial]
+    async fn priority() {
+        let ctx = Context::new().unwrap();
+        let chan = Channel::builder(&ctx, cstr!("ca:test:ai"))
+            .priority(MAX_PRIORITY)
+            .connect::<f64>()
+            .await
+            .unwrap();
+        assert_eq!(chan.priority(), MAX_PRIORITY);
+        chan.get().await.unwrap();
+        assert_eq!(
+            Channel::new(&ctx, cstr!("ca:test:ai")).unwrap().priority(),
+            0
+        );
+        assert_eq!(
+            Channel::builder(&ctx, cstr!("ca:test:ai"))
+                .priority(MAX_PRIORITY + 1)
+                .build()
+                .err(),
+            Some(error::BADPRIORITY)
+        );
+    }
+
     #[async_test]
     async fn connect_nonexistent() {
-        let mut chan = Channel::new(&Context::new().unwrap(), cstr!("__nonexistent__")).unwrap();
-        select! {
-            _ = chan.connected() => panic!(),
-            _ = sleep(Duration::from_millis(100)).fuse() => (),
-        }
+        let chan = Channel::new(&Context::new().unwrap(), cstr!("__nonexistent__")).unwrap();
+        assert_eq!(
+            chan.connected_timeout(Duration::from_millis(100))
+                .await
+                .unwrap_err(),
+            error::TIMEOUT
+        );
     }
 
     #[async_test]
     #[serial]
     async fn user_data() {
         let ctx = Context::new().unwrap();
-        let mut channel = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
+        let channel = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
         channel.connected().await;
 
         // Test that user data can be accessed without context attachment.
@@ -0,0 +1,58 @@
+use super::{Channel, ValueChannel};
+use crate::{
+    context::{Context, ContextHandle},
+    error::{self, Error},
+    types::Value,
+};
+use std::ffi::CStr;
+
+/// Priority of channels created by [`Channel::new`].
+pub const DEFAULT_PRIORITY: u8 = sys::CA_PRIORITY_DEFAULT as u8;
+/// Highest channel priority.
+pub const MAX_PRIORITY: u8 = sys::CA_PRIORITY_MAX as u8;
+
+/// Builder of [`Channel`] with specific options.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct ChannelBuilder<'a, C: ContextHandle = Context> {
+    ctx: &'a C,
+    name: &'a CStr,
+    priority: u8,
+}
+
+impl<'a, C: ContextHandle> ChannelBuilder<'a, C> {
+    pub fn new(ctx: &'a C, name: &'a CStr) -> Self {
+        Self {
+            ctx,
+            name,
+            priority: DEFAULT_PRIORITY,
+        }
+    }
+
+    /// Set channel priority from `0` to [`MAX_PRIORITY`], [`DEFAULT_PRIORITY`] by default.
+    ///
+    /// Channels of different priorities are served through separate connections to the server,
+    /// and requests of higher priority are processed by the server first.
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Create channel without waiting for connection.
+    ///
+    /// Fails with [`BADPRIORITY`](`error::BADPRIORITY`) if priority is out of range.
+    pub fn build(self) -> Result<Channel<C>, Error> {
+        if self.priority > MAX_PRIORITY {
+            return Err(error::BADPRIORITY);
+        }
+        Channel::create(self.ctx, self.name, self.priority)
+    }
+
+    /// Create channel, wait for connection, and try to cast it to typed one.
+    pub async fn connect<V: Value + ?Sized>(self) -> Result<ValueChannel<V, C>, Error> {
+        let chan = self.build()?;
+        chan.connected().await;
+        let typed = chan.into_typed::<V>().map_err(|(err, _)| err)?;
+        Ok(typed.into_value())
+    }
+}
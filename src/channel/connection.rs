@@ -0,0 +1,342 @@
+use super::{base::UserData, Channel, Timeout};
+use crate::{backend, context::ContextHandle, trace::event};
+use futures::{future::FusedFuture, Stream};
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{CStr, CString},
+    future::Future,
+    pin::Pin,
+    sync::atomic::Ordering,
+    task::{Context, Poll, Waker},
+    time::{Duration, SystemTime},
+};
+
+/// Change of channel connection state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Channel became connected to the `host`.
+    Connected { host: CString, time: SystemTime },
+    /// Channel lost connection to the `host`.
+    Disconnected { host: CString, time: SystemTime },
+}
+
+impl ConnectionEvent {
+    /// Whether channel is connected after this event.
+    pub fn is_connected(&self) -> bool {
+        matches!(self, Self::Connected { .. })
+    }
+    /// Name of the host which serves (or served) the channel.
+    pub fn host(&self) -> &CStr {
+        match self {
+            Self::Connected { host, .. } | Self::Disconnected { host, .. } => host,
+        }
+    }
+    /// Time when the event was received.
+    pub fn time(&self) -> SystemTime {
+        match self {
+            Self::Connected { time, .. } | Self::Disconnected { time, .. } => *time,
+        }
+    }
+}
+
+struct Listener<E> {
+    waker: Option<Waker>,
+    /// Present only for listeners that need every event, not only current state.
+    events: Option<VecDeque<E>>,
+}
+
+/// Listeners of some channel events.
+pub(crate) struct Listeners<E: Clone> {
+    id_counter: usize,
+    listeners: HashMap<usize, Listener<E>>,
+}
+
+impl<E: Clone> Listeners<E> {
+    pub fn new() -> Self {
+        Self {
+            id_counter: 0,
+            listeners: HashMap::new(),
+        }
+    }
+    /// Add listener and get its identifier.
+    ///
+    /// If `events` is `true` then all events will be stored for the listener until [`Self::pop`]-ped.
+    pub fn insert(&mut self, events: bool) -> usize {
+        self.id_counter += 1;
+        let id = self.id_counter;
+        let listener = Listener {
+            waker: None,
+            events: if events { Some(VecDeque::new()) } else { None },
+        };
+        assert!(self.listeners.insert(id, listener).is_none());
+        id
+    }
+    pub fn register(&mut self, id: usize, waker: &Waker) {
+        let listener = self.listeners.get_mut(&id).unwrap();
+        match &listener.waker {
+            Some(old) if old.will_wake(waker) => (),
+            _ => listener.waker = Some(waker.clone()),
+        }
+    }
+    pub fn pop(&mut self, id: usize) -> Option<E> {
+        self.listeners
+            .get_mut(&id)
+            .unwrap()
+            .events
+            .as_mut()
+            .unwrap()
+            .pop_front()
+    }
+    pub fn remove(&mut self, id: usize) {
+        self.listeners.remove(&id);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+    pub fn notify(&mut self, event: E) {
+        for listener in self.listeners.values_mut() {
+            if let Some(events) = &mut listener.events {
+                events.push_back(event.clone());
+            }
+            if let Some(waker) = listener.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Connection state of the channel and its listeners.
+pub(crate) struct ConnectionData {
+    /// Last host the channel was connected to.
+    host: CString,
+    listeners: Listeners<ConnectionEvent>,
+}
+
+impl ConnectionData {
+    pub fn new() -> Self {
+        Self {
+            host: CString::default(),
+            listeners: Listeners::new(),
+        }
+    }
+}
+
+impl<C: ContextHandle> Channel<C> {
+    pub(crate) unsafe extern "C" fn connect_callback(args: sys::connection_handler_args) {
+        let user_data = &*(backend::ca_puser(args.chid) as *const UserData);
+        let mut conn = user_data.connection.lock().unwrap();
+        let time = SystemTime::now();
+        let event = match args.op as _ {
+            sys::CA_OP_CONN_UP => {
+                conn.host = CStr::from_ptr(backend::ca_host_name(args.chid)).to_owned();
+                ConnectionEvent::Connected {
+                    host: conn.host.clone(),
+                    time,
+                }
+            }
+            sys::CA_OP_CONN_DOWN => ConnectionEvent::Disconnected {
+                host: conn.host.clone(),
+                time,
+            },
+            _ => unreachable!(),
+        };
+        event!(
+            debug,
+            channel = ?CStr::from_ptr(backend::ca_name(args.chid)),
+            host = ?conn.host,
+            connected = event.is_connected(),
+            "channel connection changed"
+        );
+        user_data
+            .connected
+            .store(event.is_connected(), Ordering::Release);
+        conn.listeners.notify(event);
+    }
+
+    /// Whether channel is currently connected.
+    pub fn is_connected(&self) -> bool {
+        self.user_data().connected.load(Ordering::Acquire)
+    }
+    /// Wait for channel become connected.
+    pub fn connected(&self) -> Connect<'_> {
+        Connect(WaitState::new(self.user_data(), true))
+    }
+    /// Wait for channel become connected but no longer than `timeout`.
+    pub fn connected_timeout(&self, timeout: Duration) -> Timeout<Connect<'_>> {
+        self.connected().timeout(timeout)
+    }
+    /// Wait for channel become disconnected.
+    pub fn disconnected(&self) -> Disconnect<'_> {
+        Disconnect(WaitState::new(self.user_data(), false))
+    }
+    /// Stream of connection state changes.
+    ///
+    /// Only changes that occured after the stream was created are reported.
+    /// To get current state use [`Self::is_connected`] after creating the stream.
+    pub fn connection_events(&self) -> ConnectionEvents<'_> {
+        ConnectionEvents::new(self.user_data())
+    }
+}
+
+struct WaitState<'a> {
+    data: &'a UserData,
+    connected: bool,
+    id: Option<usize>,
+    done: bool,
+}
+
+impl<'a> WaitState<'a> {
+    fn new(data: &'a UserData, connected: bool) -> Self {
+        Self {
+            data,
+            connected,
+            id: None,
+            done: false,
+        }
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        assert!(!self.done);
+        let mut conn = self.data.connection.lock().unwrap();
+        if self.data.connected.load(Ordering::Acquire) == self.connected {
+            if let Some(id) = self.id.take() {
+                conn.listeners.remove(id);
+            }
+            self.done = true;
+            Poll::Ready(())
+        } else {
+            let id = *self.id.get_or_insert_with(|| conn.listeners.insert(false));
+            conn.listeners.register(id, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a> Drop for WaitState<'a> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.data.connection.lock().unwrap().listeners.remove(id);
+        }
+    }
+}
+
+/// Future to wait for connection.
+#[must_use]
+pub struct Connect<'a>(WaitState<'a>);
+
+impl<'a> Connect<'a> {
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if channel isn't connected in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+}
+
+impl<'a> Future for Connect<'a> {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll(cx)
+    }
+}
+
+impl<'a> FusedFuture for Connect<'a> {
+    fn is_terminated(&self) -> bool {
+        self.0.done
+    }
+}
+
+/// Future to wait for disconnection.
+#[must_use]
+pub struct Disconnect<'a>(WaitState<'a>);
+
+impl<'a> Disconnect<'a> {
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if channel isn't disconnected in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+}
+
+impl<'a> Future for Disconnect<'a> {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll(cx)
+    }
+}
+
+impl<'a> FusedFuture for Disconnect<'a> {
+    fn is_terminated(&self) -> bool {
+        self.0.done
+    }
+}
+
+/// Stream of channel connection state changes.
+#[must_use]
+pub struct ConnectionEvents<'a> {
+    data: &'a UserData,
+    id: usize,
+}
+
+impl<'a> ConnectionEvents<'a> {
+    fn new(data: &'a UserData) -> Self {
+        let id = data.connection.lock().unwrap().listeners.insert(true);
+        Self { data, id }
+    }
+}
+
+impl<'a> Stream for ConnectionEvents<'a> {
+    type Item = ConnectionEvent;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut conn = self.data.connection.lock().unwrap();
+        match conn.listeners.pop(self.id) {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                conn.listeners.register(self.id, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a> Drop for ConnectionEvents<'a> {
+    fn drop(&mut self) {
+        self.data
+            .connection
+            .lock()
+            .unwrap()
+            .listeners
+            .remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error, Channel, Context};
+    use async_std::test as async_test;
+    use cstr::cstr;
+    use serial_test::serial;
+    use std::time::Duration;
+
+    #[async_test]
+    #[serial]
+    async fn connected_state() {
+        let ctx = Context::new().unwrap();
+        let channel = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
+        channel.connected().await;
+        assert!(channel.is_connected());
+        channel.connected().await;
+        assert_eq!(
+            channel
+                .disconnected()
+                .timeout(Duration::from_millis(100))
+                .await
+                .unwrap_err(),
+            error::TIMEOUT
+        );
+    }
+
+    #[async_test]
+    async fn disconnected_nonexistent() {
+        let channel = Channel::new(&Context::new().unwrap(), cstr!("__nonexistent__")).unwrap();
+        assert!(!channel.is_connected());
+        channel.disconnected().await;
+    }
+}
@@ -0,0 +1,300 @@
+use super::{
+    subscribe::{LastFn, Queue, QueueFn},
+    Channel, GetFn, Put, Subscription, Timeout,
+};
+use crate::{
+    context::{Context, ContextHandle},
+    error::{self, Error},
+    request::{DynCtrlField, DynKind, DynRequest, Sts, Time},
+    types::{DynArray, DynScalar, DynValue, EpicsEnum, EpicsString, EventMask, FieldId},
+};
+use futures::Stream;
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{self, Poll},
+    time::Duration,
+};
+
+impl<C: ContextHandle> Channel<C> {
+    /// Native field type of the channel and whether it is scalar.
+    fn dyn_type(&self) -> Result<(FieldId, bool), Error> {
+        Ok((self.field_type()?, self.element_count()? == 1))
+    }
+
+    /// Write value to the channel which type is not known in advance.
+    ///
+    /// Field type of the `value` must match the native field type of the channel.
+    pub fn put_dyn(&self, value: &DynValue) -> Result<Put<'_, C>, Error> {
+        if value.field_id() != self.field_type()? {
+            return Err(error::BADTYPE);
+        }
+        match value {
+            DynValue::Scalar(scalar) => match scalar {
+                DynScalar::String(x) => self.put_ref(x),
+                DynScalar::Short(x) => self.put_ref(x),
+                DynScalar::Float(x) => self.put_ref(x),
+                DynScalar::Enum(x) => self.put_ref(x),
+                DynScalar::Char(x) => self.put_ref(x),
+                DynScalar::Long(x) => self.put_ref(x),
+                DynScalar::Double(x) => self.put_ref(x),
+            },
+            DynValue::Array(array) => match array {
+                DynArray::String(v) => self.put_ref(v.as_slice()),
+                DynArray::Short(v) => self.put_ref(v.as_slice()),
+                DynArray::Float(v) => self.put_ref(v.as_slice()),
+                DynArray::Enum(v) => self.put_ref(v.as_slice()),
+                DynArray::Char(v) => self.put_ref(v.as_slice()),
+                DynArray::Long(v) => self.put_ref(v.as_slice()),
+                DynArray::Double(v) => self.put_ref(v.as_slice()),
+            },
+        }
+    }
+}
+
+impl<C: ContextHandle + Sync> Channel<C> {
+    /// Read value of the channel which type is not known in advance.
+    ///
+    /// Request of the `kind` is made for the native field type of the channel.
+    /// Channel with single element is read as scalar, otherwise as array.
+    pub fn get_dyn(&self, kind: DynKind) -> Result<GetDyn<'_, C>, Error> {
+        let (field, scalar) = self.dyn_type()?;
+        Ok(match field {
+            FieldId::String => self.get_dyn_typed::<EpicsString>(kind, scalar),
+            FieldId::Short => self.get_dyn_typed::<i16>(kind, scalar),
+            FieldId::Float => self.get_dyn_typed::<f32>(kind, scalar),
+            FieldId::Enum => self.get_dyn_typed::<EpicsEnum>(kind, scalar),
+            FieldId::Char => self.get_dyn_typed::<u8>(kind, scalar),
+            FieldId::Long => self.get_dyn_typed::<i32>(kind, scalar),
+            FieldId::Double => self.get_dyn_typed::<f64>(kind, scalar),
+        })
+    }
+
+    fn get_dyn_typed<T: DynCtrlField>(&self, kind: DynKind, scalar: bool) -> GetDyn<'_, C> {
+        GetDyn {
+            _p: PhantomData,
+            inner: match kind {
+                DynKind::Base => Box::pin(self.get_with(GetFn::<[T], DynRequest, _>::new(
+                    move |input: Result<&[T], Error>| {
+                        input.map(|req| DynRequest::from_base(req, scalar))
+                    },
+                ))),
+                DynKind::Sts => Box::pin(self.get_with(GetFn::<Sts<[T]>, DynRequest, _>::new(
+                    move |input: Result<&Sts<[T]>, Error>| {
+                        input.map(|req| DynRequest::from_sts(req, scalar))
+                    },
+                ))),
+                DynKind::Time => Box::pin(self.get_with(GetFn::<Time<[T]>, DynRequest, _>::new(
+                    move |input: Result<&Time<[T]>, Error>| {
+                        input.map(|req| DynRequest::from_time(req, scalar))
+                    },
+                ))),
+                DynKind::Ctrl => Box::pin(self.get_with(GetFn::<T::Ctrl, DynRequest, _>::new(
+                    move |input: Result<&T::Ctrl, Error>| {
+                        input.map(|req| DynRequest::from_ctrl::<T>(req, scalar))
+                    },
+                ))),
+            },
+        }
+    }
+
+    /// Subscribe to updates of the channel which type is not known in advance.
+    ///
+    /// Returned stream stores only last unread value.
+    /// Types of requests are chosen the same way as in [`Self::get_dyn`].
+    pub fn subscribe_dyn(&self, kind: DynKind) -> Result<SubscriptionDyn<'_, C>, Error> {
+        self.subscribe_dyn_any(kind, false)
+    }
+
+    /// Subscribe to updates of the channel which type is not known in advance and store all updates.
+    ///
+    /// This subscription contains internal buffer that can grow up to arbitrary size
+    /// especially in case of frequent channel updates.
+    pub fn subscribe_dyn_buffered(&self, kind: DynKind) -> Result<SubscriptionDyn<'_, C>, Error> {
+        self.subscribe_dyn_any(kind, true)
+    }
+
+    fn subscribe_dyn_any(
+        &self,
+        kind: DynKind,
+        buffered: bool,
+    ) -> Result<SubscriptionDyn<'_, C>, Error> {
+        let (field, scalar) = self.dyn_type()?;
+        Ok(match field {
+            FieldId::String => self.subscribe_dyn_typed::<EpicsString>(kind, scalar, buffered),
+            FieldId::Short => self.subscribe_dyn_typed::<i16>(kind, scalar, buffered),
+            FieldId::Float => self.subscribe_dyn_typed::<f32>(kind, scalar, buffered),
+            FieldId::Enum => self.subscribe_dyn_typed::<EpicsEnum>(kind, scalar, buffered),
+            FieldId::Char => self.subscribe_dyn_typed::<u8>(kind, scalar, buffered),
+            FieldId::Long => self.subscribe_dyn_typed::<i32>(kind, scalar, buffered),
+            FieldId::Double => self.subscribe_dyn_typed::<f64>(kind, scalar, buffered),
+        })
+    }
+
+    fn subscribe_dyn_typed<T: DynCtrlField>(
+        &self,
+        kind: DynKind,
+        scalar: bool,
+        buffered: bool,
+    ) -> SubscriptionDyn<'_, C> {
+        match kind {
+            DynKind::Base => {
+                self.subscribe_dyn_with(buffered, move |input: Result<&[T], Error>| {
+                    Some(input.map(|req| DynRequest::from_base(req, scalar)))
+                })
+            }
+            DynKind::Sts => {
+                self.subscribe_dyn_with(buffered, move |input: Result<&Sts<[T]>, Error>| {
+                    Some(input.map(|req| DynRequest::from_sts(req, scalar)))
+                })
+            }
+            DynKind::Time => {
+                self.subscribe_dyn_with(buffered, move |input: Result<&Time<[T]>, Error>| {
+                    Some(input.map(|req| DynRequest::from_time(req, scalar)))
+                })
+            }
+            DynKind::Ctrl => {
+                self.subscribe_dyn_with(buffered, move |input: Result<&T::Ctrl, Error>| {
+                    Some(input.map(|req| DynRequest::from_ctrl::<T>(req, scalar)))
+                })
+            }
+        }
+    }
+
+    fn subscribe_dyn_with<R, F>(&self, buffered: bool, func: F) -> SubscriptionDyn<'_, C>
+    where
+        R: crate::request::ReadRequest + ?Sized,
+        F: FnMut(Result<&R, Error>) -> Option<Result<DynRequest, Error>> + Send + 'static,
+    {
+        SubscriptionDyn {
+            _p: PhantomData,
+            inner: if buffered {
+                Box::pin(self.subscribe_with(QueueFn::<R, DynRequest, F>::new(func)))
+            } else {
+                Box::pin(self.subscribe_with(LastFn::<R, DynRequest, F>::new(func)))
+            },
+        }
+    }
+}
+
+/// Future that reads value of dynamically typed channel.
+#[must_use]
+pub struct GetDyn<'a, C: ContextHandle + Sync = Context> {
+    inner: Pin<Box<dyn Future<Output = Result<DynRequest, Error>> + Send + 'a>>,
+    _p: PhantomData<&'a Channel<C>>,
+}
+
+impl<'a, C: ContextHandle + Sync> GetDyn<'a, C> {
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if reading isn't done in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+}
+
+impl<'a, C: ContextHandle + Sync> Future for GetDyn<'a, C> {
+    type Output = Result<DynRequest, Error>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+trait DynStream: Stream<Item = Result<DynRequest, Error>> {
+    fn set_event_mask(self: Pin<&mut Self>, mask: EventMask);
+}
+
+impl<'a, F: Queue<Output = DynRequest>, C: ContextHandle> DynStream for Subscription<'a, F, C> {
+    fn set_event_mask(self: Pin<&mut Self>, mask: EventMask) {
+        self.set_event_mask_pinned(mask);
+    }
+}
+
+/// Subscription to dynamically typed channel.
+#[must_use]
+pub struct SubscriptionDyn<'a, C: ContextHandle + Sync = Context> {
+    inner: Pin<Box<dyn DynStream + Send + 'a>>,
+    _p: PhantomData<&'a Channel<C>>,
+}
+
+impl<'a, C: ContextHandle + Sync> SubscriptionDyn<'a, C> {
+    /// Set kinds of channel events this subscription should be notified.
+    ///
+    /// See [`Subscription::set_event_mask`].
+    pub fn set_event_mask(&mut self, mask: EventMask) {
+        self.inner.as_mut().set_event_mask(mask);
+    }
+}
+
+impl<'a, C: ContextHandle + Sync> Stream for SubscriptionDyn<'a, C> {
+    type Item = Result<DynRequest, Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        request::{DynCtrl, DynKind, DynRequest},
+        types::{DynArray, DynScalar, DynValue},
+        Context,
+    };
+    use async_std::test as async_test;
+    use cstr::cstr;
+    use futures::StreamExt;
+    use serial_test::serial;
+    use std::f64::consts::PI;
+
+    #[async_test]
+    #[serial]
+    async fn get_dyn_scalar() {
+        let ctx = Context::new().unwrap();
+        let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+        output.put(PI).unwrap().await.unwrap();
+
+        assert_eq!(
+            output.get_dyn(DynKind::Base).unwrap().await.unwrap(),
+            DynRequest::Base(DynValue::Scalar(DynScalar::Double(PI)))
+        );
+        let time = output.get_dyn(DynKind::Time).unwrap().await.unwrap();
+        assert!(time.alarm().is_some());
+        assert!(time.stamp().is_some());
+        let ctrl = output.get_dyn(DynKind::Ctrl).unwrap().await.unwrap();
+        assert!(matches!(ctrl.ctrl(), Some(DynCtrl::Double { .. })));
+    }
+
+    #[async_test]
+    #[serial]
+    async fn put_get_dyn_array() {
+        let ctx = Context::new().unwrap();
+        let output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+        let input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
+
+        let value = DynValue::from(vec![1, 2, 3]);
+        output.put_dyn(&value).unwrap().await.unwrap();
+        assert_eq!(
+            input.get_dyn(DynKind::Sts).unwrap().await.unwrap().value(),
+            &value
+        );
+        assert!(output.put_dyn(&DynValue::from(vec![1.0])).is_err());
+    }
+
+    #[async_test]
+    #[serial]
+    async fn subscribe_dyn() {
+        let ctx = Context::new().unwrap();
+        let output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+
+        output.put_ref(&[-1]).unwrap().await.unwrap();
+        let mut monitor = output.subscribe_dyn(DynKind::Time).unwrap();
+        assert_eq!(
+            monitor.next().await.unwrap().unwrap().into_value(),
+            DynValue::Array(DynArray::Long(vec![-1]))
+        );
+        output.put_ref(&[1, 2]).unwrap().await.unwrap();
+        assert_eq!(
+            monitor.next().await.unwrap().unwrap().into_value(),
+            DynValue::Array(DynArray::Long(vec![1, 2]))
+        );
+    }
+}
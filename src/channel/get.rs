@@ -1,18 +1,23 @@
-use super::{base::UserData, Channel};
+use super::{
+    base::{Slot, UserData},
+    Channel, Timeout,
+};
 use crate::{
+    backend,
+    context::{Context, ContextHandle},
     error::{result_from_raw, Error},
     request::{ReadRequest, Request},
+    trace::{self, request_span, Span},
     types::RequestId,
 };
 use pin_project::{pin_project, pinned_drop};
 use std::{
-    cell::UnsafeCell,
     future::Future,
     marker::{PhantomData, PhantomPinned},
     mem,
     pin::Pin,
-    ptr,
-    task::{Context, Poll},
+    task::{self, Poll},
+    time::Duration,
 };
 
 /// Callback that called when request result is ready.
@@ -31,61 +36,81 @@ pub(crate) enum GetState<F: Callback> {
 }
 
 /// Future that performs reading from channel.
+///
+/// Many reads can be performed on the same channel simultaneously.
 #[must_use]
 #[pin_project(PinnedDrop)]
-pub struct Get<'a, F: Callback> {
-    owner: &'a mut Channel,
-    /// Must be locked by `owner.user_data().process` mutex
-    state: UnsafeCell<GetState<F>>,
-    started: bool,
+pub struct Get<'a, F: Callback, C: ContextHandle = Context> {
+    owner: &'a Channel<C>,
+    slot: Slot<GetState<F>>,
+    id: Option<usize>,
+    span: Span,
     #[pin]
     _pp: PhantomPinned,
 }
 
-impl<'a, F: Callback> Get<'a, F> {
-    pub(crate) fn new(owner: &'a mut Channel, func: F) -> Self {
+impl<'a, F: Callback, C: ContextHandle> Get<'a, F, C> {
+    pub(crate) fn new(owner: &'a Channel<C>, func: F) -> Self {
         Self {
             owner,
-            state: UnsafeCell::new(GetState::Pending(func)),
-            started: false,
+            slot: Slot::new(GetState::Pending(func)),
+            id: None,
+            span: request_span!("get", owner, F::Request::ID),
             _pp: PhantomPinned,
         }
     }
 
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if reading isn't done in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+
     /// Initiate reading.
     ///
     /// This method can be called implicitly on the first poll.
     /// It cannot be done in constructor because `Self` must be pinned at this point.
     pub fn start(self: Pin<&mut Self>) -> Result<(), Error> {
-        assert!(!self.started);
+        assert!(self.id.is_none());
         let this = self.project();
-        let owner = this.owner;
-        owner.context().with(|| {
+        let owner = *this.owner;
+        let _entered = this.span.enter();
+        let result = owner.context().with(|| {
             let mut proc = owner.user_data().process.lock().unwrap();
-            proc.data = this.state.get() as *mut u8;
-            result_from_raw(unsafe {
-                sys::ca_array_get_callback(
+            let id = proc.insert(this.slot as *const _ as *const u8);
+            match result_from_raw(unsafe {
+                backend::ca_array_get_callback(
                     F::Request::ID.raw() as _,
                     0,
                     owner.raw(),
                     Some(Self::callback),
-                    proc.id() as _,
+                    id as _,
                 )
-            })
-            .map(|()| {
-                owner.context().flush_io();
-                *this.started = true
-            })
-        })
+            }) {
+                Ok(()) => {
+                    owner.context().auto_flush_io();
+                    *this.id = Some(id);
+                    Ok(())
+                }
+                Err(err) => {
+                    proc.remove(id);
+                    Err(err)
+                }
+            }
+        });
+        trace::started(&result);
+        result
     }
 
     unsafe extern "C" fn callback(args: sys::event_handler_args) {
-        let user_data = &*(sys::ca_puser(args.chid) as *const UserData);
-        let proc = user_data.process.lock().unwrap();
-        if proc.id() != args.usr as usize {
-            return;
-        }
-        let state = &mut *(proc.data as *mut GetState<F>);
+        let user_data = &*(backend::ca_puser(args.chid) as *const UserData);
+        let mut proc = user_data.process.lock().unwrap();
+        let id = args.usr as usize;
+        let slot = match proc.get(id) {
+            Some(ptr) => &*(ptr as *const Slot<GetState<F>>),
+            None => return,
+        };
+        proc.remove(id);
+        let state = &mut *slot.state.get();
         let func = match mem::replace(state, GetState::Empty) {
             GetState::Pending(func) => func,
             _ => unreachable!(),
@@ -97,31 +122,31 @@ impl<'a, F: Callback> Get<'a, F> {
                 args.count as usize,
             )
         })));
-        user_data.waker.wake();
+        slot.waker.wake();
     }
 }
 
-impl<'a, F: Callback> Future for Get<'a, F> {
+impl<'a, F: Callback, C: ContextHandle> Future for Get<'a, F, C> {
     type Output = Result<F::Output, Error>;
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.owner.user_data().waker.register(cx.waker());
-        if !self.started {
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.slot.waker.register(cx.waker());
+        if self.id.is_none() {
             self.start()?;
             return Poll::Pending;
         }
         let this = self.project();
         let proc = this.owner.user_data().process.lock().unwrap();
-        let state = unsafe { &mut *this.state.get() };
+        let state = unsafe { &mut *this.slot.state.get() };
         let poll = match mem::replace(state, GetState::Empty) {
             GetState::Empty => unreachable!(),
             GetState::Pending(func) => {
                 *state = GetState::Pending(func);
                 Poll::Pending
             }
-            GetState::Ready(res) => match res {
-                Ok(ret) => Poll::Ready(Ok(ret)),
-                Err(err) => Poll::Ready(Err(err)),
-            },
+            GetState::Ready(res) => {
+                this.span.in_scope(|| trace::finished(&res));
+                Poll::Ready(res)
+            }
         };
         drop(proc);
         poll
@@ -129,12 +154,13 @@ impl<'a, F: Callback> Future for Get<'a, F> {
 }
 
 #[pinned_drop]
-impl<'a, F: Callback> PinnedDrop for Get<'a, F> {
+impl<'a, F: Callback, C: ContextHandle> PinnedDrop for Get<'a, F, C> {
     #[allow(clippy::needless_lifetimes)]
     fn drop(self: Pin<&mut Self>) {
-        let mut proc = self.owner.user_data().process.lock().unwrap();
-        proc.change_id();
-        proc.data = ptr::null_mut();
+        if let Some(id) = self.id {
+            let mut proc = self.owner.user_data().process.lock().unwrap();
+            proc.remove(id);
+        }
     }
 }
 
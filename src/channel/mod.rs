@@ -5,31 +5,58 @@
 //! + [`ValueChannel`] - convenience wrapper around [`TypedChannel`].
 //!   Recommended to use when you need PV values only, not metadata. Created by [`Context::connect`] or [`TypedChannel::into_value`].
 //!
+//! Any of them can be wrapped into [`SharedChannel`] to be used from many tasks at once.
+//!
 
+pub mod access;
 pub mod base;
+pub mod builder;
+pub mod connection;
+pub mod dynamic;
 pub mod get;
+pub mod monitor;
 pub mod put;
+pub mod shared;
 pub mod subscribe;
+pub mod timeout;
 pub mod typed;
 pub mod value;
 
-pub use base::{Channel, Connect};
+pub use access::AccessRightsEvents;
+pub use base::Channel;
+pub use builder::{ChannelBuilder, DEFAULT_PRIORITY, MAX_PRIORITY};
+pub use connection::{Connect, ConnectionEvent, ConnectionEvents, Disconnect};
+pub use dynamic::{GetDyn, SubscriptionDyn};
 pub use get::{Get, GetFn};
+pub use monitor::{MonitorEvent, MonitorEvents};
 pub use put::Put;
-pub use subscribe::Subscription;
+pub use shared::{Owned, SharedChannel};
+pub use subscribe::{Lossy, Overflow, Subscription};
+pub use timeout::Timeout;
 pub use typed::TypedChannel;
 pub use value::ValueChannel;
 
-use crate::{context::Context, error::Error, types::Value};
+use crate::{
+    context::{Context, LocalContext},
+    error::Error,
+    types::Value,
+};
 use std::ffi::CStr;
 
 impl Context {
     /// Create channel, wait for connection, and try to cast it to typed one.
     pub async fn connect<V: Value + ?Sized>(&self, name: &CStr) -> Result<ValueChannel<V>, Error> {
-        let mut chan = Channel::new(self, name)?;
-        chan.connected().await;
-        let typed = chan.into_typed::<V>().map_err(|(err, _)| err)?;
-        Ok(typed.into_value())
+        Channel::builder(self, name).connect().await
+    }
+}
+
+impl LocalContext {
+    /// Create channel, wait for connection, and try to cast it to typed one.
+    pub async fn connect<V: Value + ?Sized>(
+        &self,
+        name: &CStr,
+    ) -> Result<ValueChannel<V, Self>, Error> {
+        Channel::builder(self, name).connect().await
     }
 }
 
@@ -0,0 +1,146 @@
+use super::{subscribe::Queue, Channel, ConnectionEvents, Subscription};
+use crate::{
+    context::{Context, ContextHandle},
+    error::Error,
+    request::TypedRequest,
+    trace::event,
+    types::FieldId,
+};
+use futures::Stream;
+use pin_project::pin_project;
+use std::{
+    pin::Pin,
+    task::{self, Poll},
+};
+
+/// Item of [`MonitorEvents`] stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MonitorEvent<T> {
+    /// Channel was updated.
+    Value(T),
+    /// Channel lost connection. No values will be received until it is reconnected.
+    Disconnected,
+    /// Channel connected (again) and its type is still compatible with the subscription.
+    ///
+    /// Fresh value of the channel is yielded after this event.
+    Reconnected { field_type: FieldId, count: usize },
+}
+
+/// Subscription that survives reconnection of the channel, e.g. when IOC is restarted.
+///
+/// Besides values it reports when the channel is disconnected and reconnected,
+/// so consumer knows where gaps in received values are.
+///
+/// On each reconnection the channel type is checked by the same rules as [`Channel::into_typed`].
+/// If the type is no longer compatible, the error is yielded instead of [`MonitorEvent::Reconnected`]
+/// and values are suppressed until the channel is reconnected with compatible type.
+/// The stream isn't terminated by errors, so it's up to consumer whether to wait for next reconnection or not.
+///
+/// Values received before disconnection but not read yet may be yielded after [`MonitorEvent::Disconnected`].
+#[must_use]
+#[pin_project]
+pub struct MonitorEvents<'a, F: Queue, C: ContextHandle = Context>
+where
+    F::Request: TypedRequest,
+{
+    #[pin]
+    sub: Subscription<'a, F, C>,
+    conn: ConnectionEvents<'a>,
+    connected: bool,
+    incompatible: bool,
+}
+
+impl<'a, F: Queue, C: ContextHandle> MonitorEvents<'a, F, C>
+where
+    F::Request: TypedRequest,
+{
+    fn new(owner: &'a Channel<C>, sub: Subscription<'a, F, C>) -> Self {
+        // Events are listened before reading the state to not miss any change.
+        let conn = owner.connection_events();
+        Self {
+            sub,
+            conn,
+            connected: owner.is_connected(),
+            incompatible: false,
+        }
+    }
+
+    /// Whether channel was connected according to the last yielded event.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn reconnected(owner: &Channel<C>) -> Result<MonitorEvent<F::Output>, Error> {
+        owner.check_type::<<F::Request as TypedRequest>::Value>()?;
+        Ok(MonitorEvent::Reconnected {
+            field_type: owner.field_type()?,
+            count: owner.element_count()?,
+        })
+    }
+}
+
+impl<'a, F: Queue, C: ContextHandle> Subscription<'a, F, C>
+where
+    F::Request: TypedRequest,
+{
+    /// Convert into [`MonitorEvents`] that also reports disconnections and reconnections of the channel.
+    pub fn into_monitor(self) -> MonitorEvents<'a, F, C> {
+        let owner = self.owner();
+        MonitorEvents::new(owner, self)
+    }
+}
+
+impl<'a, F: Queue, C: ContextHandle> Stream for MonitorEvents<'a, F, C>
+where
+    F::Request: TypedRequest,
+{
+    type Item = Result<MonitorEvent<F::Output>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        while let Poll::Ready(event) = Pin::new(&mut *this.conn).poll_next(cx) {
+            let event = match event {
+                Some(event) => event,
+                None => return Poll::Ready(None),
+            };
+            if event.is_connected() == *this.connected {
+                continue;
+            }
+            *this.connected = event.is_connected();
+            let owner = this.sub.owner();
+            return Poll::Ready(Some(if *this.connected {
+                match Self::reconnected(owner) {
+                    Ok(event) => Ok(event),
+                    Err(err) => {
+                        event!(warn, channel = ?owner.name(), error = ?err, "reconnected with incompatible type");
+                        *this.incompatible = true;
+                        Err(err)
+                    }
+                }
+            } else {
+                if *this.incompatible {
+                    // Values received until now are of incompatible channel.
+                    *this.incompatible = false;
+                    let _ = drop_values(this.sub.as_mut(), cx);
+                }
+                Ok(MonitorEvent::Disconnected)
+            }));
+        }
+        if *this.incompatible {
+            return drop_values(this.sub, cx).map(|()| None);
+        }
+        this.sub
+            .poll_next(cx)
+            .map(|item| item.map(|result| result.map(MonitorEvent::Value)))
+    }
+}
+
+/// Skip all received values. Returns [`Poll::Ready`] only if subscription is terminated.
+fn drop_values<S: Stream>(mut sub: Pin<&mut S>, cx: &mut task::Context<'_>) -> Poll<()> {
+    while let Poll::Ready(item) = sub.as_mut().poll_next(cx) {
+        if item.is_none() {
+            return Poll::Ready(());
+        }
+    }
+    Poll::Pending
+}
@@ -1,79 +1,146 @@
-use super::{base::UserData, Channel};
+use super::{
+    base::{Slot, UserData},
+    Channel, Timeout,
+};
 use crate::{
+    backend,
+    context::{Context, ContextHandle},
     error::{result_from_raw, Error},
     request::WriteRequest,
+    trace::{self, request_span, Span},
 };
 use std::{
     future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    task::{self, Poll},
+    time::Duration,
 };
 
+/// Write request without completion notification.
+///
+/// IO is flushed immediately unless called inside of [`batch`](`crate::context::UniqueContext::batch`).
+pub(crate) fn put_nowait<R: WriteRequest + ?Sized, C: ContextHandle>(
+    owner: &Channel<C>,
+    request: &R,
+) -> Result<(), Error> {
+    let span = request_span!("put_nowait", owner, R::ID);
+    let _entered = span.enter();
+    let result = owner.context().with(|| {
+        result_from_raw(unsafe {
+            backend::ca_array_put(
+                R::ID.raw() as _,
+                request.len() as _,
+                owner.raw(),
+                request as *const R as *const _,
+            )
+        })
+    });
+    if result.is_ok() {
+        owner.context().auto_flush_io();
+    }
+    trace::started(&result);
+    result
+}
+
 /// Future that waits for write request is done, successfully or not.
 ///
+/// Many writes can be performed on the same channel simultaneously.
+///
 /// *Waiting for this future to complete is optional.
 /// The write can be done successfully even if it dropped before completion.*
-pub struct Put<'a> {
-    owner: &'a mut Channel,
+pub struct Put<'a, C: ContextHandle = Context> {
+    owner: &'a Channel<C>,
+    /// Boxed because callback refers to it while `Self` can be moved.
+    slot: Box<Slot<Option<Result<(), Error>>>>,
+    id: usize,
+    span: Span,
 }
 
-impl<'a> Unpin for Put<'a> {}
+impl<'a, C: ContextHandle> Unpin for Put<'a, C> {}
 
-impl<'a> Put<'a> {
+impl<'a, C: ContextHandle> Put<'a, C> {
     pub fn new<R: WriteRequest + ?Sized>(
-        owner: &'a mut Channel,
+        owner: &'a Channel<C>,
         request: &R,
     ) -> Result<Self, Error> {
-        owner
-            .context()
-            .with(|| {
+        let slot = Box::new(Slot::new(None));
+        let span = request_span!("put", owner, R::ID);
+        let result = span.in_scope(|| {
+            owner.context().with(|| {
                 let mut proc = owner.user_data().process.lock().unwrap();
-                result_from_raw(unsafe {
-                    sys::ca_array_put_callback(
+                let id = proc.insert(slot.as_ref() as *const _ as *const u8);
+                match result_from_raw(unsafe {
+                    backend::ca_array_put_callback(
                         R::ID.raw() as _,
                         request.len() as _,
                         owner.raw(),
                         request as *const R as *const _,
                         Some(Self::callback),
-                        proc.id() as _,
+                        id as _,
                     )
-                })
-                .map(|()| {
-                    owner.context().flush_io();
-                    proc.put_res = None;
-                })
+                }) {
+                    Ok(()) => {
+                        owner.context().auto_flush_io();
+                        Ok(id)
+                    }
+                    Err(err) => {
+                        proc.remove(id);
+                        Err(err)
+                    }
+                }
             })
-            .map(|()| Self { owner })
+        });
+        span.in_scope(|| trace::started(&result.map(|_| ())));
+        result.map(|id| Self {
+            owner,
+            slot,
+            id,
+            span,
+        })
+    }
+
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if writing isn't done in `timeout`.
+    ///
+    /// *Note that the write itself can still be done by the server after the timeout.*
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
     }
 
     unsafe extern "C" fn callback(args: sys::event_handler_args) {
-        let user_data = &*(sys::ca_puser(args.chid) as *const UserData);
+        let user_data = &*(backend::ca_puser(args.chid) as *const UserData);
         let mut proc = user_data.process.lock().unwrap();
-        if proc.id() != args.usr as usize {
-            return;
-        }
-        proc.put_res = Some(result_from_raw(args.status));
-        user_data.waker.wake();
+        let id = args.usr as usize;
+        let slot = match proc.get(id) {
+            Some(ptr) => &*(ptr as *const Slot<Option<Result<(), Error>>>),
+            None => return,
+        };
+        proc.remove(id);
+        *slot.state.get() = Some(result_from_raw(args.status));
+        slot.waker.wake();
     }
 }
 
-impl<'a> Future for Put<'a> {
+impl<'a, C: ContextHandle> Future for Put<'a, C> {
     type Output = Result<(), Error>;
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let user_data = self.owner.user_data();
-        user_data.waker.register(cx.waker());
-        let mut proc = user_data.process.lock().unwrap();
-        match proc.put_res.take() {
-            Some(status) => Poll::Ready(status),
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.slot.waker.register(cx.waker());
+        let proc = self.owner.user_data().process.lock().unwrap();
+        let state = unsafe { &mut *self.slot.state.get() };
+        let poll = match state.take() {
+            Some(status) => {
+                self.span.in_scope(|| trace::finished(&status));
+                Poll::Ready(status)
+            }
             None => Poll::Pending,
-        }
+        };
+        drop(proc);
+        poll
     }
 }
 
-impl<'a> Drop for Put<'a> {
+impl<'a, C: ContextHandle> Drop for Put<'a, C> {
     fn drop(&mut self) {
         let mut proc = self.owner.user_data().process.lock().unwrap();
-        proc.change_id();
-        proc.put_res = None;
+        proc.remove(self.id);
     }
 }
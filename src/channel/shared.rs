@@ -0,0 +1,164 @@
+use super::{get::Callback, subscribe::Queue, Channel, Get, Put, Subscription, Timeout};
+use crate::{context::ContextHandle, error::Error};
+use futures::Stream;
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Reference-counted handle to the channel.
+///
+/// Handles can be cloned and used from many tasks concurrently,
+/// because all channel operations take shared reference.
+/// Channel is cleared when the last handle is dropped.
+/// Operations borrow the handle, so channel lives at least until they are done.
+///
+/// Any kind of channel can be shared, e.g. `SharedChannel<ValueChannel<f64>>`.
+///
+/// To make operation that doesn't borrow the handle use [`Self::get_owned`], [`Self::put_owned`] or [`Self::subscribe_owned`].
+#[derive(Debug)]
+pub struct SharedChannel<C = Channel> {
+    inner: Arc<C>,
+}
+
+impl<C> SharedChannel<C> {
+    pub fn new(chan: C) -> Self {
+        Self {
+            inner: Arc::new(chan),
+        }
+    }
+
+    /// Number of handles to the channel.
+    pub fn handle_count(this: &Self) -> usize {
+        Arc::strong_count(&this.inner)
+    }
+
+    /// Get the channel back if this is the only handle.
+    pub fn try_unwrap(this: Self) -> Result<C, Self> {
+        Arc::try_unwrap(this.inner).map_err(|inner| Self { inner })
+    }
+}
+
+impl<C: 'static> SharedChannel<C> {
+    /// Make operation that holds handle to the channel.
+    ///
+    /// # Safety
+    ///
+    /// `op` must be valid for any lifetime of the reference passed to it.
+    unsafe fn owned<T, F: FnOnce(&'static C) -> T>(&self, op: F) -> Owned<C, T> {
+        let chan = self.clone();
+        // Channel is kept alive by the handle stored along with the operation and dropped after it.
+        let op = op(&*Arc::as_ptr(&chan.inner));
+        Owned { op, chan }
+    }
+
+    /// Make read request that holds handle to the channel.
+    ///
+    /// Request is made by `op` from the channel, e.g. `chan.get_owned(|c| c.get())`.
+    pub fn get_owned<F: Callback, X: ContextHandle, O>(&self, op: O) -> Owned<C, Get<'static, F, X>>
+    where
+        O: for<'a> FnOnce(&'a C) -> Get<'a, F, X>,
+    {
+        unsafe { self.owned(op) }
+    }
+
+    /// Make write request that holds handle to the channel.
+    ///
+    /// Request is made by `op` from the channel, e.g. `chan.put_owned(|c| c.put(1.0))`.
+    pub fn put_owned<X: ContextHandle, O>(&self, op: O) -> Result<Owned<C, Put<'static, X>>, Error>
+    where
+        O: for<'a> FnOnce(&'a C) -> Result<Put<'a, X>, Error>,
+    {
+        let Owned { op, chan } = unsafe { self.owned(op) };
+        op.map(|op| Owned { op, chan })
+    }
+
+    /// Make subscription that holds handle to the channel.
+    ///
+    /// Subscription is made by `op` from the channel, e.g. `chan.subscribe_owned(|c| c.subscribe())`.
+    pub fn subscribe_owned<F: Queue, X: ContextHandle, O>(
+        &self,
+        op: O,
+    ) -> Owned<C, Subscription<'static, F, X>>
+    where
+        O: for<'a> FnOnce(&'a C) -> Subscription<'a, F, X>,
+    {
+        unsafe { self.owned(op) }
+    }
+}
+
+impl<C> Clone for SharedChannel<C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C> Deref for SharedChannel<C> {
+    type Target = C;
+    fn deref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> From<C> for SharedChannel<C> {
+    fn from(chan: C) -> Self {
+        Self::new(chan)
+    }
+}
+
+impl<X: ContextHandle> Channel<X> {
+    /// Make reference-counted handle to the channel.
+    pub fn into_shared(self) -> SharedChannel<Self> {
+        SharedChannel::new(self)
+    }
+}
+
+/// Operation that holds handle to its channel instead of borrowing it.
+///
+/// It is `'static`, so it can be spawned or stored along with the channel.
+#[must_use]
+#[pin_project]
+pub struct Owned<C, T> {
+    /// Declared first to be dropped before the channel.
+    #[pin]
+    op: T,
+    chan: SharedChannel<C>,
+}
+
+impl<C, T> Owned<C, T> {
+    /// Handle to the channel of the operation.
+    pub fn channel(&self) -> &SharedChannel<C> {
+        &self.chan
+    }
+}
+
+impl<C, T: Future> Owned<C, T>
+where
+    T::Output: super::timeout::Fallible,
+{
+    /// Fail with [`TIMEOUT`](`crate::error::TIMEOUT`) if operation isn't done in `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Timeout<Self> {
+        Timeout::new(self, timeout)
+    }
+}
+
+impl<C, T: Future> Future for Owned<C, T> {
+    type Output = T::Output;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.project().op.poll(cx)
+    }
+}
+
+impl<C, T: Stream> Stream for Owned<C, T> {
+    type Item = T::Item;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().op.poll_next(cx)
+    }
+}
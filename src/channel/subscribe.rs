@@ -1,18 +1,23 @@
-use super::{base::UserData, Channel};
+use super::{
+    base::{Slot, UserData},
+    Channel,
+};
 use crate::{
+    backend,
+    context::{Context, ContextHandle},
     error::{result_from_raw, Error},
     request::{ReadRequest, Request},
+    trace::{self, event, request_span, Span},
     types::{EventMask, RequestId},
 };
 use futures::Stream;
 use pin_project::{pin_project, pinned_drop};
 use std::{
-    cell::UnsafeCell,
     collections::VecDeque,
     marker::{PhantomData, PhantomPinned},
     pin::Pin,
     ptr,
-    task::{Context, Poll},
+    task::{self, Poll},
 };
 
 /// Subscription queue.
@@ -33,27 +38,33 @@ pub trait Queue: Send {
 ///
 /// Depending on the type of the queue stored subscription may provide
 /// either the last unread value or all received values.
+///
+/// Many subscriptions can exist on the same channel simultaneously,
+/// each with its own event mask, request type and queue.
 #[must_use]
 #[pin_project(PinnedDrop)]
-pub struct Subscription<'a, F: Queue> {
-    owner: &'a mut Channel,
-    /// Must be locked by `owner.user_data().process` mutex
-    state: UnsafeCell<F>,
+pub struct Subscription<'a, F: Queue, C: ContextHandle = Context> {
+    owner: &'a Channel<C>,
+    slot: Slot<F>,
     mask: EventMask,
+    id: Option<usize>,
     evid: Option<sys::evid>,
+    span: Span,
     #[pin]
     _pp: PhantomPinned,
 }
 
-unsafe impl<'a, F: Queue> Send for Subscription<'a, F> {}
+unsafe impl<'a, F: Queue, C: ContextHandle + Sync> Send for Subscription<'a, F, C> {}
 
-impl<'a, F: Queue> Subscription<'a, F> {
-    pub(crate) fn new(owner: &'a mut Channel, func: F) -> Self {
+impl<'a, F: Queue, C: ContextHandle> Subscription<'a, F, C> {
+    pub(crate) fn new(owner: &'a Channel<C>, func: F) -> Self {
         Self {
             owner,
-            state: UnsafeCell::new(func),
+            slot: Slot::new(func),
             mask: EventMask::VALUE | EventMask::ALARM,
+            id: None,
             evid: None,
+            span: request_span!("subscription", owner, F::Request::ID),
             _pp: PhantomPinned,
         }
     }
@@ -68,6 +79,20 @@ impl<'a, F: Queue> Subscription<'a, F> {
         self.mask = mask;
     }
 
+    /// Set kinds of channel events this subscription should be notified, see [`Self::set_event_mask`].
+    pub fn with_event_mask(mut self, mask: EventMask) -> Self {
+        self.set_event_mask(mask);
+        self
+    }
+
+    pub(crate) fn owner(&self) -> &'a Channel<C> {
+        self.owner
+    }
+
+    pub(crate) fn set_event_mask_pinned(self: Pin<&mut Self>, mask: EventMask) {
+        *self.project().mask = mask;
+    }
+
     /// Initiate subscription.
     ///
     /// **You will not receive channel update until this method was called, explicitly or implicitly.**
@@ -77,36 +102,47 @@ impl<'a, F: Queue> Subscription<'a, F> {
     pub fn start(self: Pin<&mut Self>) -> Result<(), Error> {
         assert!(self.evid.is_none());
         let this = self.project();
-        let owner = this.owner;
-        owner.context().with(|| {
+        let owner = *this.owner;
+        let _entered = this.span.enter();
+        let result = owner.context().with(|| {
             let mut proc = owner.user_data().process.lock().unwrap();
-            proc.data = this.state.get() as *mut u8;
+            let id = proc.insert_subscription(this.slot as *const _ as *const u8);
             let mut evid: sys::evid = ptr::null_mut();
-            result_from_raw(unsafe {
-                sys::ca_create_subscription(
+            match result_from_raw(unsafe {
+                backend::ca_create_subscription(
                     F::Request::ID.raw() as _,
                     0,
                     owner.raw(),
                     this.mask.raw() as _,
                     Some(Self::callback),
-                    proc.id() as _,
+                    id as _,
                     &mut evid as *mut sys::evid,
                 )
-            })
-            .map(|()| {
-                owner.context().flush_io();
-                *this.evid = Some(evid);
-            })
-        })
+            }) {
+                Ok(()) => {
+                    owner.context().auto_flush_io();
+                    *this.id = Some(id);
+                    *this.evid = Some(evid);
+                    Ok(())
+                }
+                Err(err) => {
+                    proc.remove(id);
+                    Err(err)
+                }
+            }
+        });
+        trace::started(&result);
+        result
     }
 
     unsafe extern "C" fn callback(args: sys::event_handler_args) {
-        let user_data = &*(sys::ca_puser(args.chid) as *const UserData);
+        let user_data = &*(backend::ca_puser(args.chid) as *const UserData);
         let proc = user_data.process.lock().unwrap();
-        if proc.id() != args.usr as usize {
-            return;
-        }
-        let func = &mut *(proc.data as *mut F);
+        let slot = match proc.get(args.usr as usize) {
+            Some(ptr) => &*(ptr as *const Slot<F>),
+            None => return,
+        };
+        let func = &mut *slot.state.get();
         func.push(result_from_raw(args.status).and_then(|()| {
             F::Request::from_ptr(
                 args.dbr as *const u8,
@@ -115,24 +151,27 @@ impl<'a, F: Queue> Subscription<'a, F> {
             )
         }));
         drop(proc);
-        user_data.waker.wake();
+        slot.waker.wake();
     }
 }
 
-impl<'a, F: Queue> Stream for Subscription<'a, F> {
+impl<'a, F: Queue, C: ContextHandle> Stream for Subscription<'a, F, C> {
     type Item = Result<F::Output, Error>;
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.owner.user_data().waker.register(cx.waker());
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.slot.waker.register(cx.waker());
         if self.evid.is_none() {
             self.start()?;
             return Poll::Pending;
         }
         let this = self.project();
         let proc = this.owner.user_data().process.lock().unwrap();
-        let func = unsafe { &mut *this.state.get() };
+        let func = unsafe { &mut *this.slot.state.get() };
         let poll = match func.pop() {
-            Some(res) => Poll::Ready(Some(res)),
+            Some(res) => {
+                this.span.in_scope(|| trace::updated(&res));
+                Poll::Ready(Some(res))
+            }
             None => Poll::Pending,
         };
         drop(proc);
@@ -141,18 +180,20 @@ impl<'a, F: Queue> Stream for Subscription<'a, F> {
 }
 
 #[pinned_drop]
-impl<'a, F: Queue> PinnedDrop for Subscription<'a, F> {
+impl<'a, F: Queue, C: ContextHandle> PinnedDrop for Subscription<'a, F, C> {
     #[allow(clippy::needless_lifetimes)]
     fn drop(self: Pin<&mut Self>) {
-        let mut proc = self.owner.user_data().process.lock().unwrap();
-        proc.change_id();
-        proc.data = ptr::null_mut();
+        if let Some(id) = self.id {
+            let mut proc = self.owner.user_data().process.lock().unwrap();
+            proc.remove(id);
+        }
+        // Lock is released before clearing because callback may be in progress and waiting for it.
         if let Some(evid) = self.evid {
+            self.span.in_scope(|| event!(trace, "cleared"));
             self.owner.context().with(|| unsafe {
-                result_from_raw(sys::ca_clear_subscription(evid)).unwrap();
+                result_from_raw(backend::ca_clear_subscription(evid)).unwrap();
             });
         }
-        drop(proc);
     }
 }
 
@@ -249,3 +290,117 @@ where
         self.queue.pop_front()
     }
 }
+
+/// What to do when [`BoundedFn`] queue is full and a new value is received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the oldest stored value to store the new one.
+    DropOldest,
+    /// Drop the new value.
+    DropNewest,
+    /// Replace the newest stored value with the new one.
+    Coalesce,
+}
+
+/// Item of bounded subscription queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lossy<T> {
+    pub value: T,
+    /// Number of values that were dropped right before this one because the queue was full.
+    pub dropped: usize,
+}
+
+/// Subscription queue that stores no more than `capacity` received values, applying `F` to them.
+///
+/// If `F` returned `None` the value is filtered out.
+/// When the queue is full values are dropped according to [`Overflow`] policy,
+/// and number of dropped values is reported in the next yielded [`Lossy`] item.
+pub struct BoundedFn<I, O, F = fn(Result<&I, Error>) -> Option<Result<O, Error>>>
+where
+    I: ReadRequest + ?Sized,
+    O: Send,
+    F: FnMut(Result<&I, Error>) -> Option<Result<O, Error>> + Send,
+{
+    func: F,
+    /// Values along with the number of values dropped before each of them.
+    queue: VecDeque<(Result<O, Error>, usize)>,
+    capacity: usize,
+    overflow: Overflow,
+    /// Number of dropped values not yet attached to any stored one.
+    dropped: usize,
+    _p: PhantomData<I>,
+}
+
+impl<I, O, F> BoundedFn<I, O, F>
+where
+    I: ReadRequest + ?Sized,
+    O: Send,
+    F: FnMut(Result<&I, Error>) -> Option<Result<O, Error>> + Send,
+{
+    /// Panics if `capacity` is zero.
+    pub(crate) fn new(f: F, capacity: usize, overflow: Overflow) -> Self {
+        assert!(capacity > 0, "Queue capacity must be non-zero");
+        Self {
+            func: f,
+            queue: VecDeque::with_capacity(capacity),
+            capacity,
+            overflow,
+            dropped: 0,
+            _p: PhantomData,
+        }
+    }
+
+    /// Attach dropped values to the next stored value.
+    fn carry(&mut self, dropped: usize) {
+        match self.queue.front_mut() {
+            Some((_, count)) => *count += dropped,
+            None => self.dropped += dropped,
+        }
+    }
+}
+
+impl<I, O, F> Queue for BoundedFn<I, O, F>
+where
+    I: ReadRequest + ?Sized,
+    O: Send,
+    F: FnMut(Result<&I, Error>) -> Option<Result<O, Error>> + Send,
+{
+    type Request = I;
+    type Output = Lossy<O>;
+    fn push(&mut self, input: Result<&Self::Request, Error>) {
+        let output = match (self.func)(input) {
+            Some(output) => output,
+            None => return,
+        };
+        if self.queue.len() < self.capacity {
+            let dropped = std::mem::take(&mut self.dropped);
+            self.queue.push_back((output, dropped));
+            return;
+        }
+        match self.overflow {
+            Overflow::DropOldest => {
+                let (_, dropped) = self.queue.pop_front().unwrap();
+                self.carry(dropped + 1);
+                let dropped = std::mem::take(&mut self.dropped);
+                self.queue.push_back((output, dropped));
+            }
+            Overflow::DropNewest => self.dropped += 1,
+            Overflow::Coalesce => {
+                let (last, dropped) = self.queue.back_mut().unwrap();
+                *last = output;
+                *dropped += 1;
+            }
+        }
+    }
+    fn pop(&mut self) -> Option<Result<Self::Output, Error>> {
+        let (output, dropped) = self.queue.pop_front()?;
+        Some(match output {
+            Ok(value) => Ok(Lossy { value, dropped }),
+            Err(err) => {
+                // Errors cannot hold the counter, so it is passed to the next value.
+                self.carry(dropped);
+                Err(err)
+            }
+        })
+    }
+}
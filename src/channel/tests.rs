@@ -1,18 +1,25 @@
 use crate::{
-    types::{EpicsEnum, EpicsString},
-    Context,
+    error,
+    request::{CtrlFloat, Time},
+    types::{EpicsEnum, EpicsString, EventMask, FieldId, RequestId},
+    Channel, Context, LocalContext, SharedChannel,
 };
-use async_std::test as async_test;
+use async_std::{task, test as async_test};
 use cstr::cstr;
+use futures::{pin_mut, FutureExt, StreamExt};
 use serial_test::serial;
-use std::f64::consts::{E, PI};
+use std::{
+    f64::consts::{E, PI},
+    thread,
+    time::Duration,
+};
 
 #[async_test]
 #[serial]
 async fn analog() {
     let ctx = Context::new().unwrap();
-    let mut output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
-    let mut input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+    let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+    let input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
 
     output.put(E).unwrap().await.unwrap();
     assert_eq!(input.get().await.unwrap(), E);
@@ -25,8 +32,8 @@ async fn analog() {
 #[serial]
 async fn binary() {
     let ctx = Context::new().unwrap();
-    let mut output = ctx.connect::<EpicsEnum>(cstr!("ca:test:bo")).await.unwrap();
-    let mut input = ctx.connect::<EpicsEnum>(cstr!("ca:test:bi")).await.unwrap();
+    let output = ctx.connect::<EpicsEnum>(cstr!("ca:test:bo")).await.unwrap();
+    let input = ctx.connect::<EpicsEnum>(cstr!("ca:test:bi")).await.unwrap();
 
     output.put(EpicsEnum(1)).unwrap().await.unwrap();
     assert_eq!(input.get().await.unwrap(), EpicsEnum(1));
@@ -39,11 +46,11 @@ async fn binary() {
 #[serial]
 async fn string() {
     let ctx = Context::new().unwrap();
-    let mut output = ctx
+    let output = ctx
         .connect::<EpicsString>(cstr!("ca:test:stringout"))
         .await
         .unwrap();
-    let mut input = ctx
+    let input = ctx
         .connect::<EpicsString>(cstr!("ca:test:stringin"))
         .await
         .unwrap();
@@ -62,8 +69,8 @@ async fn string() {
 async fn array() {
     let ctx = Context::new().unwrap();
     let max_len = 64;
-    let mut output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
-    let mut input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
+    let output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+    let input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
     assert_eq!(output.element_count().unwrap(), max_len);
     assert_eq!(input.element_count().unwrap(), max_len);
 
@@ -78,3 +85,197 @@ async fn array() {
     output.put_ref(&[]).unwrap().await.unwrap();
     assert_eq!(Vec::from(input.get_boxed().await.unwrap()), []);
 }
+
+#[async_test]
+#[serial]
+async fn put_nowait() {
+    let ctx = Context::new().unwrap();
+    let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+    let input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+    let array = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+
+    output.put_nowait(E).unwrap();
+    assert_eq!(input.get().await.unwrap(), E);
+
+    ctx.batch(|| {
+        output.put_nowait(PI).unwrap();
+        array.put_nowait_ref(&[1, 2, 3]).unwrap();
+    });
+    assert_eq!(input.get().await.unwrap(), PI);
+    assert_eq!(array.get_vec().await.unwrap(), [1, 2, 3]);
+}
+
+#[async_test]
+#[serial]
+async fn manual_flush() {
+    let ctx = Context::new().unwrap();
+    assert!(ctx.auto_flush());
+    ctx.set_auto_flush(false);
+    assert!(!ctx.auto_flush());
+
+    let (output, input) = ctx.batch(|| {
+        (
+            Channel::new(&ctx, cstr!("ca:test:ao")).unwrap(),
+            Channel::new(&ctx, cstr!("ca:test:ai")).unwrap(),
+        )
+    });
+    output.connected().await;
+    input.connected().await;
+    let output = output.into_typed::<f64>().unwrap().into_value();
+    let input = input.into_typed::<f64>().unwrap().into_value();
+
+    output.put_nowait(E).unwrap();
+    ctx.flush_io().unwrap();
+    ctx.set_auto_flush(true);
+    assert_eq!(input.get().await.unwrap(), E);
+}
+
+#[test]
+#[serial]
+fn non_preemptive() {
+    let ctx = LocalContext::new().unwrap();
+    assert!(!ctx.is_preemptive());
+    assert_eq!(LocalContext::new().err(), Some(error::ISATTACHED));
+
+    let (output, input) = ctx.block_on(async {
+        (
+            ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap(),
+            ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap(),
+        )
+    });
+    ctx.block_on(output.put(E).unwrap()).unwrap();
+    assert_eq!(ctx.block_on(input.get()).unwrap(), E);
+
+    let mut put = output.put(PI).unwrap();
+    thread::sleep(Duration::from_millis(10));
+    // Callbacks are not called until context is polled.
+    assert!((&mut put).now_or_never().is_none());
+    ctx.pend_event(Duration::from_millis(10)).unwrap();
+    assert!(put.now_or_never().unwrap().is_ok());
+}
+
+#[async_test]
+#[serial]
+async fn shared() {
+    let ctx = Context::new().unwrap();
+    let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+    let input = SharedChannel::new(ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap());
+    output.put(E).unwrap().await.unwrap();
+
+    let handles = (0..4).map(|_| input.clone()).collect::<Vec<_>>();
+    assert_eq!(SharedChannel::handle_count(&input), 5);
+    let tasks = handles
+        .into_iter()
+        .map(|input| task::spawn(async move { input.get().await.unwrap() }))
+        .collect::<Vec<_>>();
+    for task in tasks {
+        assert_eq!(task.await, E);
+    }
+    assert_eq!(SharedChannel::handle_count(&input), 1);
+
+    let other = input.clone();
+    drop(input);
+    assert_eq!(ctx.channels().len(), 2);
+    drop(other);
+    assert_eq!(ctx.channels().len(), 1);
+}
+
+#[async_test]
+#[serial]
+async fn owned() {
+    let ctx = Context::new().unwrap();
+    let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+    let input = SharedChannel::new(ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap());
+    output.put(E).unwrap().await.unwrap();
+
+    let mut monitor = Box::pin(input.subscribe_owned(|c| c.subscribe()));
+    let get = input.get_owned(|c| c.get());
+    drop(input);
+    // Channel is kept by its operations.
+    assert_eq!(ctx.channels().len(), 2);
+    assert_eq!(task::spawn(get).await.unwrap(), E);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), E);
+
+    let monitor = task::spawn(async move { monitor.next().await.unwrap().unwrap() });
+    let output = SharedChannel::new(output);
+    output.put_owned(|c| c.put(PI)).unwrap().await.unwrap();
+    assert_eq!(monitor.await, PI);
+    assert_eq!(ctx.channels().len(), 1);
+}
+
+#[async_test]
+#[serial]
+async fn exception_events() {
+    let ctx = Context::new().unwrap();
+    let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+    output.put(E).unwrap().await.unwrap();
+    let channel = Channel::new(&ctx, cstr!("ca:test:ao")).unwrap();
+    channel.connected().await;
+
+    let events = ctx.exception_events();
+    pin_mut!(events);
+    let value = EpicsString::from_cstr(cstr!("abc")).unwrap();
+    channel.put_nowait_ref(&value).unwrap();
+    let event = events.next().await.unwrap();
+    assert_eq!(event.error, error::PUTFAIL);
+    assert_eq!(event.channel.as_deref(), Some(cstr!("ca:test:ao")));
+    assert_eq!(event.request, Some(RequestId::Base(FieldId::String)));
+    assert_eq!(event.count, 1);
+    assert_eq!(output.get().await.unwrap(), E);
+}
+
+#[async_test]
+#[serial]
+async fn introspection() {
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+    let missing = Channel::new(&ctx, cstr!("ca:test:missing")).unwrap();
+
+    let monitor = channel.subscribe();
+    pin_mut!(monitor);
+    monitor.next().await.unwrap().unwrap();
+
+    let infos = ctx.channels();
+    assert_eq!(infos.len(), 2);
+    let info = &infos[0];
+    assert_eq!(info.name.as_c_str(), cstr!("ca:test:ai"));
+    assert!(info.connected);
+    assert!(info.host.is_some());
+    assert_eq!(info.field_type, Some(FieldId::Double));
+    assert_eq!(info.element_count, Some(1));
+    assert_eq!((info.subscriptions, info.pending), (1, 0));
+    let info = &infos[1];
+    assert_eq!(info.name.as_c_str(), missing.name());
+    assert!(!info.connected);
+    assert_eq!((info.host.as_ref(), info.field_type), (None, None));
+
+    drop(missing);
+    assert_eq!(ctx.channels().len(), 1);
+    ctx.client_status(1).unwrap();
+}
+
+#[async_test]
+#[serial]
+async fn subscriptions_with_different_masks() {
+    let ctx = Context::new().unwrap();
+    let channel = Channel::new(&ctx, cstr!("ca:test:ao")).unwrap();
+    channel.connected().await;
+    let channel = channel.into_typed::<f64>().unwrap();
+
+    let values = channel
+        .subscribe_buffered::<Time<f64>>()
+        .with_event_mask(EventMask::VALUE);
+    let props = channel
+        .subscribe_buffered::<CtrlFloat<f64>>()
+        .with_event_mask(EventMask::PROPERTY);
+    pin_mut!(values);
+    pin_mut!(props);
+    let value = values.next().await.unwrap().unwrap().value;
+    assert_eq!(props.next().await.unwrap().unwrap().value, value);
+
+    channel.put(value + 1.0).unwrap().await.unwrap();
+    assert_eq!(values.next().await.unwrap().unwrap().value, value + 1.0);
+    // Value change is not a property change.
+    channel.get::<f64>().await.unwrap();
+    assert!(props.next().now_or_never().is_none());
+}
@@ -0,0 +1,93 @@
+use crate::error::{self, Error};
+use futures::future::FusedFuture;
+use futures_timer::Delay;
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Output of the future that can be combined with timeout error.
+pub trait Fallible {
+    type Ok;
+    fn into_result(self) -> Result<Self::Ok, Error>;
+}
+
+impl Fallible for () {
+    type Ok = ();
+    fn into_result(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<T> Fallible for Result<T, Error> {
+    type Ok = T;
+    fn into_result(self) -> Result<T, Error> {
+        self
+    }
+}
+
+/// Future that completes with [`error::TIMEOUT`] if inner future isn't done in time.
+///
+/// When timeout expires the inner future is dropped immediately,
+/// so pending operation is cancelled and its callback is ignored.
+///
+/// Timer doesn't depend on any particular async runtime.
+#[must_use]
+#[pin_project]
+pub struct Timeout<F: Future>
+where
+    F::Output: Fallible,
+{
+    #[pin]
+    future: Option<F>,
+    #[pin]
+    delay: Delay,
+}
+
+impl<F: Future> Timeout<F>
+where
+    F::Output: Fallible,
+{
+    pub fn new(future: F, timeout: Duration) -> Self {
+        Self {
+            future: Some(future),
+            delay: Delay::new(timeout),
+        }
+    }
+}
+
+impl<F: Future> Future for Timeout<F>
+where
+    F::Output: Fallible,
+{
+    type Output = Result<<F::Output as Fallible>::Ok, Error>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let future = this
+            .future
+            .as_mut()
+            .as_pin_mut()
+            .expect("Timeout polled after completion");
+        if let Poll::Ready(output) = future.poll(cx) {
+            this.future.set(None);
+            return Poll::Ready(output.into_result());
+        }
+        if let Poll::Ready(()) = this.delay.poll(cx) {
+            this.future.set(None);
+            return Poll::Ready(Err(error::TIMEOUT));
+        }
+        Poll::Pending
+    }
+}
+
+impl<F: Future> FusedFuture for Timeout<F>
+where
+    F::Output: Fallible,
+{
+    fn is_terminated(&self) -> bool {
+        self.future.is_none()
+    }
+}
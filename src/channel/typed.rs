@@ -1,9 +1,10 @@
 use super::{
     get::Callback,
-    subscribe::{LastFn, Queue, QueueFn},
+    subscribe::{BoundedFn, LastFn, Overflow, Queue, QueueFn},
     Channel, Get, GetFn, Put, Subscription,
 };
 use crate::{
+    context::{Context, ContextHandle},
     error::{self, Error},
     request::{ReadRequest, Request, TypedRequest, WriteRequest},
     types::{Field, Value},
@@ -15,8 +16,8 @@ use std::{
     marker::PhantomData,
 };
 
-impl Channel {
-    fn check_type<V: Value + ?Sized>(&self) -> Result<(), Error> {
+impl<C: ContextHandle> Channel<C> {
+    pub(crate) fn check_type<V: Value + ?Sized>(&self) -> Result<(), Error> {
         if <V::Item as Field>::ID != self.field_type()? {
             Err(error::BADTYPE)
         } else if !V::check_len(self.element_count()?) {
@@ -26,15 +27,38 @@ impl Channel {
         }
     }
 
+    fn check_conversion<V: Value + ?Sized>(&self) -> Result<(), Error> {
+        if !self.field_type()?.can_convert_to(<V::Item as Field>::ID) {
+            Err(error::BADTYPE)
+        } else if !V::check_len(self.element_count()?) {
+            Err(error::BADCOUNT)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Convert into [`TypedChannel`].
     ///
     /// Conversion is successful if actual channel type matches the one passed as a parameter `V`.
-    pub fn into_typed<V: Value + ?Sized>(self) -> Result<TypedChannel<V>, (Error, Self)> {
+    pub fn into_typed<V: Value + ?Sized>(self) -> Result<TypedChannel<V, C>, (Error, Self)> {
         match self.check_type::<V>() {
             Ok(()) => Ok(TypedChannel::new_unchecked(self)),
             Err(err) => Err((err, self)),
         }
     }
+
+    /// Convert into [`TypedChannel`] which requests are converted by server to `V`.
+    ///
+    /// Unlike [`Self::into_typed`] the native channel type may differ from `V`,
+    /// it is only required that Channel Access is able to convert between them
+    /// (see [`FieldId::can_convert_to`](`crate::types::FieldId::can_convert_to`)).
+    /// E.g. `into_converting::<f64>()` accepts any numeric channel.
+    pub fn into_converting<V: Value + ?Sized>(self) -> Result<TypedChannel<V, C>, (Error, Self)> {
+        match self.check_conversion::<V>() {
+            Ok(()) => Ok(TypedChannel::new_unchecked(self)),
+            Err(err) => Err((err, self)),
+        }
+    }
 }
 
 /// Typed channel.
@@ -42,21 +66,21 @@ impl Channel {
 /// Used to make typed requests, e.g. such requests that contains typed value.
 #[repr(transparent)]
 #[derive(Deref, DerefMut, Into)]
-pub struct TypedChannel<V: Value + ?Sized> {
+pub struct TypedChannel<V: Value + ?Sized, C: ContextHandle = Context> {
     #[deref]
     #[deref_mut]
-    pub(crate) base: Channel,
+    pub(crate) base: Channel<C>,
     #[into(ignore)]
     _p: PhantomData<V>,
 }
 
-impl<V: Value + ?Sized> TypedChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> TypedChannel<V, C> {
     /// Convert [`Channel`] to [`TypedChannel<V>`] without type checking.
     ///
     /// It is safe because the type of remote channel can change at any moment and checks are done during reading/writing/monitoring anyway.
     ///
     /// If you want to check type before converting use [`Channel::into_typed`].
-    pub fn new_unchecked(base: Channel) -> Self {
+    pub fn new_unchecked(base: Channel<C>) -> Self {
         Self {
             base,
             _p: PhantomData,
@@ -64,23 +88,31 @@ impl<V: Value + ?Sized> TypedChannel<V> {
     }
 }
 
-impl<V: Value + ?Sized> Debug for TypedChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> Debug for TypedChannel<V, C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "TypedChannel<{}>({:?})", type_name::<V>(), self.raw())
     }
 }
 
-impl<V: Value + ?Sized> TypedChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> TypedChannel<V, C> {
     /// Make write request by reference.
-    pub fn put_ref<R>(&mut self, req: &R) -> Result<Put<'_>, Error>
+    pub fn put_ref<R>(&self, req: &R) -> Result<Put<'_, C>, Error>
     where
         R: TypedRequest<Value = V> + WriteRequest + ?Sized,
     {
         self.base.put_ref::<R>(req)
     }
 
+    /// Make write request by reference without waiting for it to be done.
+    pub fn put_nowait_ref<R>(&self, req: &R) -> Result<(), Error>
+    where
+        R: TypedRequest<Value = V> + WriteRequest + ?Sized,
+    {
+        self.base.put_nowait_ref::<R>(req)
+    }
+
     /// Make read request and call closure when it's done, successfully or not.
-    pub fn get_with<R, F>(&mut self, func: F) -> Get<'_, F>
+    pub fn get_with<R, F>(&self, func: F) -> Get<'_, F, C>
     where
         R: TypedRequest<Value = V> + ReadRequest + ?Sized,
         F: Callback<Request = R>,
@@ -89,7 +121,7 @@ impl<V: Value + ?Sized> TypedChannel<V> {
     }
 
     /// Subscribe to channel updates and call closure each time when update occured.
-    pub fn subscribe_with<F: Queue>(&mut self, func: F) -> Subscription<'_, F>
+    pub fn subscribe_with<F: Queue>(&self, func: F) -> Subscription<'_, F, C>
     where
         F::Request: TypedRequest<Value = V> + ReadRequest,
     {
@@ -97,9 +129,9 @@ impl<V: Value + ?Sized> TypedChannel<V> {
     }
 }
 
-impl<T: Field> TypedChannel<[T]> {
+impl<T: Field, C: ContextHandle> TypedChannel<[T], C> {
     /// Make read request and obtain boxed response.
-    pub fn get_boxed<R>(&mut self) -> Get<'_, GetFn<R, Box<R>>>
+    pub fn get_boxed<R>(&self) -> Get<'_, GetFn<R, Box<R>>, C>
     where
         R: TypedRequest<Value = [T]> + ReadRequest + ?Sized,
     {
@@ -107,7 +139,7 @@ impl<T: Field> TypedChannel<[T]> {
     }
 
     /// Subscribe to channel updates and obtain stream that provides boxed responses.
-    pub fn subscribe_boxed<R>(&mut self) -> Subscription<'_, LastFn<R, Box<R>>>
+    pub fn subscribe_boxed<R>(&self) -> Subscription<'_, LastFn<R, Box<R>>, C>
     where
         R: TypedRequest<Value = [T]> + ReadRequest + ?Sized,
     {
@@ -115,17 +147,25 @@ impl<T: Field> TypedChannel<[T]> {
     }
 }
 
-impl<T: Field> TypedChannel<T> {
+impl<T: Field, C: ContextHandle> TypedChannel<T, C> {
     /// Write scalar request.
-    pub fn put<R>(&mut self, req: R) -> Result<Put<'_>, Error>
+    pub fn put<R>(&self, req: R) -> Result<Put<'_, C>, Error>
     where
         R: TypedRequest<Value = T> + WriteRequest,
     {
         self.put_ref::<R>(&req)
     }
 
+    /// Write scalar request without waiting for it to be done.
+    pub fn put_nowait<R>(&self, req: R) -> Result<(), Error>
+    where
+        R: TypedRequest<Value = T> + WriteRequest,
+    {
+        self.put_nowait_ref::<R>(&req)
+    }
+
     /// Get result of scalar read request.
-    pub fn get<R>(&mut self) -> Get<'_, GetFn<R, R>>
+    pub fn get<R>(&self) -> Get<'_, GetFn<R, R>, C>
     where
         R: TypedRequest<Value = T> + ReadRequest + Copy,
     {
@@ -136,7 +176,7 @@ impl<T: Field> TypedChannel<T> {
     ///
     /// Note, that returned stream stores only last unread value.
     /// To store all values use [`Self::subscribe_buffered`].
-    pub fn subscribe<R>(&mut self) -> Subscription<'_, LastFn<R, R>>
+    pub fn subscribe<R>(&self) -> Subscription<'_, LastFn<R, R>, C>
     where
         R: TypedRequest<Value = T> + ReadRequest + Copy,
     {
@@ -147,12 +187,30 @@ impl<T: Field> TypedChannel<T> {
     ///
     /// This subscription contains internal buffer that can grow up to arbitrary size
     /// especially in case of frequent channel updates.
-    pub fn subscribe_buffered<R>(&mut self) -> Subscription<'_, QueueFn<R, R>>
+    /// To limit its size use [`Self::subscribe_bounded`].
+    pub fn subscribe_buffered<R>(&self) -> Subscription<'_, QueueFn<R, R>, C>
     where
         R: TypedRequest<Value = T> + ReadRequest + Copy,
     {
         self.subscribe_with(QueueFn::<R, R>::new(copy_some::<R>))
     }
+
+    /// Subscribe to updates of scalar channel and store no more than `capacity` updates.
+    ///
+    /// When the buffer is full updates are dropped according to `overflow` policy.
+    /// Number of dropped updates is reported in stream items.
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn subscribe_bounded<R>(
+        &self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Subscription<'_, BoundedFn<R, R>, C>
+    where
+        R: TypedRequest<Value = T> + ReadRequest + Copy,
+    {
+        self.subscribe_with(BoundedFn::<R, R>::new(copy_some::<R>, capacity, overflow))
+    }
 }
 
 fn clone_boxed<R: Request + ?Sized>(input: Result<&R, Error>) -> Result<Box<R>, Error> {
@@ -175,7 +233,7 @@ fn copy_some<R: Copy>(input: Result<&R, Error>) -> Option<Result<R, Error>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Channel, Context};
+    use crate::{error, types::EpicsString, Channel, Context};
     use async_std::test as async_test;
     use cstr::cstr;
     use serial_test::serial;
@@ -184,9 +242,44 @@ mod tests {
     #[serial]
     async fn downcast() {
         let ctx = Context::new().unwrap();
-        let mut base = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
+        let base = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
         base.connected().await;
         let base = base.into_typed::<u8>().unwrap_err().1;
         base.into_typed::<f64>().unwrap();
     }
+
+    #[async_test]
+    #[serial]
+    async fn converting() {
+        let ctx = Context::new().unwrap();
+        let base = Channel::new(&ctx, cstr!("ca:test:stringin")).unwrap();
+        base.connected().await;
+        let (err, base) = base.into_converting::<f64>().unwrap_err();
+        assert_eq!(err, error::BADTYPE);
+        base.into_converting::<EpicsString>().unwrap();
+
+        let output = Channel::new(&ctx, cstr!("ca:test:ao")).unwrap();
+        output.connected().await;
+        let output = output.into_converting::<i32>().unwrap().into_value();
+        let input = Channel::new(&ctx, cstr!("ca:test:ai")).unwrap();
+        input.connected().await;
+        let input = input.into_converting::<EpicsString>().unwrap().into_value();
+        output.put(42).unwrap().await.unwrap();
+        assert_eq!(input.get().await.unwrap().to_str().unwrap(), "42");
+    }
+
+    #[async_test]
+    #[serial]
+    async fn converting_array() {
+        let ctx = Context::new().unwrap();
+        let output = Channel::new(&ctx, cstr!("ca:test:aao")).unwrap();
+        output.connected().await;
+        let output = output.into_converting::<[f64]>().unwrap().into_value();
+        let input = Channel::new(&ctx, cstr!("ca:test:aai")).unwrap();
+        input.connected().await;
+        let input = input.into_converting::<[i16]>().unwrap().into_value();
+
+        output.put_ref(&[1.0, -2.0, 3.0]).unwrap().await.unwrap();
+        assert_eq!(input.get_vec().await.unwrap(), [1, -2, 3]);
+    }
 }
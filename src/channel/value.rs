@@ -1,10 +1,11 @@
 use super::{
     get::Callback,
-    subscribe::{LastFn, Queue, QueueFn},
+    subscribe::{BoundedFn, LastFn, Overflow, Queue, QueueFn},
     typed::TypedChannel,
     Get, GetFn, Put, Subscription,
 };
 use crate::{
+    context::{Context, ContextHandle},
     error::Error,
     request::Request,
     types::{Field, Value},
@@ -15,8 +16,8 @@ use std::{
     fmt::{self, Debug},
 };
 
-impl<V: Value + ?Sized> TypedChannel<V> {
-    pub fn into_value(self) -> ValueChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> TypedChannel<V, C> {
+    pub fn into_value(self) -> ValueChannel<V, C> {
         ValueChannel::from(self)
     }
 }
@@ -24,24 +25,29 @@ impl<V: Value + ?Sized> TypedChannel<V> {
 /// Channel used to read and write only value rather than other requests.
 #[repr(transparent)]
 #[derive(From, Into, Deref, DerefMut)]
-pub struct ValueChannel<V: Value + ?Sized> {
-    pub(crate) typed: TypedChannel<V>,
+pub struct ValueChannel<V: Value + ?Sized, C: ContextHandle = Context> {
+    pub(crate) typed: TypedChannel<V, C>,
 }
 
-impl<V: Value + ?Sized> Debug for ValueChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> Debug for ValueChannel<V, C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "ValueChannel<{}>({:?})", type_name::<V>(), self.raw())
     }
 }
 
-impl<V: Value + ?Sized> ValueChannel<V> {
+impl<V: Value + ?Sized, C: ContextHandle> ValueChannel<V, C> {
     /// Write value by reference to the channel.
-    pub fn put_ref(&mut self, data: &V) -> Result<Put<'_>, Error> {
+    pub fn put_ref(&self, data: &V) -> Result<Put<'_, C>, Error> {
         self.typed.put_ref::<V>(data)
     }
 
+    /// Write value by reference without waiting for it to be done.
+    pub fn put_nowait_ref(&self, data: &V) -> Result<(), Error> {
+        self.typed.put_nowait_ref::<V>(data)
+    }
+
     /// Request value from the channel and call callback when it's done.
-    pub fn get_with<F>(&mut self, func: F) -> Get<'_, F>
+    pub fn get_with<F>(&self, func: F) -> Get<'_, F, C>
     where
         F: Callback<Request = V>,
     {
@@ -49,52 +55,68 @@ impl<V: Value + ?Sized> ValueChannel<V> {
     }
 
     /// Subscribe to value updates and call closure each time when update occured.
-    pub fn subscribe_with<F: Queue<Request = V>>(&mut self, func: F) -> Subscription<'_, F> {
+    pub fn subscribe_with<F: Queue<Request = V>>(&self, func: F) -> Subscription<'_, F, C> {
         self.typed.subscribe_with(func)
     }
 }
 
-impl<T: Field> ValueChannel<[T]> {
+impl<T: Field, C: ContextHandle> ValueChannel<[T], C> {
     /// Request array value and store it in [`Vec`].
-    pub fn get_vec(&mut self) -> Get<'_, GetFn<[T], Vec<T>>> {
+    pub fn get_vec(&self) -> Get<'_, GetFn<[T], Vec<T>>, C> {
         self.get_with(GetFn::<[T], Vec<T>>::new(clone_vec::<T>))
     }
 
     /// Write value to slice and return received value length (which may be greater than `dst` length).
-    pub fn get_to_slice<'a, 'b>(&'a mut self, dst: &'b mut [T]) -> Get<'a, GetToSlice<'b, T>> {
+    pub fn get_to_slice<'a, 'b>(&'a self, dst: &'b mut [T]) -> Get<'a, GetToSlice<'b, T>, C> {
         self.get_with(GetToSlice { dst })
     }
 
     /// Subscribe to array value updates and obtain [`Vec`] stream.
-    pub fn subscribe_vec(&mut self) -> Subscription<'_, LastFn<[T], Vec<T>>> {
+    pub fn subscribe_vec(&self) -> Subscription<'_, LastFn<[T], Vec<T>>, C> {
         self.subscribe_with(LastFn::<[T], Vec<T>>::new(clone_vec_some::<T>))
     }
 }
 
-impl<T: Field> ValueChannel<T> {
+impl<T: Field, C: ContextHandle> ValueChannel<T, C> {
     /// Write scalar value.
-    pub fn put(&mut self, val: T) -> Result<Put<'_>, Error> {
+    pub fn put(&self, val: T) -> Result<Put<'_, C>, Error> {
         self.typed.put::<T>(val)
     }
 
+    /// Write scalar value without waiting for it to be done.
+    pub fn put_nowait(&self, val: T) -> Result<(), Error> {
+        self.typed.put_nowait::<T>(val)
+    }
+
     /// Get scalar value.
-    pub fn get(&mut self) -> Get<'_, GetFn<T, T>> {
+    pub fn get(&self) -> Get<'_, GetFn<T, T>, C> {
         self.typed.get::<T>()
     }
 
     /// Subscribe to updates of scalar value.
     ///
     /// See [`TypedChannel::subscribe`].
-    pub fn subscribe(&mut self) -> Subscription<'_, LastFn<T, T>> {
+    pub fn subscribe(&self) -> Subscription<'_, LastFn<T, T>, C> {
         self.typed.subscribe::<T>()
     }
 
     /// Subscribe to updates of scalar value and store all updates.
     ///
     /// See [`TypedChannel::subscribe_buffered`].
-    pub fn subscribe_buffered(&mut self) -> Subscription<'_, QueueFn<T, T>> {
+    pub fn subscribe_buffered(&self) -> Subscription<'_, QueueFn<T, T>, C> {
         self.typed.subscribe_buffered::<T>()
     }
+
+    /// Subscribe to updates of scalar value and store no more than `capacity` updates.
+    ///
+    /// See [`TypedChannel::subscribe_bounded`].
+    pub fn subscribe_bounded(
+        &self,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Subscription<'_, BoundedFn<T, T>, C> {
+        self.typed.subscribe_bounded::<T>(capacity, overflow)
+    }
 }
 
 fn clone_vec<T: Field>(input: Result<&[T], Error>) -> Result<Vec<T>, Error> {
@@ -123,22 +145,29 @@ impl<'a, T: Field> Callback for GetToSlice<'a, T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::Context;
+    use crate::{
+        channel::Timeout,
+        error::{self, Error},
+        Channel, Context,
+    };
     use async_std::{task::sleep, test as async_test};
     use cstr::cstr;
-    use futures::{join, pin_mut, StreamExt};
+    use futures::{future, join, pin_mut, StreamExt};
     use serial_test::serial;
-    use std::{f64::consts::PI, time::Duration};
+    use std::{
+        f64::consts::{E, PI},
+        time::Duration,
+    };
 
     #[async_test]
     #[serial]
     async fn put_get_scalar() {
         let ctx = Context::new().unwrap();
 
-        let mut output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+        let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
         output.put(PI).unwrap().await.unwrap();
 
-        let mut input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+        let input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
         assert_eq!(input.get().await.unwrap(), PI);
     }
 
@@ -147,8 +176,8 @@ mod tests {
     async fn subscribe_buffered() {
         let ctx = Context::new().unwrap();
 
-        let mut output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
-        let mut input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+        let output = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+        let input = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
 
         output.put(0.0).unwrap().await.unwrap();
         let monitor = input.subscribe_buffered();
@@ -174,13 +203,60 @@ mod tests {
         );
     }
 
+    #[async_test]
+    #[serial]
+    async fn concurrent_operations() {
+        let ctx = Context::new().unwrap();
+        let channel = ctx.connect::<f64>(cstr!("ca:test:ao")).await.unwrap();
+
+        channel.put(0.0).unwrap().await.unwrap();
+        let monitor = channel.subscribe_buffered();
+        pin_mut!(monitor);
+        assert_eq!(monitor.next().await.unwrap().unwrap(), 0.0);
+
+        let (first, second) = join!(channel.put(E).unwrap(), channel.put(PI).unwrap());
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(monitor.next().await.unwrap().unwrap(), E);
+        assert_eq!(monitor.next().await.unwrap().unwrap(), PI);
+
+        let (first, second) = join!(channel.get(), channel.get());
+        assert_eq!(first.unwrap(), PI);
+        assert_eq!(second.unwrap(), PI);
+    }
+
+    #[async_test]
+    #[serial]
+    async fn get_timeout() {
+        let never = future::pending::<Result<(), Error>>();
+        assert_eq!(
+            Timeout::new(never, Duration::from_millis(10))
+                .await
+                .unwrap_err(),
+            error::TIMEOUT
+        );
+
+        let ctx = Context::new().unwrap();
+        let missing = Channel::new(&ctx, cstr!("__nonexistent__")).unwrap();
+        assert_eq!(
+            missing
+                .connected_timeout(Duration::from_millis(10))
+                .await
+                .unwrap_err(),
+            error::TIMEOUT
+        );
+
+        let channel = ctx.connect::<f64>(cstr!("ca:test:ai")).await.unwrap();
+        channel.get().timeout(Duration::from_secs(1)).await.unwrap();
+    }
+
     #[async_test]
     #[serial]
     async fn put_get_array() {
         let ctx = Context::new().unwrap();
 
-        let mut output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
-        let mut input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
+        let output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+        let input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
 
         let data = (0..8).collect::<Vec<i32>>();
         output.put_ref(&data).unwrap().await.unwrap();
@@ -192,8 +268,8 @@ mod tests {
     async fn subscribe_array() {
         let ctx = Context::new().unwrap();
 
-        let mut output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
-        let mut input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
+        let output = ctx.connect::<[i32]>(cstr!("ca:test:aao")).await.unwrap();
+        let input = ctx.connect::<[i32]>(cstr!("ca:test:aai")).await.unwrap();
 
         output.put_ref(&[-1]).unwrap().await.unwrap();
         let monitor = input.subscribe_vec();
@@ -0,0 +1,169 @@
+use super::{Context, LocalContext, UniqueContext};
+use crate::error::Error;
+use std::time::Duration;
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+use std::{env, sync::Mutex};
+
+/// Settings of the context.
+///
+/// Settings that are `None` are taken from standard `EPICS_CA_*` environment variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextConfig {
+    /// Addresses to search channels at (`EPICS_CA_ADDR_LIST`).
+    pub addr_list: Option<Vec<String>>,
+    /// Whether to add broadcast addresses of local interfaces to search addresses (`EPICS_CA_AUTO_ADDR_LIST`).
+    pub auto_addr_list: Option<bool>,
+    /// Servers to search channels at over TCP (`EPICS_CA_NAME_SERVERS`).
+    pub name_servers: Option<Vec<String>>,
+    /// Time after which unresponsive server is considered disconnected (`EPICS_CA_CONN_TMO`).
+    pub conn_timeout: Option<Duration>,
+    /// Maximum size of array in bytes (`EPICS_CA_MAX_ARRAY_BYTES`).
+    pub max_array_bytes: Option<usize>,
+    /// Default port of servers (`EPICS_CA_SERVER_PORT`).
+    pub server_port: Option<u16>,
+    /// Whether callbacks are called from auxiliary threads at any time, disabled only for [`LocalContext`].
+    pub preemptive: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            addr_list: None,
+            auto_addr_list: None,
+            name_servers: None,
+            conn_timeout: None,
+            max_array_bytes: None,
+            server_port: None,
+            preemptive: true,
+        }
+    }
+}
+
+/// Serializes temporary changes of environment made for EPICS CA library.
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(all(feature = "libca", not(any(feature = "pure-rust", feature = "mock"))))]
+impl ContextConfig {
+    /// Environment variables that represent settings.
+    fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = Vec::new();
+        if let Some(addr_list) = &self.addr_list {
+            vars.push(("EPICS_CA_ADDR_LIST", addr_list.join(" ")));
+        }
+        if let Some(auto) = self.auto_addr_list {
+            vars.push((
+                "EPICS_CA_AUTO_ADDR_LIST",
+                if auto { "YES" } else { "NO" }.into(),
+            ));
+        }
+        if let Some(name_servers) = &self.name_servers {
+            vars.push(("EPICS_CA_NAME_SERVERS", name_servers.join(" ")));
+        }
+        if let Some(timeout) = self.conn_timeout {
+            vars.push(("EPICS_CA_CONN_TMO", timeout.as_secs_f64().to_string()));
+        }
+        if let Some(bytes) = self.max_array_bytes {
+            vars.push(("EPICS_CA_MAX_ARRAY_BYTES", bytes.to_string()));
+        }
+        if let Some(port) = self.server_port {
+            vars.push(("EPICS_CA_SERVER_PORT", port.to_string()));
+        }
+        vars
+    }
+
+    /// Call `f` with environment variables set according to the settings and restore them afterwards.
+    ///
+    /// EPICS CA library reads settings from environment when context and its first channel are created,
+    /// so calls that create them are made with the lock held and don't see settings of other contexts.
+    pub(crate) fn with_env<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let prev = self
+            .vars()
+            .into_iter()
+            .map(|(name, value)| {
+                let prev = env::var_os(name);
+                env::set_var(name, value);
+                (name, prev)
+            })
+            .collect::<Vec<_>>();
+        let ret = f();
+        for (name, prev) in prev {
+            match prev {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+        ret
+    }
+}
+
+/// Builder of the context with specific settings.
+///
+/// Settings are applied only to the context being created, so contexts with different settings can coexist.
+/// Pure-Rust backend uses only address list, auto address list, connection timeout, maximum array size and server port.
+///
+/// *Note that EPICS CA library takes settings from environment variables only,
+/// so with `libca` feature they are set temporarily while the context and its channels are created.
+/// Other code that reads the environment at the same time (e.g. from another thread) may see them.*
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct ContextBuilder {
+    config: ContextConfig,
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set addresses to search channels at.
+    pub fn addr_list<I: IntoIterator<Item = S>, S: Into<String>>(mut self, addrs: I) -> Self {
+        self.config.addr_list = Some(addrs.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set whether to add broadcast addresses of local interfaces to search addresses.
+    pub fn auto_addr_list(mut self, enabled: bool) -> Self {
+        self.config.auto_addr_list = Some(enabled);
+        self
+    }
+    /// Set servers to search channels at over TCP.
+    pub fn name_servers<I: IntoIterator<Item = S>, S: Into<String>>(mut self, addrs: I) -> Self {
+        self.config.name_servers = Some(addrs.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Set time after which unresponsive server is considered disconnected.
+    pub fn conn_timeout(mut self, timeout: Duration) -> Self {
+        self.config.conn_timeout = Some(timeout);
+        self
+    }
+    /// Set maximum size of array in bytes.
+    pub fn max_array_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_array_bytes = Some(bytes);
+        self
+    }
+    /// Set default port of servers.
+    pub fn server_port(mut self, port: u16) -> Self {
+        self.config.server_port = Some(port);
+        self
+    }
+
+    /// Settings of the context.
+    pub fn config(&self) -> &ContextConfig {
+        &self.config
+    }
+
+    /// Create unique context.
+    pub fn build_unique(self) -> Result<UniqueContext, Error> {
+        UniqueContext::create(self.config)
+    }
+    /// Create shared context.
+    pub fn build(self) -> Result<Context, Error> {
+        self.build_unique().map(Context::from)
+    }
+    /// Create non-preemptive context bound to the current thread.
+    pub fn build_local(mut self) -> Result<LocalContext, Error> {
+        self.config.preemptive = false;
+        LocalContext::create(self.config)
+    }
+}
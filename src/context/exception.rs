@@ -0,0 +1,110 @@
+use super::UniqueContext;
+use crate::{backend, channel::connection::Listeners, error::Error, types::RequestId};
+use futures::Stream;
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+/// Asynchronous error reported by Channel Access, e.g. rejected write without callback or unresponsive server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionEvent {
+    pub error: Error,
+    /// Name of the channel the exception is related to.
+    pub channel: Option<CString>,
+    /// Type of the request, if exception is caused by one.
+    pub request: Option<RequestId>,
+    /// Number of elements of the request.
+    pub count: usize,
+    /// Description of the exception.
+    pub message: String,
+    /// Source file where exception is raised.
+    pub file: Option<String>,
+    pub line: u32,
+}
+
+impl ExceptionEvent {
+    unsafe fn from_raw(args: &sys::exception_handler_args) -> Option<Self> {
+        let string = |ptr: *const c_char| {
+            (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        };
+        Some(Self {
+            error: Error::try_from_raw(args.stat as i32)?,
+            channel: (!args.chid.is_null())
+                .then(|| CStr::from_ptr(backend::ca_name(args.chid)).to_owned()),
+            request: RequestId::try_from_raw(args.type_ as i32),
+            count: args.count.max(0) as usize,
+            message: string(args.ctx).unwrap_or_default(),
+            file: string(args.pFile),
+            line: args.lineNo,
+        })
+    }
+}
+
+/// Listeners of exceptions of the context.
+pub(crate) type ExceptionData = Mutex<Listeners<ExceptionEvent>>;
+
+pub(crate) unsafe extern "C" fn exception_handler(args: sys::exception_handler_args) {
+    let data = &*(args.usr as *const ExceptionData);
+    let event = match ExceptionEvent::from_raw(&args) {
+        Some(event) => event,
+        None => return,
+    };
+    let mut listeners = data.lock().unwrap();
+    if listeners.is_empty() {
+        // Nobody listens, so report it like EPICS CA library does.
+        #[cfg(feature = "tracing")]
+        tracing::warn!(?event, "CA client exception");
+        #[cfg(not(feature = "tracing"))]
+        eprintln!("CA client exception: {:?}", event);
+    } else {
+        listeners.notify(event);
+    }
+}
+
+impl UniqueContext {
+    /// Stream of exceptions reported by Channel Access.
+    ///
+    /// While there are no such streams, exceptions are printed to stderr.
+    /// Only exceptions that occured after the stream was created are reported.
+    pub fn exception_events(&self) -> ExceptionEvents<'_> {
+        ExceptionEvents::new(self)
+    }
+}
+
+/// Stream of context exceptions.
+#[must_use]
+pub struct ExceptionEvents<'a> {
+    owner: &'a UniqueContext,
+    id: usize,
+}
+
+impl<'a> ExceptionEvents<'a> {
+    fn new(owner: &'a UniqueContext) -> Self {
+        let id = owner.exceptions.lock().unwrap().insert(true);
+        Self { owner, id }
+    }
+}
+
+impl<'a> Stream for ExceptionEvents<'a> {
+    type Item = ExceptionEvent;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut exceptions = self.owner.exceptions.lock().unwrap();
+        match exceptions.pop(self.id) {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                exceptions.register(self.id, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a> Drop for ExceptionEvents<'a> {
+    fn drop(&mut self) {
+        self.owner.exceptions.lock().unwrap().remove(self.id);
+    }
+}
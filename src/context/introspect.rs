@@ -0,0 +1,85 @@
+use super::UniqueContext;
+use crate::{
+    backend,
+    channel::base::{raw, UserData},
+    error::{result_from_raw, Error},
+    types::FieldId,
+};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    sync::{atomic::Ordering, Mutex},
+};
+
+/// State of the channel created in the context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub name: CString,
+    pub priority: u8,
+    pub connected: bool,
+    /// Host which serves the channel, `None` if disconnected.
+    pub host: Option<CString>,
+    /// Field type, `None` if disconnected.
+    pub field_type: Option<FieldId>,
+    /// Number of elements, `None` if disconnected.
+    pub element_count: Option<usize>,
+    /// Number of active subscriptions.
+    pub subscriptions: usize,
+    /// Number of reads and writes waiting for completion.
+    pub pending: usize,
+}
+
+impl ChannelInfo {
+    unsafe fn from_raw(chan: sys::chanId, priority: u8) -> Self {
+        let user_data = &*(backend::ca_puser(chan) as *const UserData);
+        let proc = user_data.process.lock().unwrap();
+        Self {
+            name: CStr::from_ptr(backend::ca_name(chan)).to_owned(),
+            priority,
+            connected: user_data.connected.load(Ordering::Acquire),
+            host: raw::host_name(chan).ok().map(CStr::to_owned),
+            field_type: raw::field_type(chan).ok(),
+            element_count: raw::element_count(chan).ok(),
+            subscriptions: proc.subscription_count(),
+            pending: proc.pending_count(),
+        }
+    }
+}
+
+/// Identifiers of channels created in the context and their priorities.
+///
+/// Locked while channels are inspected, so they cannot be cleared meanwhile.
+pub(crate) type ChannelRegistry = Mutex<HashMap<usize, u8>>;
+
+impl UniqueContext {
+    pub(crate) fn register_channel(&self, chan: sys::chanId, priority: u8) {
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(chan as usize, priority);
+    }
+    pub(crate) fn unregister_channel(&self, chan: sys::chanId) {
+        self.channels.lock().unwrap().remove(&(chan as usize));
+    }
+
+    /// State of all channels that currently exist in the context, sorted by name.
+    pub fn channels(&self) -> Vec<ChannelInfo> {
+        let channels = self.channels.lock().unwrap();
+        let mut infos = channels
+            .iter()
+            .map(|(&chan, &priority)| unsafe {
+                ChannelInfo::from_raw(chan as sys::chanId, priority)
+            })
+            .collect::<Vec<_>>();
+        infos.sort_by(|a, b| (&a.name, a.priority).cmp(&(&b.name, b.priority)));
+        infos
+    }
+
+    /// Print diagnostic information about the context to stdout.
+    ///
+    /// Greater `level` means more details.
+    /// With `tracing` feature output of EPICS CA library is forwarded to `tracing` instead.
+    pub fn client_status(&self, level: u32) -> Result<(), Error> {
+        self.with(|| result_from_raw(unsafe { backend::ca_client_status(level) }))
+    }
+}
@@ -0,0 +1,462 @@
+mod builder;
+mod exception;
+mod introspect;
+
+pub use builder::{ContextBuilder, ContextConfig};
+pub use exception::{ExceptionEvent, ExceptionEvents};
+pub use introspect::ChannelInfo;
+
+use crate::error::{self, result_from_raw, Error};
+use crate::{backend, channel::connection::Listeners};
+use derivative::Derivative;
+use exception::{exception_handler, ExceptionData};
+use introspect::ChannelRegistry;
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    future::Future,
+    ops::Deref,
+    pin::pin,
+    ptr::{self, NonNull},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{self, Poll},
+    time::Duration,
+};
+
+/// Timeout that `ca_poll` uses.
+const MIN_PEND_TIMEOUT: f64 = 1e-12;
+/// How long [`UniqueContext::block_on`] waits for callbacks before polling the future again.
+const BLOCK_ON_INTERVAL: Duration = Duration::from_millis(1);
+
+thread_local! {
+    /// Contexts which flushes are deferred in the current thread.
+    static DEFERRED: RefCell<Vec<*mut sys::ca_client_context>> = const { RefCell::new(Vec::new()) };
+}
+
+thread_local! {
+    /// Non-preemptive context that is created in the current thread and can't be detached from it.
+    static BOUND: Cell<*mut sys::ca_client_context> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Removes context from deferred ones even on panic.
+struct DeferGuard(*mut sys::ca_client_context);
+
+impl Drop for DeferGuard {
+    fn drop(&mut self) {
+        DEFERRED.with(|d| {
+            let mut deferred = d.borrow_mut();
+            if let Some(pos) = deferred.iter().rposition(|raw| *raw == self.0) {
+                deferred.remove(pos);
+            }
+        });
+    }
+}
+
+/// Unique context.
+///
+/// Manages raw EPICS CA context.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct UniqueContext {
+    raw: NonNull<sys::ca_client_context>,
+    config: ContextConfig,
+    auto_flush: AtomicBool,
+    /// Boxed because its pointer is passed to exception handler.
+    #[derivative(Debug = "ignore")]
+    exceptions: Box<ExceptionData>,
+    channels: ChannelRegistry,
+}
+
+unsafe impl Send for UniqueContext {}
+/// Context is attached per thread, so it can be used from many threads simultaneously.
+unsafe impl Sync for UniqueContext {}
+
+impl UniqueContext {
+    /// Create a new unique context.
+    ///
+    /// To create context with specific settings use [`ContextBuilder`].
+    pub fn new() -> Result<Self, Error> {
+        ContextBuilder::new().build_unique()
+    }
+    fn create(config: ContextConfig) -> Result<Self, Error> {
+        let prev = Self::current();
+        if !prev.is_null() {
+            if !config.preemptive {
+                // Non-preemptive context stays attached to the thread.
+                return Err(error::ISATTACHED);
+            }
+            Self::check_unbound(prev);
+            Self::detach();
+        }
+        let exceptions = Box::new(ExceptionData::new(Listeners::new()));
+        let ret = Self::init_current(&config, &exceptions).map(|()| {
+            let raw = Self::current();
+            if config.preemptive {
+                Self::detach();
+            } else {
+                BOUND.with(|b| b.set(raw));
+            }
+            Self {
+                raw: NonNull::new(raw).unwrap(),
+                config,
+                auto_flush: AtomicBool::new(true),
+                exceptions,
+                channels: ChannelRegistry::default(),
+            }
+        });
+        if let Some(prev) = NonNull::new(prev) {
+            Self::attach(prev);
+        }
+        ret
+    }
+    /// Create raw context in the current thread and set up its handlers.
+    fn init_current(config: &ContextConfig, exceptions: &ExceptionData) -> Result<(), Error> {
+        use sys::ca_preemptive_callback_select::*;
+        result_from_raw(unsafe {
+            backend::context_create(
+                match config.preemptive {
+                    true => ca_enable_preemptive_callback,
+                    false => ca_disable_preemptive_callback,
+                },
+                config,
+            )
+        })?;
+        #[cfg(feature = "tracing")]
+        result_from_raw(unsafe {
+            backend::ca_replace_printf_handler(crate::trace::PRINTF_HANDLER)
+        })?;
+        result_from_raw(unsafe {
+            backend::ca_add_exception_event(
+                Some(exception_handler),
+                exceptions as *const _ as *mut _,
+            )
+        })
+    }
+
+    /// Whether callbacks are called from auxiliary threads at any time.
+    pub fn is_preemptive(&self) -> bool {
+        self.config.preemptive
+    }
+    /// Call pending callbacks and return immediately.
+    ///
+    /// In non-preemptive mode callbacks are called only by this or [`Self::pend_event`],
+    /// so one of them must be called periodically, e.g. from the event loop of the application.
+    pub fn poll(&self) -> Result<(), Error> {
+        self.pend_event(Duration::ZERO)
+    }
+    /// Call callbacks during `timeout`.
+    ///
+    /// In preemptive mode it just waits for `timeout`.
+    pub fn pend_event(&self, timeout: Duration) -> Result<(), Error> {
+        // Zero timeout means forever.
+        let timeout = timeout.as_secs_f64().max(MIN_PEND_TIMEOUT);
+        self.flush_io()?;
+        match result_from_raw(self.with(|| unsafe { backend::ca_pend_event(timeout) })) {
+            Err(error::TIMEOUT) => Ok(()),
+            other => other,
+        }
+    }
+
+    /// Run `future` to completion in the current thread calling callbacks of the context meanwhile.
+    ///
+    /// Useful to drive non-preemptive context.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = futures::task::noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            self.pend_event(BLOCK_ON_INTERVAL).unwrap();
+        }
+    }
+
+    /// Settings the context is created with.
+    pub fn config(&self) -> &ContextConfig {
+        &self.config
+    }
+    pub(crate) fn current() -> *mut sys::ca_client_context {
+        unsafe { backend::ca_current_context() }
+    }
+    fn attach(raw: NonNull<sys::ca_client_context>) {
+        unsafe { backend::ca_attach_context(raw.as_ptr()) };
+    }
+    fn detach() {
+        unsafe { backend::ca_detach_context() };
+    }
+    /// Panic if `raw` context is non-preemptive one which cannot be detached from the current thread.
+    fn check_unbound(raw: *mut sys::ca_client_context) {
+        if BOUND.with(|b| b.get()) == raw {
+            panic!("Thread of non-preemptive context cannot use other contexts");
+        }
+    }
+
+    /// Perform some operation inside of the context.
+    ///
+    /// This calls can be safely nested (either from same context or different ones).
+    ///
+    /// [`LocalContext`] can be used only from the thread it was created in,
+    /// and that thread cannot use other contexts.
+    pub fn with<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        let prev = Self::current();
+        if prev != self.raw.as_ptr() {
+            if !self.config.preemptive {
+                panic!("Non-preemptive context is used outside of its thread");
+            }
+            if !prev.is_null() {
+                Self::check_unbound(prev);
+                Self::detach();
+            }
+            Self::attach(self.raw);
+        }
+        let ret = f();
+        if prev != self.raw.as_ptr() {
+            Self::detach();
+            if let Some(prev) = NonNull::new(prev) {
+                Self::attach(prev);
+            }
+        }
+        ret
+    }
+
+    /// Flush IO queue.
+    ///
+    /// Sends all requests that are queued but not sent yet.
+    pub fn flush_io(&self) -> Result<(), Error> {
+        self.with(|| result_from_raw(unsafe { backend::ca_flush_io() }))
+    }
+
+    /// Flush IO queue if it isn't deferred.
+    ///
+    /// **Must be called after almost any EPICS CA function to ensure it has an effect.**
+    pub(crate) fn auto_flush_io(&self) {
+        if !self.auto_flush() || DEFERRED.with(|d| d.borrow().contains(&self.raw.as_ptr())) {
+            return;
+        }
+        self.flush_io().unwrap()
+    }
+
+    /// Whether IO is flushed after each request.
+    pub fn auto_flush(&self) -> bool {
+        self.auto_flush.load(Ordering::Acquire)
+    }
+    /// Set whether IO is flushed after each request (enabled by default).
+    ///
+    /// If disabled then requests may not be sent until [`Self::flush_io`] is called or [`Self::batch`] is done.
+    /// *Note that reads and subscriptions are requested on the first poll of their futures and streams.*
+    pub fn set_auto_flush(&self, enabled: bool) {
+        self.auto_flush.store(enabled, Ordering::Release);
+    }
+
+    /// Run `f` with IO flushes deferred and flush once in the end.
+    ///
+    /// Useful to send many requests (e.g. create channels or [`put_nowait`](`crate::ValueChannel::put_nowait`)) together.
+    /// Batches can be nested, IO is flushed when the outermost one is done.
+    pub fn batch<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        let raw = self.raw.as_ptr();
+        DEFERRED.with(|d| d.borrow_mut().push(raw));
+        let guard = DeferGuard(raw);
+        let ret = f();
+        drop(guard);
+        if !DEFERRED.with(|d| d.borrow().contains(&raw)) {
+            self.flush_io().unwrap();
+        }
+        ret
+    }
+}
+
+impl Drop for UniqueContext {
+    fn drop(&mut self) {
+        let prev = Self::current();
+        if !self.config.preemptive {
+            // Local context is dropped in its thread where it stays attached.
+            debug_assert_eq!(prev, self.raw.as_ptr());
+            BOUND.with(|b| b.set(ptr::null_mut()));
+            unsafe { backend::ca_context_destroy() };
+            return;
+        }
+        if !prev.is_null() {
+            Self::check_unbound(prev);
+            Self::detach();
+        }
+        Self::attach(self.raw);
+        unsafe { backend::ca_context_destroy() };
+        if let Some(prev) = NonNull::new(prev) {
+            Self::attach(prev);
+        }
+    }
+}
+
+/// Shared context.
+#[derive(Clone, Debug)]
+pub struct Context {
+    arc: Arc<UniqueContext>,
+}
+
+unsafe impl Send for Context {}
+
+impl Deref for Context {
+    type Target = UniqueContext;
+    fn deref(&self) -> &Self::Target {
+        &self.arc
+    }
+}
+
+impl From<UniqueContext> for Context {
+    fn from(uniq: UniqueContext) -> Self {
+        Self {
+            arc: Arc::new(uniq),
+        }
+    }
+}
+
+impl Context {
+    /// Creates a new [`UniqueContext`] and shares it.
+    pub fn new() -> Result<Self, Error> {
+        UniqueContext::new().map(Self::from)
+    }
+    /// Make builder of the context with specific settings.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+}
+
+/// Non-preemptive context bound to the thread it is created in.
+///
+/// Callbacks are called only when context is [polled](`UniqueContext::poll`),
+/// e.g. by [`block_on`](`UniqueContext::block_on`) or from the event loop of the application.
+///
+/// Neither context nor its channels can be sent to other threads,
+/// and the thread cannot use other contexts while this one exists.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<epics_ca::context::LocalContext>();
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocalContext {
+    rc: Rc<UniqueContext>,
+}
+
+impl Deref for LocalContext {
+    type Target = UniqueContext;
+    fn deref(&self) -> &Self::Target {
+        &self.rc
+    }
+}
+
+impl LocalContext {
+    /// Create a new non-preemptive context in the current thread.
+    ///
+    /// Fails with [`ISATTACHED`](`error::ISATTACHED`) if the thread already has one.
+    pub fn new() -> Result<Self, Error> {
+        ContextBuilder::new().build_local()
+    }
+    fn create(config: ContextConfig) -> Result<Self, Error> {
+        UniqueContext::create(config).map(|uniq| Self { rc: Rc::new(uniq) })
+    }
+}
+
+/// Handle that channels use to keep their context alive.
+///
+/// Channels of [`Context`] can be used from any thread, while channels of [`LocalContext`] are bound to its thread.
+pub trait ContextHandle: Clone + Debug + Deref<Target = UniqueContext> + sealed::Sealed {}
+
+impl ContextHandle for Context {}
+impl ContextHandle for LocalContext {}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Context {}
+    impl Sealed for super::LocalContext {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContextBuilder, UniqueContext as Context};
+
+    #[test]
+    fn new() {
+        Context::new().unwrap();
+    }
+
+    #[test]
+    fn builder() {
+        use std::{env, time::Duration};
+        let prev = env::var_os("EPICS_CA_MAX_ARRAY_BYTES");
+        let ctx = ContextBuilder::new()
+            .addr_list(["127.0.0.1", "127.0.0.2:5064"])
+            .auto_addr_list(false)
+            .conn_timeout(Duration::from_secs(10))
+            .max_array_bytes(1 << 20)
+            .server_port(5070)
+            .build_unique()
+            .unwrap();
+        let config = ctx.config();
+        assert_eq!(
+            config.addr_list.as_deref(),
+            Some(&["127.0.0.1".to_string(), "127.0.0.2:5064".to_string()][..])
+        );
+        assert_eq!(config.auto_addr_list, Some(false));
+        assert_eq!(config.name_servers, None);
+        assert_eq!(config.conn_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(config.max_array_bytes, Some(1 << 20));
+        assert_eq!(config.server_port, Some(5070));
+        assert!(config.preemptive);
+        assert_eq!(env::var_os("EPICS_CA_MAX_ARRAY_BYTES"), prev);
+    }
+
+    #[test]
+    fn attach() {
+        assert!(Context::current().is_null());
+        let ctx = Context::new().unwrap();
+        assert!(Context::current().is_null());
+        ctx.with(|| {
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+        });
+        assert!(Context::current().is_null());
+    }
+
+    #[test]
+    fn reattach_same() {
+        assert!(Context::current().is_null());
+        let ctx = Context::new().unwrap();
+        assert!(Context::current().is_null());
+        ctx.with(|| {
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+            ctx.with(|| {
+                assert_eq!(Context::current(), ctx.raw.as_ptr());
+            });
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+        });
+        assert!(Context::current().is_null());
+    }
+
+    #[test]
+    fn reattach_different() {
+        assert!(Context::current().is_null());
+        let ctx = Context::new().unwrap();
+        assert!(Context::current().is_null());
+        ctx.with(|| {
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+            let other_ctx = Context::new().unwrap();
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+            other_ctx.with(|| {
+                assert_eq!(Context::current(), other_ctx.raw.as_ptr());
+                ctx.with(|| {
+                    assert_eq!(Context::current(), ctx.raw.as_ptr());
+                });
+                assert_eq!(Context::current(), other_ctx.raw.as_ptr());
+            });
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+            drop(other_ctx);
+            assert_eq!(Context::current(), ctx.raw.as_ptr());
+        });
+        assert!(Context::current().is_null());
+    }
+}
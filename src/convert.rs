@@ -0,0 +1,482 @@
+use crate::{
+    error::{self, Error},
+    request::{
+        CtrlEnum, CtrlFloat, CtrlInt, CtrlString, GrEnum, GrFloat, GrInt, GrString, Limits, Sts,
+        Time, Units,
+    },
+    types::{
+        Alarm, DynArray, DynScalar, DynValue, EpicsEnum, EpicsString, EpicsTimeStamp, Field,
+        FieldId, RequestId, StaticCString,
+    },
+};
+use std::{
+    ffi::{c_void, CString},
+    mem::{self, size_of},
+    ptr, slice,
+};
+
+/// Metadata of PV that is sent in graphic and control requests.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Meta {
+    pub units: String,
+    pub precision: i16,
+    pub limits: Limits<f64>,
+    pub enum_strs: Vec<String>,
+}
+
+pub(crate) fn into_array(value: DynValue) -> DynArray {
+    match value {
+        DynValue::Scalar(scalar) => match scalar {
+            DynScalar::String(x) => DynArray::String(vec![x]),
+            DynScalar::Short(x) => DynArray::Short(vec![x]),
+            DynScalar::Float(x) => DynArray::Float(vec![x]),
+            DynScalar::Enum(x) => DynArray::Enum(vec![x]),
+            DynScalar::Char(x) => DynArray::Char(vec![x]),
+            DynScalar::Long(x) => DynArray::Long(vec![x]),
+            DynScalar::Double(x) => DynArray::Double(vec![x]),
+        },
+        DynValue::Array(array) => array,
+    }
+}
+
+pub(crate) fn from_array(array: &DynArray, scalar: bool) -> DynValue {
+    match array {
+        DynArray::String(v) => DynValue::from_items(v, scalar),
+        DynArray::Short(v) => DynValue::from_items(v, scalar),
+        DynArray::Float(v) => DynValue::from_items(v, scalar),
+        DynArray::Enum(v) => DynValue::from_items(v, scalar),
+        DynArray::Char(v) => DynValue::from_items(v, scalar),
+        DynArray::Long(v) => DynValue::from_items(v, scalar),
+        DynArray::Double(v) => DynValue::from_items(v, scalar),
+    }
+}
+
+/// Truncate array or pad it with zeros.
+pub(crate) fn resize(array: &mut DynArray, len: usize) {
+    match array {
+        DynArray::String(v) => v.resize(len, EpicsString::default()),
+        DynArray::Short(v) => v.resize(len, 0),
+        DynArray::Float(v) => v.resize(len, 0.0),
+        DynArray::Enum(v) => v.resize(len, EpicsEnum::default()),
+        DynArray::Char(v) => v.resize(len, 0),
+        DynArray::Long(v) => v.resize(len, 0),
+        DynArray::Double(v) => v.resize(len, 0.0),
+    }
+}
+
+pub(crate) fn epics_string(s: &str) -> EpicsString {
+    static_cstring(s)
+}
+
+fn static_cstring<const N: usize>(s: &str) -> StaticCString<N> {
+    let bytes = s.as_bytes();
+    let bytes = &bytes[..usize::min(bytes.len(), N - 1)];
+    let bytes = &bytes[..bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len())];
+    StaticCString::from_cstr(&CString::new(bytes).unwrap()).unwrap()
+}
+
+fn numbers(array: &DynArray) -> Option<Vec<f64>> {
+    Some(match array {
+        DynArray::String(_) => return None,
+        DynArray::Short(v) => v.iter().map(|x| *x as f64).collect(),
+        DynArray::Float(v) => v.iter().map(|x| *x as f64).collect(),
+        DynArray::Enum(v) => v.iter().map(|x| x.0 as f64).collect(),
+        DynArray::Char(v) => v.iter().map(|x| *x as f64).collect(),
+        DynArray::Long(v) => v.iter().map(|x| *x as f64).collect(),
+        DynArray::Double(v) => v.clone(),
+    })
+}
+
+fn strings(array: &DynArray, enum_strs: &[String]) -> Vec<String> {
+    match array {
+        DynArray::String(v) => v.iter().map(|s| s.to_string_lossy().into_owned()).collect(),
+        DynArray::Enum(v) => v
+            .iter()
+            .map(|e| match enum_strs.get(e.0 as usize) {
+                Some(s) => s.clone(),
+                None => e.0.to_string(),
+            })
+            .collect(),
+        _ => numbers(array)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect(),
+    }
+}
+
+fn from_numbers(target: FieldId, numbers: Vec<f64>) -> DynArray {
+    let iter = numbers.into_iter();
+    match target {
+        FieldId::String => DynArray::String(iter.map(|x| epics_string(&x.to_string())).collect()),
+        FieldId::Short => DynArray::Short(iter.map(|x| x as i16).collect()),
+        FieldId::Float => DynArray::Float(iter.map(|x| x as f32).collect()),
+        FieldId::Enum => DynArray::Enum(iter.map(|x| EpicsEnum(x as u16)).collect()),
+        FieldId::Char => DynArray::Char(iter.map(|x| x as u8).collect()),
+        FieldId::Long => DynArray::Long(iter.map(|x| x as i32).collect()),
+        FieldId::Double => DynArray::Double(iter.collect()),
+    }
+}
+
+fn from_strings(
+    target: FieldId,
+    strings: &[String],
+    enum_strs: &[String],
+) -> Result<DynArray, Error> {
+    if target == FieldId::String {
+        return Ok(DynArray::String(
+            strings.iter().map(|s| epics_string(s)).collect(),
+        ));
+    }
+    let numbers = strings
+        .iter()
+        .map(|s| {
+            let s = s.trim();
+            match enum_strs.iter().position(|e| e == s) {
+                Some(index) if target == FieldId::Enum => Ok(index as f64),
+                _ => s.parse::<f64>().map_err(|_| error::NOCONVERT),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(from_numbers(target, numbers))
+}
+
+/// Convert array to another field type the same way Channel Access server does.
+pub(crate) fn convert(
+    array: &DynArray,
+    target: FieldId,
+    enum_strs: &[String],
+) -> Result<DynArray, Error> {
+    if array.field_id() == target {
+        Ok(array.clone())
+    } else if target == FieldId::String || array.field_id() == FieldId::String {
+        from_strings(target, &strings(array, enum_strs), enum_strs)
+    } else {
+        Ok(from_numbers(target, numbers(array).unwrap()))
+    }
+}
+
+/// Read array of raw items.
+///
+/// # Safety
+///
+/// `ptr` must point to `count` items of `field` type.
+pub(crate) unsafe fn decode(field: FieldId, ptr: *const c_void, count: usize) -> DynArray {
+    unsafe fn items<T: Field>(ptr: *const c_void, count: usize) -> Vec<T> {
+        if count == 0 {
+            Vec::new()
+        } else {
+            slice::from_raw_parts(ptr as *const T, count).to_vec()
+        }
+    }
+    match field {
+        FieldId::String => DynArray::String(items(ptr, count)),
+        FieldId::Short => DynArray::Short(items(ptr, count)),
+        FieldId::Float => DynArray::Float(items(ptr, count)),
+        FieldId::Enum => DynArray::Enum(items(ptr, count)),
+        FieldId::Char => DynArray::Char(items(ptr, count)),
+        FieldId::Long => DynArray::Long(items(ptr, count)),
+        FieldId::Double => DynArray::Double(items(ptr, count)),
+    }
+}
+
+/// Raw request data.
+pub(crate) struct Buffer {
+    /// `u64` is used for alignment.
+    data: Vec<u64>,
+    pub count: usize,
+}
+
+impl Buffer {
+    /// Put `header` request and then `items` starting from the `value` field of the header.
+    fn new<R: Copy, T: Copy>(header: &R, value: &T, items: &[T]) -> Self {
+        let offset = value as *const T as usize - header as *const R as usize;
+        let size = usize::max(size_of::<R>(), offset + mem::size_of_val(items));
+        let mut data = vec![0u64; size.div_ceil(8)];
+        unsafe {
+            let base = data.as_mut_ptr() as *mut u8;
+            ptr::copy_nonoverlapping(header as *const R as *const u8, base, size_of::<R>());
+            ptr::copy_nonoverlapping(items.as_ptr(), base.add(offset) as *mut T, items.len());
+        }
+        Self {
+            data,
+            count: items.len(),
+        }
+    }
+    pub fn as_ptr(&self) -> *const c_void {
+        self.data.as_ptr() as *const c_void
+    }
+}
+
+/// Data of PV needed to make a request.
+pub(crate) struct Header<'a> {
+    pub alarm: Alarm,
+    pub stamp: EpicsTimeStamp,
+    pub meta: &'a Meta,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Base,
+    Sts,
+    Time,
+    Gr,
+    Ctrl,
+}
+
+trait Encode: Field {
+    fn encode(kind: Kind, header: &Header, items: &[Self]) -> Buffer;
+}
+
+fn encode_plain<T: Field>(kind: Kind, header: &Header, items: &[T]) -> Buffer {
+    match kind {
+        Kind::Base => {
+            let first = items.first().copied().unwrap_or(unsafe { mem::zeroed() });
+            Buffer::new(&first, &first, items)
+        }
+        Kind::Sts => {
+            let mut req: Sts<T> = unsafe { mem::zeroed() };
+            req.alarm = header.alarm;
+            Buffer::new(&req, &req.value, items)
+        }
+        Kind::Time => {
+            let mut req: Time<T> = unsafe { mem::zeroed() };
+            req.alarm = header.alarm;
+            req.stamp = header.stamp;
+            Buffer::new(&req, &req.value, items)
+        }
+        Kind::Gr | Kind::Ctrl => unreachable!(),
+    }
+}
+
+macro_rules! set_limits {
+    ($req:expr, $limits:expr, $type:ty) => {
+        $req.upper_disp_limit = $limits.upper_disp as $type;
+        $req.lower_disp_limit = $limits.lower_disp as $type;
+        $req.upper_alarm_limit = $limits.upper_alarm as $type;
+        $req.upper_warning_limit = $limits.upper_warning as $type;
+        $req.lower_warning_limit = $limits.lower_warning as $type;
+        $req.lower_alarm_limit = $limits.lower_alarm as $type;
+    };
+}
+
+macro_rules! impl_encode_int {
+    ($type:ty) => {
+        impl Encode for $type {
+            fn encode(kind: Kind, header: &Header, items: &[Self]) -> Buffer {
+                let meta = header.meta;
+                match kind {
+                    Kind::Gr => {
+                        let mut req: GrInt<$type> = unsafe { mem::zeroed() };
+                        req.alarm = header.alarm;
+                        req.units = Units(static_cstring(&meta.units));
+                        set_limits!(req, meta.limits, $type);
+                        Buffer::new(&req, &req.value, items)
+                    }
+                    Kind::Ctrl => {
+                        let mut req: CtrlInt<$type> = unsafe { mem::zeroed() };
+                        req.alarm = header.alarm;
+                        req.units = Units(static_cstring(&meta.units));
+                        set_limits!(req, meta.limits, $type);
+                        req.upper_ctrl_limit = meta.limits.upper_ctrl as $type;
+                        req.lower_ctrl_limit = meta.limits.lower_ctrl as $type;
+                        Buffer::new(&req, &req.value, items)
+                    }
+                    _ => encode_plain(kind, header, items),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_encode_float {
+    ($type:ty) => {
+        impl Encode for $type {
+            fn encode(kind: Kind, header: &Header, items: &[Self]) -> Buffer {
+                let meta = header.meta;
+                match kind {
+                    Kind::Gr => {
+                        let mut req: GrFloat<$type> = unsafe { mem::zeroed() };
+                        req.alarm = header.alarm;
+                        req.precision = meta.precision;
+                        req.units = Units(static_cstring(&meta.units));
+                        set_limits!(req, meta.limits, $type);
+                        Buffer::new(&req, &req.value, items)
+                    }
+                    Kind::Ctrl => {
+                        let mut req: CtrlFloat<$type> = unsafe { mem::zeroed() };
+                        req.alarm = header.alarm;
+                        req.precision = meta.precision;
+                        req.units = Units(static_cstring(&meta.units));
+                        set_limits!(req, meta.limits, $type);
+                        req.upper_ctrl_limit = meta.limits.upper_ctrl as $type;
+                        req.lower_ctrl_limit = meta.limits.lower_ctrl as $type;
+                        Buffer::new(&req, &req.value, items)
+                    }
+                    _ => encode_plain(kind, header, items),
+                }
+            }
+        }
+    };
+}
+
+impl_encode_int!(i16);
+impl_encode_int!(u8);
+impl_encode_int!(i32);
+impl_encode_float!(f32);
+impl_encode_float!(f64);
+
+impl Encode for EpicsEnum {
+    fn encode(kind: Kind, header: &Header, items: &[Self]) -> Buffer {
+        macro_rules! encode_enum {
+            ($req_type:ident) => {{
+                let mut req: $req_type<EpicsEnum> = unsafe { mem::zeroed() };
+                req.alarm = header.alarm;
+                let strs = &header.meta.enum_strs;
+                req.no_str = usize::min(strs.len(), req.strs.len()) as u16;
+                for (dst, src) in req.strs.iter_mut().zip(strs.iter()) {
+                    *dst = static_cstring(src);
+                }
+                Buffer::new(&req, &req.value, items)
+            }};
+        }
+        match kind {
+            Kind::Gr => encode_enum!(GrEnum),
+            Kind::Ctrl => encode_enum!(CtrlEnum),
+            _ => encode_plain(kind, header, items),
+        }
+    }
+}
+
+impl Encode for EpicsString {
+    fn encode(kind: Kind, header: &Header, items: &[Self]) -> Buffer {
+        match kind {
+            Kind::Gr => {
+                let mut req: GrString<EpicsString> = unsafe { mem::zeroed() };
+                req.alarm = header.alarm;
+                Buffer::new(&req, &req.value, items)
+            }
+            Kind::Ctrl => {
+                let mut req: CtrlString<EpicsString> = unsafe { mem::zeroed() };
+                req.alarm = header.alarm;
+                Buffer::new(&req, &req.value, items)
+            }
+            _ => encode_plain(kind, header, items),
+        }
+    }
+}
+
+/// Make raw response to the read request of `count` elements (`0` means current length).
+pub(crate) fn encode(
+    id: RequestId,
+    count: usize,
+    value: &DynArray,
+    header: &Header,
+) -> Result<Buffer, Error> {
+    let (kind, field) = match id {
+        RequestId::Base(field) => (Kind::Base, field),
+        RequestId::Sts(field) => (Kind::Sts, field),
+        RequestId::Time(field) => (Kind::Time, field),
+        RequestId::Gr(field) => (Kind::Gr, field),
+        RequestId::Ctrl(field) => (Kind::Ctrl, field),
+        _ => return Err(error::BADTYPE),
+    };
+    let mut items = convert(value, field, &header.meta.enum_strs)?;
+    resize(&mut items, if count == 0 { value.len() } else { count });
+    Ok(match &items {
+        DynArray::String(v) => EpicsString::encode(kind, header, v),
+        DynArray::Short(v) => i16::encode(kind, header, v),
+        DynArray::Float(v) => f32::encode(kind, header, v),
+        DynArray::Enum(v) => EpicsEnum::encode(kind, header, v),
+        DynArray::Char(v) => u8::encode(kind, header, v),
+        DynArray::Long(v) => i32::encode(kind, header, v),
+        DynArray::Double(v) => f64::encode(kind, header, v),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        request::{Request, TypedRequest},
+        types::AlarmSeverity,
+    };
+
+    #[test]
+    fn conversion() {
+        let strs = ["Off".to_string(), "On".to_string()];
+        let value = DynArray::Long(vec![1, -2]);
+        assert_eq!(
+            convert(&value, FieldId::Double, &[]).unwrap(),
+            DynArray::Double(vec![1.0, -2.0])
+        );
+        assert_eq!(
+            convert(&value, FieldId::String, &[]).unwrap(),
+            DynArray::String(vec![epics_string("1"), epics_string("-2")])
+        );
+        assert_eq!(
+            convert(&DynArray::Enum(vec![EpicsEnum(1)]), FieldId::String, &strs).unwrap(),
+            DynArray::String(vec![epics_string("On")])
+        );
+        assert_eq!(
+            convert(
+                &DynArray::String(vec![epics_string("Off")]),
+                FieldId::Enum,
+                &strs
+            )
+            .unwrap(),
+            DynArray::Enum(vec![EpicsEnum(0)])
+        );
+        assert_eq!(
+            convert(
+                &DynArray::String(vec![epics_string("abc")]),
+                FieldId::Double,
+                &[]
+            ),
+            Err(error::NOCONVERT)
+        );
+    }
+
+    #[test]
+    fn encode_time() {
+        let meta = Meta::default();
+        let header = Header {
+            alarm: Alarm {
+                severity: AlarmSeverity::Minor,
+                ..Default::default()
+            },
+            stamp: EpicsTimeStamp::now(),
+            meta: &meta,
+        };
+        let value = DynArray::Long(vec![1, 2, 3]);
+        let id = Time::<[f64]>::ID;
+        let buffer = encode(id, 0, &value, &header).unwrap();
+        let req =
+            unsafe { Time::<[f64]>::from_ptr(buffer.as_ptr() as *const u8, id, buffer.count) }
+                .unwrap();
+        assert_eq!(req.value(), [1.0, 2.0, 3.0]);
+        assert_eq!(req.alarm, header.alarm);
+        assert_eq!(req.stamp, header.stamp);
+    }
+
+    #[test]
+    fn encode_ctrl_enum() {
+        let meta = Meta {
+            enum_strs: vec!["Off".into(), "On".into()],
+            ..Default::default()
+        };
+        let header = Header {
+            alarm: Alarm::default(),
+            stamp: EpicsTimeStamp::now(),
+            meta: &meta,
+        };
+        let value = DynArray::Enum(vec![EpicsEnum(1)]);
+        let id = CtrlEnum::<EpicsEnum>::ID;
+        let buffer = encode(id, 1, &value, &header).unwrap();
+        let req = unsafe { CtrlEnum::<EpicsEnum>::from_ptr(buffer.as_ptr() as *const u8, id, 1) }
+            .unwrap();
+        assert_eq!(req.value, EpicsEnum(1));
+        assert_eq!(req.no_str, 2);
+        assert_eq!(req.strs[1].to_str().unwrap(), "On");
+    }
+}
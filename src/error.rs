@@ -173,7 +173,7 @@ impl ErrorSeverity {
 }
 
 /// Error that can occur in EPICS client or server.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct Error {
     pub kind: ErrorKind,
     pub severity: ErrorSeverity,
@@ -5,19 +5,51 @@
 //! + [Channels](channel)
 //! + [Requests](request)
 //!
+//! Exactly one backend must be selected: `libca` (default), `pure-rust` or `mock`.
+//!
+
+#[cfg(not(any(feature = "libca", feature = "pure-rust", feature = "mock")))]
+compile_error!("One of `libca`, `pure-rust` or `mock` features must be enabled");
+#[cfg(all(feature = "libca", feature = "pure-rust"))]
+compile_error!(
+    "`pure-rust` feature cannot be combined with `libca`, use `default-features = false`"
+);
+#[cfg(all(feature = "mock", any(feature = "libca", feature = "pure-rust")))]
+compile_error!(
+    "`mock` feature cannot be combined with other backends, use `default-features = false`"
+);
 
+mod backend;
+/// Requests to many channels awaited together
+pub mod batch;
+/// Blocking API
+pub mod blocking;
 /// Channels
 pub mod channel;
 /// Context
 pub mod context;
+#[cfg(any(feature = "server", feature = "mock"))]
+mod convert;
 /// Error types
 pub mod error;
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(any(feature = "pure-rust", feature = "server"))]
+mod protocol;
+#[cfg(feature = "pure-rust")]
+mod pure;
+#[cfg(any(feature = "server", feature = "mock"))]
+mod pv;
 /// Different types of requests
 pub mod request;
+#[cfg(feature = "server")]
+pub mod server;
+mod trace;
 /// Native EPICS types
 pub mod types;
 mod utils;
 
-pub use channel::{Channel, TypedChannel, ValueChannel};
-pub use context::Context;
+pub use batch::BatchJoin;
+pub use channel::{Channel, SharedChannel, TypedChannel, ValueChannel};
+pub use context::{Context, LocalContext};
 pub use error::Error;
@@ -0,0 +1,143 @@
+use super::raw::ChannelState;
+use crate::{
+    convert,
+    pv::{Observer, Pv},
+    trace,
+    types::{DynArray, DynValue},
+};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    ptr,
+    sync::{Arc, Mutex, OnceLock, Weak},
+};
+
+/// In-memory database of process variables served by mock backend.
+///
+/// Channels are connected to PV with the same name.
+/// If there is no such PV, channel waits until it is [added](`Self::add`).
+pub struct Database {
+    inner: Mutex<DatabaseInner>,
+}
+
+#[derive(Default)]
+struct DatabaseInner {
+    pvs: HashMap<CString, Pv>,
+    /// Channels waiting for PV to appear.
+    pending: HashMap<CString, Vec<Weak<ChannelState>>>,
+}
+
+impl Database {
+    fn new() -> Self {
+        Self {
+            inner: Mutex::new(DatabaseInner::default()),
+        }
+    }
+
+    /// Database shared by all contexts in the process.
+    pub fn global() -> &'static Database {
+        static GLOBAL: OnceLock<Database> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            let db = Database::new();
+            #[cfg(test)]
+            crate::pv::add_test_records(|name, value, max_len| db.add_array(name, value, max_len));
+            db
+        })
+    }
+
+    /// Add PV with initial `value`.
+    ///
+    /// Scalar value makes PV of single element and vector makes array PV of the vector length.
+    /// Existing PV with the same name is [removed](`Self::remove`).
+    pub fn add(&self, name: &str, value: impl Into<DynValue>) -> Pv {
+        let value = value.into();
+        let max_len = usize::max(value.len(), 1);
+        self.add_array(name, convert::into_array(value), max_len)
+    }
+
+    /// Add array PV that can store up to `max_len` elements.
+    pub fn add_array(&self, name: &str, value: DynArray, max_len: usize) -> Pv {
+        let name = CString::new(name).unwrap();
+        let pv = Pv::new(name.clone(), value, max_len);
+        let old = {
+            let mut inner = self.inner.lock().unwrap();
+            for channel in inner.pending.remove(&name).unwrap_or_default() {
+                if let Some(channel) = channel.upgrade() {
+                    attach(&pv, &channel);
+                }
+            }
+            inner.pvs.insert(name, pv.clone())
+        };
+        // Channels of the old PV are connected to the new one.
+        if let Some(old) = old {
+            old.detach_all();
+        }
+        pv
+    }
+
+    /// Find PV by name.
+    pub fn get(&self, name: &str) -> Option<Pv> {
+        let name = CString::new(name).ok()?;
+        self.inner.lock().unwrap().pvs.get(&name).cloned()
+    }
+
+    /// Remove PV from the database.
+    ///
+    /// All channels connected to the PV become disconnected and wait for PV with the same name.
+    pub fn remove(&self, name: &str) -> Option<Pv> {
+        let name = CString::new(name).ok()?;
+        let pv = self.inner.lock().unwrap().pvs.remove(&name)?;
+        pv.detach_all();
+        Some(pv)
+    }
+
+    /// Connect newly created channel or put it to pending ones.
+    pub(crate) fn connect(&self, channel: &Arc<ChannelState>) {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.pvs.get(channel.name()) {
+            Some(pv) => attach(pv, channel),
+            None => inner
+                .pending
+                .entry(channel.name().to_owned())
+                .or_default()
+                .push(Arc::downgrade(channel)),
+        }
+    }
+
+    /// Forget about cleared channel.
+    pub(crate) fn release(&self, channel: &ChannelState) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(pending) = inner.pending.get_mut(channel.name()) {
+            pending.retain(|other| other.strong_count() > 0 && !ptr_eq(other, channel));
+            if pending.is_empty() {
+                inner.pending.remove(channel.name());
+            }
+        }
+    }
+
+    /// Print summary of the database, listing PVs if `level` is non-zero.
+    pub(crate) fn print_status(&self, level: u32) {
+        let inner = self.inner.lock().unwrap();
+        trace::print(&format!(
+            "Mock database: {} PVs, {} channel names pending",
+            inner.pvs.len(),
+            inner.pending.len()
+        ));
+        if level > 0 {
+            let mut names = inner.pvs.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                trace::print(&format!("    {}", name.to_string_lossy()));
+            }
+        }
+    }
+}
+
+fn attach(pv: &Pv, channel: &Arc<ChannelState>) {
+    let state = pv.attach(Arc::downgrade(channel) as Weak<dyn Observer>);
+    channel.attach(pv, &state);
+}
+
+fn ptr_eq(weak: &Weak<ChannelState>, channel: &ChannelState) -> bool {
+    ptr::eq(weak.as_ptr(), channel)
+}
@@ -0,0 +1,26 @@
+//! In-memory backend that is used instead of EPICS CA library when `mock` feature is enabled.
+//!
+//! Channels connect to PVs of the global [`Database`] without any networking,
+//! so applications and tests can be run without IOC and `epics-base`.
+//!
+//! ```
+//! # use epics_ca::{mock::Database, Context};
+//! # use cstr::cstr;
+//! # async_std::task::block_on(async {
+//! let pv = Database::global().add("mock:doc:value", 1.0);
+//! let ctx = Context::new().unwrap();
+//! let channel = ctx.connect::<f64>(cstr!("mock:doc:value")).await.unwrap();
+//! assert_eq!(channel.get().await.unwrap(), 1.0);
+//! channel.put(2.0).unwrap().await.unwrap();
+//! assert_eq!(pv.value(), 2.0.into());
+//! # });
+//! ```
+
+mod database;
+pub(crate) mod raw;
+
+pub use crate::pv::Pv;
+pub use database::Database;
+
+#[cfg(test)]
+mod tests;
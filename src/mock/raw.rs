@@ -0,0 +1,651 @@
+//! Mock implementation of Channel Access functions used by the crate.
+//!
+//! Functions have the same signatures as ones from [`sys`].
+//! All callbacks are called asynchronously from the worker thread of the context,
+//! or from [`ca_pend_event`] if preemptive callbacks are disabled.
+
+#![allow(clippy::missing_safety_doc)]
+
+use super::database::Database;
+use crate::{
+    convert::{self, Buffer},
+    error::{self, Error},
+    pv::{Observer, Pv, PvState},
+    types::{AccessRights, EventMask, RequestId},
+};
+use std::{
+    cell::Cell,
+    ffi::{c_void, CStr, CString},
+    os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong},
+    ptr,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use sys::{
+    access_rights_handler_args, caArh, caCh, caEventCallBackFunc, caExceptionHandler,
+    ca_client_context, ca_preemptive_callback_select, ca_real, capri, chid, chtype,
+    connection_handler_args, event_handler_args, evid, exception_handler_args,
+};
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Calls callbacks of the context in order they were issued.
+///
+/// Callbacks are called from separate thread or, in non-preemptive mode, by [`Worker::poll`].
+struct Worker {
+    sender: Mutex<Option<Sender<Task>>>,
+    exception_handler: Mutex<(caExceptionHandler, UserPtr)>,
+    /// Present in non-preemptive mode.
+    receiver: Option<Mutex<Receiver<Task>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Worker {
+    fn new(preemptive: bool) -> Self {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        if !preemptive {
+            return Self {
+                sender: Mutex::new(Some(sender)),
+                exception_handler: Mutex::new((None, UserPtr(ptr::null_mut()))),
+                receiver: Some(Mutex::new(receiver)),
+                thread: Mutex::new(None),
+            };
+        }
+        let thread = thread::Builder::new()
+            .name("mock-ca".into())
+            .spawn(move || {
+                for task in receiver {
+                    task();
+                }
+            })
+            .unwrap();
+        Self {
+            sender: Mutex::new(Some(sender)),
+            exception_handler: Mutex::new((None, UserPtr(ptr::null_mut()))),
+            receiver: None,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+    /// Call pending tasks during `timeout`.
+    fn poll(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let receiver = match &self.receiver {
+            Some(receiver) => receiver,
+            None => return thread::sleep(timeout),
+        };
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            // Lock is released before the task is called.
+            let task = receiver.lock().unwrap().recv_timeout(wait);
+            match task {
+                Ok(task) => task(),
+                Err(_) => break,
+            }
+        }
+    }
+    fn spawn<F: FnOnce() + Send + 'static>(&self, task: F) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            // Worker is stopped only when context is destroyed.
+            let _ = sender.send(Box::new(task));
+        }
+    }
+    /// Call remaining tasks and stop the thread.
+    fn stop(&self) {
+        drop(self.sender.lock().unwrap().take());
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            if thread.thread().id() != thread::current().id() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct MockContext {
+    worker: Arc<Worker>,
+}
+
+impl Drop for MockContext {
+    fn drop(&mut self) {
+        self.worker.stop();
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<*mut MockContext> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Pointer that is passed back to user callbacks.
+#[derive(Clone, Copy)]
+struct UserPtr(*mut c_void);
+
+unsafe impl Send for UserPtr {}
+unsafe impl Sync for UserPtr {}
+
+impl UserPtr {
+    /// Method is used to capture the whole wrapper by closures.
+    fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Raw channel. Its pointer is used as `chid`.
+pub(crate) struct ChannelState {
+    name: CString,
+    puser: UserPtr,
+    conn_cb: caCh,
+    access_cb: Mutex<caArh>,
+    worker: Arc<Worker>,
+    /// Becomes `false` when channel is cleared. Locked while callback is running.
+    alive: Mutex<bool>,
+    pv: Mutex<Option<Pv>>,
+    subscriptions: Mutex<Vec<Arc<SubscriptionState>>>,
+}
+
+impl ChannelState {
+    pub(crate) fn name(&self) -> &CStr {
+        &self.name
+    }
+    fn raw(self: &Arc<Self>) -> chid {
+        Arc::as_ptr(self) as chid
+    }
+    fn pv(&self) -> Option<Pv> {
+        self.pv.lock().unwrap().clone()
+    }
+
+    /// Call `f` from the worker thread if channel is still alive.
+    fn enqueue<F: FnOnce(chid) + Send + 'static>(self: &Arc<Self>, f: F) {
+        let this = self.clone();
+        self.worker.spawn(move || {
+            let alive = this.alive.lock().unwrap();
+            if *alive {
+                f(this.raw());
+            }
+        });
+    }
+    /// Report exception to the handler of the context.
+    fn exception(self: &Arc<Self>, status: c_int, type_: chtype, count: usize, ctx: &'static CStr) {
+        let (func, usr) = *self.worker.exception_handler.lock().unwrap();
+        if let Some(func) = func {
+            self.enqueue(move |chid| unsafe {
+                func(exception_handler_args {
+                    usr: usr.get(),
+                    chid,
+                    type_: type_ as c_long,
+                    count: count as c_long,
+                    addr: ptr::null_mut(),
+                    stat: status as c_long,
+                    op: sys::CA_OP_PUT as c_long,
+                    ctx: ctx.as_ptr(),
+                    pFile: concat!(file!(), "\0").as_ptr() as *const c_char,
+                    lineNo: line!(),
+                })
+            });
+        }
+    }
+    fn notify_access(self: &Arc<Self>, access: AccessRights) {
+        if let Some(func) = *self.access_cb.lock().unwrap() {
+            self.enqueue(move |chid| unsafe {
+                func(access_rights_handler_args {
+                    chid,
+                    ar: access.raw(),
+                })
+            });
+        }
+    }
+    fn notify_connection(self: &Arc<Self>, op: i32) {
+        if let Some(func) = self.conn_cb {
+            self.enqueue(move |chid| unsafe {
+                func(connection_handler_args {
+                    chid,
+                    op: op as c_long,
+                })
+            });
+        }
+    }
+
+    /// Channel is connected to the PV.
+    pub(crate) fn attach(self: &Arc<Self>, pv: &Pv, state: &PvState) {
+        *self.pv.lock().unwrap() = Some(pv.clone());
+        self.notify_access(state.access());
+        self.notify_connection(sys::CA_OP_CONN_UP);
+        for sub in self.subscriptions.lock().unwrap().iter() {
+            sub.post(self, state);
+        }
+    }
+}
+
+impl Observer for ChannelState {
+    fn on_change(self: Arc<Self>, state: &PvState, mask: EventMask) {
+        for sub in self.subscriptions.lock().unwrap().iter() {
+            if sub.mask.intersects(mask) {
+                sub.post(&self, state);
+            }
+        }
+    }
+    fn on_access(self: Arc<Self>, access: AccessRights) {
+        self.notify_access(access);
+    }
+    /// Channel becomes disconnected and waits for PV with the same name.
+    fn on_remove(self: Arc<Self>) {
+        *self.pv.lock().unwrap() = None;
+        self.notify_access(AccessRights::default());
+        self.notify_connection(sys::CA_OP_CONN_DOWN);
+        if *self.alive.lock().unwrap() {
+            Database::global().connect(&self);
+        }
+    }
+}
+
+struct SubscriptionState {
+    channel: Weak<ChannelState>,
+    id: RequestId,
+    count: usize,
+    mask: EventMask,
+    func: caEventCallBackFunc,
+    usr: UserPtr,
+    /// Becomes `false` when subscription is cleared. Locked while callback is running.
+    active: Mutex<bool>,
+}
+
+impl SubscriptionState {
+    fn post(self: &Arc<Self>, channel: &Arc<ChannelState>, state: &PvState) {
+        let result = read(state, self.id, self.count);
+        let this = self.clone();
+        channel.enqueue(move |chid| {
+            let active = this.active.lock().unwrap();
+            if *active {
+                unsafe { call_event(this.func, this.usr, chid, this.id, result) };
+            }
+        });
+    }
+}
+
+fn read(state: &PvState, id: RequestId, count: usize) -> Result<Buffer, Error> {
+    if !state.access().read_access() {
+        return Err(error::NORDACCESS);
+    }
+    state.encode(id, count)
+}
+
+unsafe fn call_event(
+    func: caEventCallBackFunc,
+    usr: UserPtr,
+    chid: chid,
+    id: RequestId,
+    result: Result<Buffer, Error>,
+) {
+    let func = match func {
+        Some(func) => func,
+        None => return,
+    };
+    let (dbr, count, status) = match &result {
+        Ok(buffer) => (buffer.as_ptr(), buffer.count, sys::ECA_NORMAL),
+        Err(err) => (ptr::null(), 0, err.into_raw()),
+    };
+    func(event_handler_args {
+        usr: usr.get(),
+        chid,
+        type_: id.raw() as c_long,
+        count: count as c_long,
+        dbr,
+        status,
+    });
+}
+
+fn current() -> Option<&'static MockContext> {
+    unsafe { CURRENT.with(|c| c.get()).as_ref() }
+}
+
+/// Borrow channel by its identifier.
+unsafe fn channel(chan: chid) -> Arc<ChannelState> {
+    let chan = chan as *const ChannelState;
+    Arc::increment_strong_count(chan);
+    Arc::from_raw(chan)
+}
+
+pub unsafe fn ca_context_create(select: ca_preemptive_callback_select) -> c_int {
+    if current().is_none() {
+        let preemptive = select == ca_preemptive_callback_select::ca_enable_preemptive_callback;
+        let ctx = Box::new(MockContext {
+            worker: Arc::new(Worker::new(preemptive)),
+        });
+        CURRENT.with(|c| c.set(Box::into_raw(ctx)));
+    }
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_context_destroy() {
+    let ctx = CURRENT.with(|c| c.replace(ptr::null_mut()));
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+pub unsafe fn ca_pend_event(timeout: ca_real) -> c_int {
+    match current() {
+        Some(ctx) => {
+            ctx.worker.poll(Duration::from_secs_f64(timeout.max(0.0)));
+            sys::ECA_TIMEOUT
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_add_exception_event(func: caExceptionHandler, usr: *mut c_void) -> c_int {
+    match current() {
+        Some(ctx) => {
+            *ctx.worker.exception_handler.lock().unwrap() = (func, UserPtr(usr));
+            sys::ECA_NORMAL
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+/// Nothing is printed by this backend, so the handler is never called.
+#[cfg(feature = "tracing")]
+pub unsafe fn ca_replace_printf_handler(_func: sys::caPrintfFunc) -> c_int {
+    match current() {
+        Some(_) => sys::ECA_NORMAL,
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_current_context() -> *mut ca_client_context {
+    CURRENT.with(|c| c.get()) as *mut ca_client_context
+}
+
+pub unsafe fn ca_attach_context(context: *mut ca_client_context) -> c_int {
+    if current().is_some() {
+        return sys::ECA_ISATTACHED;
+    }
+    CURRENT.with(|c| c.set(context as *mut MockContext));
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_detach_context() {
+    CURRENT.with(|c| c.set(ptr::null_mut()));
+}
+
+pub unsafe fn ca_flush_io() -> c_int {
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_client_status(level: c_uint) -> c_int {
+    match current() {
+        Some(_) => {
+            Database::global().print_status(level);
+            sys::ECA_NORMAL
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_create_channel(
+    name: *const c_char,
+    conn_cb: caCh,
+    puser: *mut c_void,
+    priority: capri,
+    pchid: *mut chid,
+) -> c_int {
+    if priority > sys::CA_PRIORITY_MAX as capri {
+        return sys::ECA_BADPRIORITY;
+    }
+    let ctx = match current() {
+        Some(ctx) => ctx,
+        None => return sys::ECA_NOCACTX,
+    };
+    let name = CStr::from_ptr(name);
+    if name.to_bytes().is_empty() {
+        return sys::ECA_EMPTYSTR;
+    }
+    let chan = Arc::new(ChannelState {
+        name: name.to_owned(),
+        puser: UserPtr(puser),
+        conn_cb,
+        access_cb: Mutex::new(None),
+        worker: ctx.worker.clone(),
+        alive: Mutex::new(true),
+        pv: Mutex::new(None),
+        subscriptions: Mutex::new(Vec::new()),
+    });
+    Database::global().connect(&chan);
+    *pchid = Arc::into_raw(chan) as chid;
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_clear_channel(chan: chid) -> c_int {
+    let chan = Arc::from_raw(chan as *const ChannelState);
+    *chan.alive.lock().unwrap() = false;
+    for sub in chan.subscriptions.lock().unwrap().drain(..) {
+        *sub.active.lock().unwrap() = false;
+    }
+    let pv = chan.pv.lock().unwrap().take();
+    match pv {
+        Some(pv) => pv.detach(&*chan),
+        None => Database::global().release(&chan),
+    }
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_name(chan: chid) -> *const c_char {
+    (*(chan as *const ChannelState)).name.as_ptr()
+}
+
+pub unsafe fn ca_puser(chan: chid) -> *mut c_void {
+    (*(chan as *const ChannelState)).puser.get()
+}
+
+pub unsafe fn ca_host_name(chan: chid) -> *const c_char {
+    const HOST: &[u8] = b"mock\0";
+    const DISCONN_HOST: &[u8] = b"<disconnected>\0";
+    match (*(chan as *const ChannelState)).pv() {
+        Some(_) => HOST.as_ptr() as *const c_char,
+        None => DISCONN_HOST.as_ptr() as *const c_char,
+    }
+}
+
+pub unsafe fn ca_field_type(chan: chid) -> c_short {
+    match (*(chan as *const ChannelState)).pv() {
+        Some(pv) => pv.field_type().raw() as c_short,
+        None => sys::TYPENOTCONN as c_short,
+    }
+}
+
+pub unsafe fn ca_element_count(chan: chid) -> c_ulong {
+    match (*(chan as *const ChannelState)).pv() {
+        Some(pv) => pv.max_len() as c_ulong,
+        None => 0,
+    }
+}
+
+unsafe fn access(chan: chid) -> AccessRights {
+    match (*(chan as *const ChannelState)).pv() {
+        Some(pv) => pv.access_rights(),
+        None => AccessRights::default(),
+    }
+}
+
+pub unsafe fn ca_read_access(chan: chid) -> c_uint {
+    access(chan).read_access() as c_uint
+}
+
+pub unsafe fn ca_write_access(chan: chid) -> c_uint {
+    access(chan).write_access() as c_uint
+}
+
+pub unsafe fn ca_replace_access_rights_event(chan: chid, func: caArh) -> c_int {
+    let chan = channel(chan);
+    *chan.access_cb.lock().unwrap() = func;
+    if let Some(pv) = chan.pv() {
+        chan.notify_access(pv.access_rights());
+    }
+    sys::ECA_NORMAL
+}
+
+fn check_count(pv: &Pv, count: c_ulong) -> Result<usize, c_int> {
+    let count = count as usize;
+    if count > pv.max_len() {
+        Err(sys::ECA_BADCOUNT)
+    } else {
+        Ok(count)
+    }
+}
+
+fn request_id(type_: chtype) -> Result<RequestId, c_int> {
+    RequestId::try_from_raw(type_ as i32).ok_or(sys::ECA_BADTYPE)
+}
+
+pub unsafe fn ca_array_get_callback(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+) -> c_int {
+    let chan = channel(chan);
+    let pv = match chan.pv() {
+        Some(pv) => pv,
+        None => return sys::ECA_DISCONN,
+    };
+    let (id, count) = match request_id(type_).and_then(|id| Ok((id, check_count(&pv, count)?))) {
+        Ok(x) => x,
+        Err(eca) => return eca,
+    };
+    if !pv.access_rights().read_access() {
+        return sys::ECA_NORDACCESS;
+    }
+    let result = pv.read(id, count);
+    let usr = UserPtr(usr);
+    chan.enqueue(move |chid| call_event(func, usr, chid, id, result));
+    sys::ECA_NORMAL
+}
+
+/// Check write request and perform it.
+///
+/// Returns number of elements and status of the write.
+unsafe fn write(
+    type_: chtype,
+    count: c_ulong,
+    chan: &ChannelState,
+    value: *const c_void,
+) -> Result<(usize, c_int), c_int> {
+    let pv = chan.pv().ok_or(sys::ECA_DISCONN)?;
+    let field = match request_id(type_)? {
+        RequestId::Base(field) => field,
+        _ => return Err(sys::ECA_BADTYPE),
+    };
+    let count = check_count(&pv, count)?;
+    if !pv.access_rights().write_access() {
+        return Err(sys::ECA_NOWTACCESS);
+    }
+    let status = match pv.write(convert::decode(field, value, count)) {
+        Ok(()) => sys::ECA_NORMAL,
+        Err(_) => sys::ECA_PUTFAIL,
+    };
+    Ok((count, status))
+}
+
+pub unsafe fn ca_array_put(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    value: *const c_void,
+) -> c_int {
+    let chan = channel(chan);
+    match write(type_, count, &chan, value) {
+        Ok((count, status)) => {
+            if status != sys::ECA_NORMAL {
+                // Failure of the write itself is reported asynchronously.
+                chan.exception(status, type_, count, c"Write failed");
+            }
+            sys::ECA_NORMAL
+        }
+        Err(eca) => eca,
+    }
+}
+
+pub unsafe fn ca_array_put_callback(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    value: *const c_void,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+) -> c_int {
+    let chan = channel(chan);
+    let (count, status) = match write(type_, count, &chan, value) {
+        Ok(result) => result,
+        Err(eca) => return eca,
+    };
+    let usr = UserPtr(usr);
+    chan.enqueue(move |chid| {
+        if let Some(func) = func {
+            func(event_handler_args {
+                usr: usr.get(),
+                chid,
+                type_,
+                count: count as c_long,
+                dbr: ptr::null(),
+                status,
+            })
+        }
+    });
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_create_subscription(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    mask: c_long,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+    pevid: *mut evid,
+) -> c_int {
+    let chan = channel(chan);
+    let id = match request_id(type_) {
+        Ok(id) => id,
+        Err(eca) => return eca,
+    };
+    let mask = match EventMask::try_from_raw(mask as i32) {
+        Some(mask) if !mask.is_empty() => mask,
+        _ => return sys::ECA_BADMASK,
+    };
+    let pv = chan.pv();
+    if let Some(pv) = &pv {
+        if let Err(eca) = check_count(pv, count) {
+            return eca;
+        }
+    }
+    let sub = Arc::new(SubscriptionState {
+        channel: Arc::downgrade(&chan),
+        id,
+        count: count as usize,
+        mask,
+        func,
+        usr: UserPtr(usr),
+        active: Mutex::new(true),
+    });
+    chan.subscriptions.lock().unwrap().push(sub.clone());
+    if let Some(pv) = pv {
+        sub.post(&chan, &pv.state());
+    }
+    *pevid = Arc::into_raw(sub) as evid;
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_clear_subscription(evid: evid) -> c_int {
+    let sub = Arc::from_raw(evid as *const SubscriptionState);
+    *sub.active.lock().unwrap() = false;
+    if let Some(chan) = sub.channel.upgrade() {
+        chan.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, &sub));
+    }
+    sys::ECA_NORMAL
+}
@@ -0,0 +1,218 @@
+use super::Database;
+use crate::{
+    channel::{Lossy, MonitorEvent, Overflow},
+    error,
+    request::{DynCtrl, DynKind},
+    types::{AccessRights, Alarm, AlarmSeverity, DynValue, FieldId},
+    Channel, Context,
+};
+use async_std::test as async_test;
+use cstr::cstr;
+use futures::{pin_mut, FutureExt, StreamExt};
+use serial_test::serial;
+
+#[async_test]
+#[serial]
+async fn connect_later() {
+    let db = Database::global();
+    let ctx = Context::new().unwrap();
+    let channel = Channel::new(&ctx, cstr!("mock:test:later")).unwrap();
+    assert!(!channel.is_connected());
+
+    db.add("mock:test:later", 1i32);
+    channel.connected().await;
+    assert_eq!(channel.element_count().unwrap(), 1);
+    assert_eq!(channel.host_name().unwrap(), cstr!("mock"));
+
+    let events = channel.connection_events();
+    pin_mut!(events);
+    db.remove("mock:test:later").unwrap();
+    assert!(!events.next().await.unwrap().is_connected());
+    assert_eq!(channel.field_type(), Err(error::DISCONN));
+
+    db.add("mock:test:later", 2i32);
+    assert!(events.next().await.unwrap().is_connected());
+    db.remove("mock:test:later");
+}
+
+#[async_test]
+#[serial]
+async fn write_from_pv() {
+    let pv = Database::global().add("mock:test:write", vec![0.0; 4]);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<[f64]>(cstr!("mock:test:write"))
+        .await
+        .unwrap();
+
+    let monitor = channel.subscribe_vec();
+    pin_mut!(monitor);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), [0.0; 4]);
+
+    pv.set_value(vec![1, 2]).unwrap();
+    assert_eq!(monitor.next().await.unwrap().unwrap(), [1.0, 2.0]);
+    assert_eq!(pv.set_value(vec![0; 5]), Err(error::BADCOUNT));
+    Database::global().remove("mock:test:write");
+}
+
+#[async_test]
+#[serial]
+async fn metadata() {
+    let pv = Database::global().add("mock:test:meta", 1.5);
+    pv.set_units("mm");
+    pv.set_precision(3);
+    pv.set_alarm(Alarm {
+        severity: AlarmSeverity::Major,
+        ..Default::default()
+    });
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<f64>(cstr!("mock:test:meta")).await.unwrap();
+
+    let ctrl = channel.get_dyn(DynKind::Ctrl).unwrap().await.unwrap();
+    assert_eq!(ctrl.value(), &DynValue::from(1.5));
+    assert_eq!(ctrl.alarm().unwrap().severity, AlarmSeverity::Major);
+    match ctrl.ctrl().unwrap() {
+        DynCtrl::Double {
+            precision, units, ..
+        } => {
+            assert_eq!(*precision, 3);
+            assert_eq!(units.0.to_str().unwrap(), "mm");
+        }
+        other => panic!("unexpected ctrl: {:?}", other),
+    }
+    Database::global().remove("mock:test:meta");
+}
+
+#[async_test]
+#[serial]
+async fn access_rights() {
+    let pv = Database::global().add("mock:test:access", 0i16);
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<i16>(cstr!("mock:test:access")).await.unwrap();
+    assert!(channel.access_rights().write_access());
+
+    let events = channel.access_rights_events();
+    pin_mut!(events);
+    pv.set_access_rights(AccessRights::new(true, false));
+    assert_eq!(events.next().await.unwrap(), AccessRights::new(true, false));
+    assert_eq!(channel.put(1).err(), Some(error::NOWTACCESS));
+    assert_eq!(channel.get().await.unwrap(), 0);
+    Database::global().remove("mock:test:access");
+}
+
+#[async_test]
+#[serial]
+async fn monitor_reconnect() {
+    let db = Database::global();
+    db.add("mock:test:monitor", 1i32);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<i32>(cstr!("mock:test:monitor"))
+        .await
+        .unwrap();
+
+    let monitor = channel.subscribe_buffered().into_monitor();
+    pin_mut!(monitor);
+    assert_eq!(monitor.next().await.unwrap(), Ok(MonitorEvent::Value(1)));
+
+    db.remove("mock:test:monitor").unwrap();
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Disconnected)
+    );
+    assert!(!monitor.is_connected());
+
+    db.add("mock:test:monitor", 2i32);
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Reconnected {
+            field_type: FieldId::Long,
+            count: 1
+        })
+    );
+    assert_eq!(monitor.next().await.unwrap(), Ok(MonitorEvent::Value(2)));
+
+    db.remove("mock:test:monitor").unwrap();
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Disconnected)
+    );
+    let pv = db.add("mock:test:monitor", 3.0f64);
+    assert_eq!(monitor.next().await.unwrap(), Err(error::BADTYPE));
+    pv.set_value(4.0).unwrap();
+    channel.get().await.unwrap();
+    // Values are not yielded while channel type is incompatible.
+    assert!(monitor.next().now_or_never().is_none());
+
+    db.remove("mock:test:monitor").unwrap();
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Disconnected)
+    );
+    db.add("mock:test:monitor", vec![0i32; 4]);
+    assert_eq!(monitor.next().await.unwrap(), Err(error::BADCOUNT));
+
+    db.remove("mock:test:monitor").unwrap();
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Disconnected)
+    );
+    db.add("mock:test:monitor", 5i32);
+    assert_eq!(
+        monitor.next().await.unwrap(),
+        Ok(MonitorEvent::Reconnected {
+            field_type: FieldId::Long,
+            count: 1
+        })
+    );
+    assert_eq!(monitor.next().await.unwrap(), Ok(MonitorEvent::Value(5)));
+    db.remove("mock:test:monitor");
+}
+
+#[async_test]
+#[serial]
+async fn subscribe_bounded() {
+    let pv = Database::global().add("mock:test:bounded", 0i32);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<i32>(cstr!("mock:test:bounded"))
+        .await
+        .unwrap();
+
+    for (overflow, expected) in [
+        (Overflow::DropOldest, [(4, 3), (5, 0)]),
+        (Overflow::DropNewest, [(1, 0), (2, 0)]),
+        (Overflow::Coalesce, [(1, 0), (5, 3)]),
+    ] {
+        pv.set_value(0i32).unwrap();
+        let monitor = channel.subscribe_bounded(2, overflow);
+        pin_mut!(monitor);
+        assert_eq!(monitor.next().await.unwrap().unwrap().value, 0);
+
+        for x in 1..=5 {
+            pv.set_value(x).unwrap();
+        }
+        // Read is processed after all preceding updates are delivered.
+        assert_eq!(channel.get().await.unwrap(), 5);
+        for (value, dropped) in expected {
+            assert_eq!(
+                monitor.next().await.unwrap(),
+                Ok(Lossy { value, dropped }),
+                "{:?}",
+                overflow
+            );
+        }
+        if overflow == Overflow::DropNewest {
+            // Dropped values are reported along with the next stored one.
+            pv.set_value(6).unwrap();
+            assert_eq!(
+                monitor.next().await.unwrap(),
+                Ok(Lossy {
+                    value: 6,
+                    dropped: 3
+                })
+            );
+        }
+    }
+    Database::global().remove("mock:test:bounded");
+}
@@ -0,0 +1,309 @@
+use crate::{
+    request::{
+        CtrlEnum, CtrlFloat, CtrlInt, CtrlString, GrEnum, GrFloat, GrInt, GrString, Sts,
+        StsackString, Time,
+    },
+    types::{EpicsEnum, EpicsString, Field, FieldId, RequestId},
+};
+use std::{
+    mem::{self, size_of},
+    ptr,
+};
+
+/// Memory layout of the request.
+///
+/// Request structures have the same layout as DBR types on the wire
+/// except byte order of numeric fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// Offsets and sizes of numeric metadata fields.
+    fields: Vec<(usize, usize)>,
+    /// Offset of the value.
+    value: usize,
+    item_size: usize,
+    swap_items: bool,
+    struct_size: usize,
+}
+
+/// Offset of the field.
+fn offset<R, T>(base: &R, field: &T) -> usize {
+    field as *const T as usize - base as *const R as usize
+}
+
+impl Layout {
+    fn new<R, T: Field>(req: &R, value: &T, fields: Vec<(usize, usize)>) -> Self {
+        Self {
+            fields,
+            value: offset(req, value),
+            item_size: size_of::<T>(),
+            swap_items: T::ID != FieldId::String,
+            struct_size: size_of::<R>(),
+        }
+    }
+
+    /// Layout of the request or `None` if request is unknown.
+    pub fn of(id: RequestId) -> Option<Self> {
+        let (kind, field) = match id {
+            RequestId::Base(field) => (Kind::Base, field),
+            RequestId::Sts(field) => (Kind::Sts, field),
+            RequestId::Time(field) => (Kind::Time, field),
+            RequestId::Gr(field) => (Kind::Gr, field),
+            RequestId::Ctrl(field) => (Kind::Ctrl, field),
+            RequestId::PutAckt | RequestId::PutAcks => {
+                return Some(Self {
+                    fields: Vec::new(),
+                    value: 0,
+                    item_size: size_of::<u16>(),
+                    swap_items: true,
+                    struct_size: size_of::<u16>(),
+                });
+            }
+            RequestId::StsackString => {
+                let req: StsackString<EpicsString> = unsafe { mem::zeroed() };
+                let mut fields = alarm_fields();
+                fields.push((offset(&req, &req.ackt), 2));
+                fields.push((offset(&req, &req.acks), 2));
+                return Some(Self::new(&req, &req.value, fields));
+            }
+            RequestId::ClassName => {
+                let req: EpicsString = unsafe { mem::zeroed() };
+                return Some(Self::new(&req, &req, Vec::new()));
+            }
+        };
+        Some(match field {
+            FieldId::String => EpicsString::layout(kind),
+            FieldId::Short => i16::layout(kind),
+            FieldId::Float => f32::layout(kind),
+            FieldId::Enum => EpicsEnum::layout(kind),
+            FieldId::Char => u8::layout(kind),
+            FieldId::Long => i32::layout(kind),
+            FieldId::Double => f64::layout(kind),
+        })
+    }
+
+    /// Size of the request with `count` elements without padding.
+    pub fn size(&self, count: usize) -> usize {
+        self.value + self.item_size * count
+    }
+    /// Size of memory needed to store the request with `count` elements.
+    pub fn alloc_size(&self, count: usize) -> usize {
+        usize::max(self.struct_size, self.size(count))
+    }
+
+    /// Convert request of `count` elements between native and network byte order.
+    ///
+    /// Conversion is symmetric, so it is used in both directions.
+    pub fn swap(&self, data: &mut [u8], count: usize) {
+        if cfg!(target_endian = "big") {
+            return;
+        }
+        for (offset, size) in self.fields.iter() {
+            data[*offset..(*offset + *size)].reverse();
+        }
+        if self.swap_items && self.item_size > 1 {
+            let end = usize::min(self.size(count), data.len());
+            for item in data[self.value..end].chunks_exact_mut(self.item_size) {
+                item.reverse();
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Base,
+    Sts,
+    Time,
+    Gr,
+    Ctrl,
+}
+
+fn alarm_fields() -> Vec<(usize, usize)> {
+    vec![(0, 2), (2, 2)]
+}
+
+trait DbrLayout: Field {
+    fn layout(kind: Kind) -> Layout;
+}
+
+fn plain_layout<T: Field>(kind: Kind) -> Layout {
+    match kind {
+        Kind::Base => {
+            let req: T = unsafe { mem::zeroed() };
+            Layout::new(&req, &req, Vec::new())
+        }
+        Kind::Sts => {
+            let req: Sts<T> = unsafe { mem::zeroed() };
+            Layout::new(&req, &req.value, alarm_fields())
+        }
+        Kind::Time => {
+            let req: Time<T> = unsafe { mem::zeroed() };
+            let mut fields = alarm_fields();
+            fields.push((offset(&req, &req.stamp), 4));
+            fields.push((offset(&req, &req.stamp) + 4, 4));
+            Layout::new(&req, &req.value, fields)
+        }
+        Kind::Gr | Kind::Ctrl => unreachable!(),
+    }
+}
+
+macro_rules! limit_fields {
+    ($req:expr, $fields:expr, $type:ty, [$($limit:ident),*]) => {
+        $(
+            $fields.push((offset(&$req, &$req.$limit), size_of::<$type>()));
+        )*
+    };
+}
+
+macro_rules! impl_layout_number {
+    ($type:ty, $gr:ident, $ctrl:ident, $($precision:ident)?) => {
+        impl DbrLayout for $type {
+            fn layout(kind: Kind) -> Layout {
+                match kind {
+                    Kind::Gr => {
+                        let req: $gr<$type> = unsafe { mem::zeroed() };
+                        let mut fields = alarm_fields();
+                        $(fields.push((offset(&req, &req.$precision), 2));)?
+                        limit_fields!(req, fields, $type, [
+                            upper_disp_limit, lower_disp_limit,
+                            upper_alarm_limit, upper_warning_limit,
+                            lower_warning_limit, lower_alarm_limit
+                        ]);
+                        Layout::new(&req, &req.value, fields)
+                    }
+                    Kind::Ctrl => {
+                        let req: $ctrl<$type> = unsafe { mem::zeroed() };
+                        let mut fields = alarm_fields();
+                        $(fields.push((offset(&req, &req.$precision), 2));)?
+                        limit_fields!(req, fields, $type, [
+                            upper_disp_limit, lower_disp_limit,
+                            upper_alarm_limit, upper_warning_limit,
+                            lower_warning_limit, lower_alarm_limit,
+                            upper_ctrl_limit, lower_ctrl_limit
+                        ]);
+                        Layout::new(&req, &req.value, fields)
+                    }
+                    _ => plain_layout::<$type>(kind),
+                }
+            }
+        }
+    };
+}
+
+impl_layout_number!(i16, GrInt, CtrlInt,);
+impl_layout_number!(u8, GrInt, CtrlInt,);
+impl_layout_number!(i32, GrInt, CtrlInt,);
+impl_layout_number!(f32, GrFloat, CtrlFloat, precision);
+impl_layout_number!(f64, GrFloat, CtrlFloat, precision);
+
+impl DbrLayout for EpicsEnum {
+    fn layout(kind: Kind) -> Layout {
+        match kind {
+            Kind::Gr => {
+                let req: GrEnum<EpicsEnum> = unsafe { mem::zeroed() };
+                let mut fields = alarm_fields();
+                fields.push((offset(&req, &req.no_str), 2));
+                Layout::new(&req, &req.value, fields)
+            }
+            Kind::Ctrl => {
+                let req: CtrlEnum<EpicsEnum> = unsafe { mem::zeroed() };
+                let mut fields = alarm_fields();
+                fields.push((offset(&req, &req.no_str), 2));
+                Layout::new(&req, &req.value, fields)
+            }
+            _ => plain_layout::<EpicsEnum>(kind),
+        }
+    }
+}
+
+impl DbrLayout for EpicsString {
+    fn layout(kind: Kind) -> Layout {
+        match kind {
+            Kind::Gr => {
+                let req: GrString<EpicsString> = unsafe { mem::zeroed() };
+                Layout::new(&req, &req.value, alarm_fields())
+            }
+            Kind::Ctrl => {
+                let req: CtrlString<EpicsString> = unsafe { mem::zeroed() };
+                Layout::new(&req, &req.value, alarm_fields())
+            }
+            _ => plain_layout::<EpicsString>(kind),
+        }
+    }
+}
+
+/// Request data with alignment suitable for any request structure.
+pub(crate) struct AlignedBuffer {
+    data: Vec<u64>,
+}
+
+impl AlignedBuffer {
+    pub fn new(bytes: &[u8], alloc_size: usize) -> Self {
+        let mut data = vec![0u64; usize::max(alloc_size, bytes.len()).div_ceil(8)];
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), data.as_mut_ptr() as *mut u8, bytes.len())
+        };
+        Self { data }
+    }
+    pub fn as_ptr(&self) -> *const u8 {
+        self.data.as_ptr() as *const u8
+    }
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut u8, self.data.len() * 8)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{Request, TypedRequest};
+
+    #[test]
+    fn sizes() {
+        // Sizes of `dbr_*` structures from `db_access.h`.
+        assert_eq!(
+            Layout::of(RequestId::Time(FieldId::Double))
+                .unwrap()
+                .size(1),
+            24
+        );
+        assert_eq!(
+            Layout::of(RequestId::Ctrl(FieldId::Double))
+                .unwrap()
+                .size(1),
+            88
+        );
+        assert_eq!(
+            Layout::of(RequestId::Ctrl(FieldId::Enum)).unwrap().size(1),
+            424
+        );
+        assert_eq!(
+            Layout::of(RequestId::Gr(FieldId::Short)).unwrap().size(1),
+            26
+        );
+        assert_eq!(
+            Layout::of(RequestId::Sts(FieldId::String)).unwrap().size(1),
+            44
+        );
+    }
+
+    #[test]
+    fn swap() {
+        let id = Time::<[i32]>::ID;
+        let layout = Layout::of(id).unwrap();
+        let mut wire = vec![0u8; layout.size(2)];
+        wire[2..4].copy_from_slice(&2u16.to_be_bytes());
+        wire[4..8].copy_from_slice(&7u32.to_be_bytes());
+        wire[12..16].copy_from_slice(&(-1i32).to_be_bytes());
+        wire[16..20].copy_from_slice(&5i32.to_be_bytes());
+
+        let mut buffer = AlignedBuffer::new(&wire, layout.alloc_size(2));
+        layout.swap(buffer.as_mut_slice(), 2);
+        let req = unsafe { Time::<[i32]>::from_ptr(buffer.as_ptr(), id, 2) }.unwrap();
+        assert_eq!(req.value(), [-1, 5]);
+        assert_eq!(req.stamp.sec(), 7);
+        assert_eq!(req.alarm.severity.raw() as u32, 2);
+    }
+}
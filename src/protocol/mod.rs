@@ -0,0 +1,277 @@
+//! Channel Access wire protocol.
+//!
+//! Messages consist of header and optional payload padded to 8 bytes.
+//! All numbers are transmitted in big-endian byte order.
+
+mod dbr;
+
+pub(crate) use dbr::{AlignedBuffer, Layout};
+
+use std::{
+    env,
+    io::{self, Read, Write},
+};
+
+/// Minor protocol version supported by the crate.
+pub(crate) const MINOR_VERSION: u16 = 13;
+pub(crate) const DEFAULT_SERVER_PORT: u16 = 5064;
+pub(crate) const DEFAULT_REPEATER_PORT: u16 = 5065;
+
+/// Message commands.
+#[allow(dead_code)]
+pub(crate) mod command {
+    pub const VERSION: u16 = 0;
+    pub const EVENT_ADD: u16 = 1;
+    pub const EVENT_CANCEL: u16 = 2;
+    pub const WRITE: u16 = 4;
+    pub const SEARCH: u16 = 6;
+    pub const EVENTS_OFF: u16 = 8;
+    pub const EVENTS_ON: u16 = 9;
+    pub const ERROR: u16 = 11;
+    pub const CLEAR_CHANNEL: u16 = 12;
+    pub const RSRV_IS_UP: u16 = 13;
+    pub const NOT_FOUND: u16 = 14;
+    pub const READ_NOTIFY: u16 = 15;
+    pub const REPEATER_CONFIRM: u16 = 17;
+    pub const CREATE_CHAN: u16 = 18;
+    pub const WRITE_NOTIFY: u16 = 19;
+    pub const CLIENT_NAME: u16 = 20;
+    pub const HOST_NAME: u16 = 21;
+    pub const ACCESS_RIGHTS: u16 = 22;
+    pub const ECHO: u16 = 23;
+    pub const REPEATER_REGISTER: u16 = 24;
+    pub const CREATE_CH_FAIL: u16 = 26;
+    pub const SERVER_DISCONN: u16 = 27;
+}
+
+/// Search request flag that asks server not to reply if it doesn't have the channel.
+pub(crate) const DONT_REPLY: u16 = 5;
+
+/// Access rights bits of `ACCESS_RIGHTS` message.
+pub(crate) const ACCESS_READ: u32 = 1 << 0;
+pub(crate) const ACCESS_WRITE: u32 = 1 << 1;
+
+const HEADER_SIZE: usize = 16;
+const EXTENDED_SIZE: usize = 8;
+
+/// Array size limit used when `EPICS_CA_MAX_ARRAY_BYTES` isn't set, the same as in EPICS.
+const DEFAULT_MAX_ARRAY_BYTES: usize = 16384;
+/// Room for DBR metadata that precedes array in payload.
+const MAX_META_SIZE: usize = 1024;
+
+/// Maximum payload size of messages read from stream.
+///
+/// If `max_array_bytes` is `None` it is taken from `EPICS_CA_MAX_ARRAY_BYTES` environment variable.
+pub(crate) fn max_payload_size(max_array_bytes: Option<usize>) -> usize {
+    max_array_bytes
+        .or_else(|| {
+            env::var("EPICS_CA_MAX_ARRAY_BYTES")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+        })
+        .unwrap_or(DEFAULT_MAX_ARRAY_BYTES)
+        .saturating_add(MAX_META_SIZE)
+}
+
+/// Message header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub command: u16,
+    pub payload_size: u32,
+    pub data_type: u16,
+    pub data_count: u32,
+    pub param1: u32,
+    pub param2: u32,
+}
+
+impl Header {
+    pub fn new(command: u16) -> Self {
+        Self {
+            command,
+            ..Default::default()
+        }
+    }
+    fn is_extended(&self) -> bool {
+        self.payload_size >= 0xffff || self.data_count >= 0xffff
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let extended = self.is_extended();
+        buf.extend_from_slice(&self.command.to_be_bytes());
+        if extended {
+            buf.extend_from_slice(&0xffffu16.to_be_bytes());
+        } else {
+            buf.extend_from_slice(&(self.payload_size as u16).to_be_bytes());
+        }
+        buf.extend_from_slice(&self.data_type.to_be_bytes());
+        if extended {
+            buf.extend_from_slice(&0u16.to_be_bytes());
+        } else {
+            buf.extend_from_slice(&(self.data_count as u16).to_be_bytes());
+        }
+        buf.extend_from_slice(&self.param1.to_be_bytes());
+        buf.extend_from_slice(&self.param2.to_be_bytes());
+        if extended {
+            buf.extend_from_slice(&self.payload_size.to_be_bytes());
+            buf.extend_from_slice(&self.data_count.to_be_bytes());
+        }
+    }
+
+    /// Parse header from the beginning of `data`.
+    ///
+    /// Returns header and number of bytes it occupies or `None` if `data` is too short.
+    pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
+        if data.len() < HEADER_SIZE {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let u32_at = |i: usize| u32::from_be_bytes(data[i..(i + 4)].try_into().unwrap());
+        let mut header = Header {
+            command: u16_at(0),
+            payload_size: u16_at(2) as u32,
+            data_type: u16_at(4),
+            data_count: u16_at(6) as u32,
+            param1: u32_at(8),
+            param2: u32_at(12),
+        };
+        if header.payload_size == 0xffff && header.data_count == 0 {
+            if data.len() < HEADER_SIZE + EXTENDED_SIZE {
+                return None;
+            }
+            header.payload_size = u32_at(16);
+            header.data_count = u32_at(20);
+            Some((header, HEADER_SIZE + EXTENDED_SIZE))
+        } else {
+            Some((header, HEADER_SIZE))
+        }
+    }
+}
+
+/// Size of payload padded to 8 bytes.
+pub(crate) fn padded(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// Complete message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Message {
+    pub header: Header,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(header: Header) -> Self {
+        Self {
+            header,
+            payload: Vec::new(),
+        }
+    }
+    /// Message with payload. Payload is padded and its size is stored in header.
+    pub fn with_payload(mut header: Header, mut payload: Vec<u8>) -> Self {
+        payload.resize(padded(payload.len()), 0);
+        header.payload_size = payload.len() as u32;
+        Self { header, payload }
+    }
+    /// Message with string payload terminated by zero.
+    #[cfg(feature = "pure-rust")]
+    pub fn with_str(header: Header, s: &[u8]) -> Self {
+        let mut payload = s.to_vec();
+        payload.push(0);
+        Self::with_payload(header, payload)
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        self.header.encode(buf);
+        buf.extend_from_slice(&self.payload);
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Parse all messages from datagram.
+    pub fn decode_all(mut data: &[u8]) -> Vec<Self> {
+        let mut messages = Vec::new();
+        while let Some((header, size)) = Header::decode(data) {
+            let end = size + header.payload_size as usize;
+            if data.len() < end {
+                break;
+            }
+            messages.push(Message {
+                header,
+                payload: data[size..end].to_vec(),
+            });
+            data = &data[end..];
+        }
+        messages
+    }
+
+    /// Read single message from stream.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if payload is larger than `max_payload_size`.
+    pub fn read_from<R: Read>(reader: &mut R, max_payload_size: usize) -> io::Result<Self> {
+        let mut buf = vec![0; HEADER_SIZE];
+        reader.read_exact(&mut buf)?;
+        if u16::from_be_bytes([buf[2], buf[3]]) == 0xffff
+            && u16::from_be_bytes([buf[6], buf[7]]) == 0
+        {
+            buf.resize(HEADER_SIZE + EXTENDED_SIZE, 0);
+            reader.read_exact(&mut buf[HEADER_SIZE..])?;
+        }
+        let (header, _) = Header::decode(&buf).unwrap();
+        if header.payload_size as usize > max_payload_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Message payload is too large: {}", header.payload_size),
+            ));
+        }
+        let mut payload = vec![0; header.payload_size as usize];
+        reader.read_exact(&mut payload)?;
+        Ok(Message { header, payload })
+    }
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut header = Header::new(command::READ_NOTIFY);
+        header.data_type = 6;
+        header.data_count = 3;
+        header.param1 = 1;
+        header.param2 = 2;
+        let message = Message::with_payload(header, vec![1, 2, 3]);
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 16 + 8);
+        assert_eq!(
+            Message::read_from(&mut bytes.as_slice(), max_payload_size(None)).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn extended_header() {
+        let mut header = Header::new(command::EVENT_ADD);
+        header.data_count = 0x10000;
+        let message = Message::with_payload(header, vec![0; 0x10000]);
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 24 + 0x10000);
+        assert_eq!(
+            Message::read_from(&mut bytes.as_slice(), 0x10000).unwrap(),
+            message
+        );
+        assert_eq!(
+            Message::read_from(&mut bytes.as_slice(), 0x8000)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(Message::decode_all(&bytes), [message]);
+    }
+}
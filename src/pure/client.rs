@@ -0,0 +1,1154 @@
+use crate::{
+    context::ContextConfig,
+    protocol::{
+        command, max_payload_size, AlignedBuffer, Header, Layout, Message, ACCESS_READ,
+        ACCESS_WRITE, DEFAULT_REPEATER_PORT, DEFAULT_SERVER_PORT, DONT_REPLY, MINOR_VERSION,
+    },
+    trace,
+    types::{AccessRights, EventMask, RequestId},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env,
+    ffi::{c_void, CStr, CString},
+    io::{self, BufReader},
+    net::{
+        IpAddr, Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream, ToSocketAddrs, UdpSocket,
+    },
+    os::raw::{c_char, c_long},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Condvar, Mutex, OnceLock, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use sys::{
+    access_rights_handler_args, caArh, caCh, caEventCallBackFunc, caExceptionHandler, chid,
+    connection_handler_args, event_handler_args, exception_handler_args,
+};
+
+const MIN_SEARCH_INTERVAL: Duration = Duration::from_millis(32);
+const MAX_SEARCH_INTERVAL: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_CONN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time to wait for reply to echo request, limited by connection timeout.
+const ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_DATAGRAM_SIZE: usize = 1400;
+
+/// Client configuration.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    /// Addresses where search requests are sent.
+    pub addr_list: Vec<SocketAddr>,
+    pub repeater_port: u16,
+    /// Circuit that received nothing for this time is probed by echo request.
+    pub conn_timeout: Duration,
+    /// Limit of payload size of messages received from servers.
+    pub max_payload_size: usize,
+}
+
+impl Config {
+    /// Take settings of the context, missing ones are taken from standard EPICS environment variables.
+    pub fn new(settings: &ContextConfig) -> Self {
+        let port = |name: &str, default: u16| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        let server_port = settings
+            .server_port
+            .unwrap_or_else(|| port("EPICS_CA_SERVER_PORT", DEFAULT_SERVER_PORT));
+        let addrs = match &settings.addr_list {
+            Some(addrs) => addrs.clone(),
+            None => env::var("EPICS_CA_ADDR_LIST")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+        };
+        let mut addr_list: Vec<SocketAddr> = addrs
+            .iter()
+            .filter_map(|s| parse_addr(s, server_port))
+            .collect();
+        let auto = settings.auto_addr_list.unwrap_or_else(|| {
+            env::var("EPICS_CA_AUTO_ADDR_LIST")
+                .map(|s| !s.trim().eq_ignore_ascii_case("no"))
+                .unwrap_or(true)
+        });
+        if auto {
+            addr_list.push(SocketAddrV4::new(Ipv4Addr::BROADCAST, server_port).into());
+        }
+        let conn_timeout = settings.conn_timeout.unwrap_or_else(|| {
+            env::var("EPICS_CA_CONN_TMO")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .unwrap_or(DEFAULT_CONN_TIMEOUT)
+        });
+        Self {
+            addr_list,
+            repeater_port: port("EPICS_CA_REPEATER_PORT", DEFAULT_REPEATER_PORT),
+            conn_timeout,
+            max_payload_size: max_payload_size(settings.max_array_bytes),
+        }
+    }
+}
+
+/// Parse `host[:port]` address.
+fn parse_addr(s: &str, default_port: u16) -> Option<SocketAddr> {
+    let with_port = if s.contains(':') {
+        s.to_string()
+    } else {
+        format!("{}:{}", s, default_port)
+    };
+    with_port
+        .to_socket_addrs()
+        .ok()?
+        .find(|addr| addr.is_ipv4())
+}
+
+/// Host name that is kept for the whole program lifetime, so it can be returned as raw pointer.
+fn intern_host(addr: SocketAddr) -> &'static CStr {
+    static HOSTS: OnceLock<Mutex<HashMap<SocketAddr, &'static CStr>>> = OnceLock::new();
+    let mut hosts = HOSTS.get_or_init(Default::default).lock().unwrap();
+    hosts
+        .entry(addr)
+        .or_insert_with(|| Box::leak(CString::new(addr.to_string()).unwrap().into_boxed_c_str()))
+}
+
+/// Pointer that is passed back to user callbacks.
+#[derive(Clone, Copy)]
+pub(crate) struct UserPtr(*mut c_void);
+
+unsafe impl Send for UserPtr {}
+unsafe impl Sync for UserPtr {}
+
+impl UserPtr {
+    pub fn new(ptr: *mut c_void) -> Self {
+        Self(ptr)
+    }
+    /// Method is used to capture the whole wrapper by closures.
+    pub fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Callbacks waiting to be called by [`Client::poll`] in non-preemptive mode.
+#[derive(Default)]
+struct EventQueue {
+    tasks: Mutex<VecDeque<Task>>,
+    ready: Condvar,
+}
+
+/// Channel Access client that serves single context.
+pub(crate) struct Client {
+    udp: UdpSocket,
+    config: Config,
+    /// Present if callbacks are called only when client is polled.
+    events: Option<EventQueue>,
+    pub exception_handler: Mutex<(caExceptionHandler, UserPtr)>,
+    next_id: AtomicU32,
+    state: Mutex<ClientState>,
+    stopped: AtomicBool,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+struct ClientState {
+    /// Channels that are not connected and should be searched.
+    searching: HashMap<u32, Weak<ChannelState>>,
+    search_interval: Duration,
+    next_search: Instant,
+    /// Separate circuit is used for each server and priority.
+    circuits: HashMap<(SocketAddr, u16), Arc<Circuit>>,
+    /// Channels waiting for circuit which is being connected.
+    connecting: HashMap<(SocketAddr, u16), Vec<Arc<ChannelState>>>,
+    /// Servers which beacons were received.
+    servers: HashSet<SocketAddr>,
+}
+
+impl Client {
+    pub fn new(config: Config, preemptive: bool) -> io::Result<Arc<Self>> {
+        let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        udp.set_broadcast(true)?;
+        udp.set_read_timeout(Some(POLL_INTERVAL))?;
+        let client = Arc::new(Self {
+            udp,
+            config,
+            events: match preemptive {
+                true => None,
+                false => Some(EventQueue::default()),
+            },
+            exception_handler: Mutex::new((None, UserPtr::new(ptr::null_mut()))),
+            next_id: AtomicU32::new(1),
+            state: Mutex::new(ClientState {
+                searching: HashMap::new(),
+                search_interval: MIN_SEARCH_INTERVAL,
+                next_search: Instant::now(),
+                circuits: HashMap::new(),
+                connecting: HashMap::new(),
+                servers: HashSet::new(),
+            }),
+            stopped: AtomicBool::new(false),
+            threads: Mutex::new(Vec::new()),
+        });
+        client.register_repeater();
+        let weak = Arc::downgrade(&client);
+        let thread = thread::Builder::new()
+            .name("ca-udp".into())
+            .spawn(move || udp_loop(weak))?;
+        client.threads.lock().unwrap().push(thread);
+        Ok(client)
+    }
+
+    /// Call `task` now or defer it until [`Self::poll`] in non-preemptive mode.
+    fn dispatch<F: FnOnce() + Send + 'static>(&self, task: F) {
+        match &self.events {
+            Some(events) => {
+                events.tasks.lock().unwrap().push_back(Box::new(task));
+                events.ready.notify_all();
+            }
+            None => task(),
+        }
+    }
+
+    /// Call deferred callbacks during `timeout`.
+    pub fn poll(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let events = match &self.events {
+            Some(events) => events,
+            None => return thread::sleep(timeout),
+        };
+        let mut tasks = events.tasks.lock().unwrap();
+        loop {
+            if let Some(task) = tasks.pop_front() {
+                drop(tasks);
+                task();
+                tasks = events.tasks.lock().unwrap();
+                continue;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            tasks = events.ready.wait_timeout(tasks, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Unique identifier of channel, request or subscription.
+    pub fn next_id(&self) -> u32 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Ask local CA repeater to forward server beacons to us.
+    fn register_repeater(&self) {
+        let message = Message::new(Header::new(command::REPEATER_REGISTER));
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, self.config.repeater_port);
+        // Repeater may be not running, beacons are not required to work.
+        let _ = self.udp.send_to(&message.to_bytes(), addr);
+    }
+
+    /// Start searching channel.
+    pub fn search(&self, channel: &Arc<ChannelState>) {
+        let mut state = self.state.lock().unwrap();
+        state.searching.insert(channel.cid, Arc::downgrade(channel));
+        state.search_interval = MIN_SEARCH_INTERVAL;
+        state.next_search = Instant::now();
+    }
+    /// Forget channel.
+    pub fn release(&self, channel: &ChannelState) {
+        self.state.lock().unwrap().searching.remove(&channel.cid);
+    }
+
+    fn send_search(&self) {
+        let channels = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            if state.searching.is_empty() || now < state.next_search {
+                return;
+            }
+            state.next_search = now + state.search_interval;
+            state.search_interval = Duration::min(state.search_interval * 2, MAX_SEARCH_INTERVAL);
+            state.searching.retain(|_, c| c.strong_count() > 0);
+            state
+                .searching
+                .values()
+                .filter_map(|c| c.upgrade())
+                .collect::<Vec<_>>()
+        };
+
+        let mut version = Header::new(command::VERSION);
+        version.data_count = MINOR_VERSION as u32;
+        let mut datagrams = Vec::new();
+        let mut buf = Vec::new();
+        for channel in channels {
+            let mut header = Header::new(command::SEARCH);
+            header.data_type = DONT_REPLY;
+            header.data_count = MINOR_VERSION as u32;
+            header.param1 = channel.cid;
+            header.param2 = channel.cid;
+            let message = Message::with_str(header, channel.name.to_bytes());
+            if !buf.is_empty() && buf.len() + message.payload.len() + 16 > MAX_DATAGRAM_SIZE {
+                datagrams.push(buf);
+                buf = Vec::new();
+            }
+            if buf.is_empty() {
+                Message::new(version).encode(&mut buf);
+            }
+            message.encode(&mut buf);
+        }
+        datagrams.push(buf);
+        for datagram in datagrams {
+            for addr in self.config.addr_list.iter() {
+                // Unreachable addresses are skipped.
+                let _ = self.udp.send_to(&datagram, addr);
+            }
+        }
+    }
+
+    fn handle_datagram(self: &Arc<Self>, data: &[u8], src: SocketAddr) {
+        for message in Message::decode_all(data) {
+            let header = &message.header;
+            match header.command {
+                command::SEARCH => {
+                    let ip = match header.param1 {
+                        u32::MAX => src.ip(),
+                        ip => IpAddr::V4(Ipv4Addr::from(ip)),
+                    };
+                    self.found(header.param2, SocketAddr::new(ip, header.data_type));
+                }
+                command::RSRV_IS_UP => {
+                    let ip = match header.param2 {
+                        0 => src.ip(),
+                        ip => IpAddr::V4(Ipv4Addr::from(ip)),
+                    };
+                    let server = SocketAddr::new(ip, header.data_type);
+                    let mut state = self.state.lock().unwrap();
+                    // New server appeared, channels may be served by it.
+                    if state.servers.insert(server) {
+                        state.search_interval = MIN_SEARCH_INTERVAL;
+                        state.next_search = Instant::now();
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Channel is found on the server.
+    fn found(self: &Arc<Self>, cid: u32, server: SocketAddr) {
+        let mut state = self.state.lock().unwrap();
+        let channel = match state.searching.remove(&cid).and_then(|c| c.upgrade()) {
+            Some(channel) => channel,
+            None => return,
+        };
+        let key = (server, channel.priority);
+        if let Some(circuit) = state.circuits.get(&key).cloned() {
+            drop(state);
+            return circuit.create_channel(&channel);
+        }
+        let waiting = state.connecting.entry(key).or_default();
+        waiting.push(channel);
+        if waiting.len() > 1 {
+            // Circuit is already being connected.
+            return;
+        }
+        drop(state);
+        // Connection may take long, so it is made by separate thread to not delay other replies.
+        let client = Arc::downgrade(self);
+        let spawned = thread::Builder::new()
+            .name("ca-connect".into())
+            .spawn(move || connect(client, key));
+        match spawned {
+            Ok(thread) => self.threads.lock().unwrap().push(thread),
+            Err(err) => self.connected(key, Err(err)),
+        }
+    }
+
+    /// Connection to the server is made, create channels that wait for it.
+    fn connected(self: &Arc<Self>, key: (SocketAddr, u16), stream: io::Result<TcpStream>) {
+        let circuit = stream.and_then(|stream| self.open_circuit(key, stream));
+        let channels = self
+            .state
+            .lock()
+            .unwrap()
+            .connecting
+            .remove(&key)
+            .unwrap_or_default();
+        for channel in channels {
+            match &circuit {
+                Ok(circuit) => circuit.create_channel(&channel),
+                Err(_) => self.search(&channel),
+            }
+        }
+    }
+
+    /// Start virtual circuit over connected `stream`.
+    fn open_circuit(
+        self: &Arc<Self>,
+        key: (SocketAddr, u16),
+        stream: TcpStream,
+    ) -> io::Result<Arc<Circuit>> {
+        stream.set_nodelay(true)?;
+        // Write that is blocked for long means that server is unresponsive.
+        stream.set_write_timeout(Some(self.echo_timeout()))?;
+        let circuit = Arc::new(Circuit {
+            host: intern_host(key.0),
+            writer: Mutex::new(stream.try_clone()?),
+            state: Mutex::new(CircuitState::default()),
+            heartbeat: Mutex::new(Heartbeat {
+                received: Instant::now(),
+                echo: None,
+            }),
+        });
+        circuit.handshake(key.1);
+
+        // Circuit is registered before reading thread is started, so it is removed when connection is lost.
+        self.state
+            .lock()
+            .unwrap()
+            .circuits
+            .insert(key, circuit.clone());
+        let client = Arc::downgrade(self);
+        let reader = circuit.clone();
+        let max_payload_size = self.config.max_payload_size;
+        let spawned = thread::Builder::new()
+            .name("ca-tcp".into())
+            .spawn(move || tcp_loop(client, reader, key, stream, max_payload_size));
+        match spawned {
+            Ok(thread) => {
+                self.threads.lock().unwrap().push(thread);
+                Ok(circuit)
+            }
+            Err(err) => {
+                self.state.lock().unwrap().circuits.remove(&key);
+                Err(err)
+            }
+        }
+    }
+
+    fn echo_timeout(&self) -> Duration {
+        Duration::min(ECHO_TIMEOUT, self.config.conn_timeout)
+    }
+
+    /// Probe idle circuits and close ones which server doesn't reply in time.
+    fn probe_circuits(&self) {
+        let circuits = self
+            .state
+            .lock()
+            .unwrap()
+            .circuits
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for circuit in circuits {
+            if !circuit.probe(self.config.conn_timeout, self.echo_timeout()) {
+                // Reading thread disconnects channels of the circuit.
+                circuit.close();
+            }
+        }
+    }
+
+    /// Virtual circuit is closed.
+    fn circuit_lost(&self, key: (SocketAddr, u16), circuit: &Circuit) {
+        self.state.lock().unwrap().circuits.remove(&key);
+        let (channels, ios) = {
+            let mut state = circuit.state.lock().unwrap();
+            state.closed = true;
+            state.subscriptions.clear();
+            (
+                state.channels.drain().map(|(_, c)| c).collect::<Vec<_>>(),
+                state.ios.drain().map(|(_, io)| io).collect::<Vec<_>>(),
+            )
+        };
+        for io in ios {
+            io.complete(sys::ECA_DISCONN, None);
+        }
+        for channel in channels {
+            if channel.is_connected() {
+                channel.disconnected();
+            }
+            if *channel.alive.lock().unwrap() {
+                self.search(&channel);
+            }
+        }
+    }
+
+    /// Print summary of circuits, listing their channels if `level` is greater than one.
+    pub fn print_status(&self, level: u32) {
+        let (circuits, searching) = {
+            let state = self.state.lock().unwrap();
+            let mut circuits = state
+                .circuits
+                .iter()
+                .map(|(key, circuit)| (*key, circuit.clone()))
+                .collect::<Vec<_>>();
+            circuits.sort_by_key(|(key, _)| *key);
+            (circuits, state.searching.len())
+        };
+        trace::print(&format!(
+            "Channel Access client: {} circuits, {} channels searching",
+            circuits.len(),
+            searching
+        ));
+        if level == 0 {
+            return;
+        }
+        for ((addr, priority), circuit) in circuits {
+            let state = circuit.state.lock().unwrap();
+            trace::print(&format!(
+                "    Circuit to {} ({}), priority {}: {} channels, {} pending requests, {} subscriptions",
+                circuit.host.to_string_lossy(),
+                addr,
+                priority,
+                state.channels.len(),
+                state.ios.len(),
+                state.subscriptions.len()
+            ));
+            if level > 1 {
+                for chan in state.channels.values() {
+                    trace::print(&format!("        {}", chan.name.to_string_lossy()));
+                }
+            }
+        }
+    }
+
+    /// Stop all threads and close connections.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        for circuit in self.state.lock().unwrap().circuits.values() {
+            circuit.close();
+        }
+        let threads = self.threads.lock().unwrap().drain(..).collect::<Vec<_>>();
+        for thread in threads {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn udp_loop(client: Weak<Client>) {
+    let mut buf = vec![0; 0x10000];
+    loop {
+        let client = match client.upgrade() {
+            Some(client) if !client.stopped.load(Ordering::Acquire) => client,
+            _ => break,
+        };
+        client.send_search();
+        client.probe_circuits();
+        if let Ok((len, src)) = client.udp.recv_from(&mut buf) {
+            client.handle_datagram(&buf[..len], src);
+        }
+    }
+}
+
+/// Connect to the server of the circuit `key`.
+fn connect(client: Weak<Client>, key: (SocketAddr, u16)) {
+    let stream = TcpStream::connect_timeout(&key.0, CONNECT_TIMEOUT);
+    if let Some(client) = client.upgrade() {
+        if client.stopped.load(Ordering::Acquire) {
+            return;
+        }
+        client.connected(key, stream);
+    }
+}
+
+fn tcp_loop(
+    client: Weak<Client>,
+    circuit: Arc<Circuit>,
+    key: (SocketAddr, u16),
+    stream: TcpStream,
+    max_payload_size: usize,
+) {
+    let mut reader = BufReader::new(stream);
+    while let Ok(message) = Message::read_from(&mut reader, max_payload_size) {
+        circuit.received();
+        circuit.handle(&message);
+    }
+    if let Some(client) = client.upgrade() {
+        client.circuit_lost(key, &circuit);
+    }
+}
+
+/// TCP connection to the server.
+pub(crate) struct Circuit {
+    host: &'static CStr,
+    writer: Mutex<TcpStream>,
+    state: Mutex<CircuitState>,
+    heartbeat: Mutex<Heartbeat>,
+}
+
+/// Activity of the server on the circuit.
+struct Heartbeat {
+    /// Time of the last received message.
+    received: Instant,
+    /// Time when echo request was sent, if it is not replied yet.
+    echo: Option<Instant>,
+}
+
+#[derive(Default)]
+struct CircuitState {
+    channels: HashMap<u32, Arc<ChannelState>>,
+    /// Access rights received before channel is created.
+    access: HashMap<u32, AccessRights>,
+    ios: HashMap<u32, PendingIo>,
+    subscriptions: HashMap<u32, Arc<SubscriptionState>>,
+    closed: bool,
+}
+
+impl Circuit {
+    fn send(&self, message: &Message) {
+        let mut writer = self.writer.lock().unwrap();
+        if message.write_to(&mut *writer).is_err() {
+            // Message may be written partially, so reading thread is woken up to close the circuit.
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Close connection. Reading thread exits and disconnects channels.
+    fn close(&self) {
+        let _ = self.writer.lock().unwrap().shutdown(Shutdown::Both);
+    }
+
+    /// Any message from the server proves that it is alive.
+    fn received(&self) {
+        let mut heartbeat = self.heartbeat.lock().unwrap();
+        heartbeat.received = Instant::now();
+        heartbeat.echo = None;
+    }
+
+    /// Send echo request if nothing is received for `idle` time.
+    ///
+    /// Returns `false` if reply to the previous request is not received during `timeout`.
+    fn probe(&self, idle: Duration, timeout: Duration) -> bool {
+        let now = Instant::now();
+        let mut heartbeat = self.heartbeat.lock().unwrap();
+        match heartbeat.echo {
+            Some(sent) => now.duration_since(sent) < timeout,
+            None => {
+                if now.duration_since(heartbeat.received) >= idle {
+                    heartbeat.echo = Some(now);
+                    drop(heartbeat);
+                    self.send(&Message::new(Header::new(command::ECHO)));
+                }
+                true
+            }
+        }
+    }
+
+    fn handshake(&self, priority: u16) {
+        let mut version = Header::new(command::VERSION);
+        version.data_type = priority;
+        version.data_count = MINOR_VERSION as u32;
+        self.send(&Message::new(version));
+        let user = env::var("USER")
+            .or_else(|_| env::var("USERNAME"))
+            .unwrap_or_default();
+        self.send(&Message::with_str(
+            Header::new(command::CLIENT_NAME),
+            user.as_bytes(),
+        ));
+        let host = env::var("HOSTNAME").unwrap_or_else(|_| "localhost".into());
+        self.send(&Message::with_str(
+            Header::new(command::HOST_NAME),
+            host.as_bytes(),
+        ));
+    }
+
+    fn create_channel(&self, channel: &Arc<ChannelState>) {
+        self.state
+            .lock()
+            .unwrap()
+            .channels
+            .insert(channel.cid, channel.clone());
+        let mut header = Header::new(command::CREATE_CHAN);
+        header.param1 = channel.cid;
+        header.param2 = MINOR_VERSION as u32;
+        self.send(&Message::with_str(header, channel.name.to_bytes()));
+    }
+
+    /// Send request that expects reply with `ioid`.
+    pub fn request(&self, header: Header, payload: Vec<u8>, io: PendingIo) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        state.ios.insert(header.param2, io);
+        drop(state);
+        self.send(&Message::with_payload(header, payload));
+        true
+    }
+
+    /// Send request that has no reply.
+    pub fn write(&self, header: Header, payload: Vec<u8>) -> bool {
+        if self.state.lock().unwrap().closed {
+            return false;
+        }
+        self.send(&Message::with_payload(header, payload));
+        true
+    }
+
+    fn add_subscription(&self, sid: u32, sub: &Arc<SubscriptionState>) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        state.subscriptions.insert(sub.subid, sub.clone());
+        drop(state);
+        let mut header = Header::new(command::EVENT_ADD);
+        header.data_type = sub.id.raw() as u16;
+        header.data_count = sub.count as u32;
+        header.param1 = sid;
+        header.param2 = sub.subid;
+        // Low, high and timeout values are not used, only mask is.
+        let mut payload = vec![0; 12];
+        payload.extend_from_slice(&(sub.mask.bits() as u16).to_be_bytes());
+        self.send(&Message::with_payload(header, payload));
+    }
+
+    fn cancel_subscription(&self, sid: u32, sub: &SubscriptionState) {
+        self.state.lock().unwrap().subscriptions.remove(&sub.subid);
+        let mut header = Header::new(command::EVENT_CANCEL);
+        header.data_type = sub.id.raw() as u16;
+        header.data_count = sub.count as u32;
+        header.param1 = sid;
+        header.param2 = sub.subid;
+        self.send(&Message::new(header));
+    }
+
+    fn clear_channel(&self, sid: u32, channel: &ChannelState) {
+        let mut state = self.state.lock().unwrap();
+        state.channels.remove(&channel.cid);
+        state.ios.retain(|_, io| io.channel.cid != channel.cid);
+        state
+            .subscriptions
+            .retain(|_, sub| !ptr::eq(sub.channel.as_ptr(), channel));
+        drop(state);
+        let mut header = Header::new(command::CLEAR_CHANNEL);
+        header.param1 = sid;
+        header.param2 = channel.cid;
+        self.send(&Message::new(header));
+    }
+
+    fn handle(self: &Arc<Self>, message: &Message) {
+        let header = &message.header;
+        match header.command {
+            command::ACCESS_RIGHTS => {
+                let access = AccessRights::new(
+                    header.param2 & ACCESS_READ != 0,
+                    header.param2 & ACCESS_WRITE != 0,
+                );
+                let mut state = self.state.lock().unwrap();
+                match state.channels.get(&header.param1).cloned() {
+                    Some(channel) if channel.is_connected() => {
+                        drop(state);
+                        channel.set_access(access);
+                    }
+                    _ => {
+                        state.access.insert(header.param1, access);
+                    }
+                }
+            }
+            command::CREATE_CHAN => {
+                let mut state = self.state.lock().unwrap();
+                let channel = match state.channels.get(&header.param1) {
+                    Some(channel) => channel.clone(),
+                    None => return,
+                };
+                if !*channel.alive.lock().unwrap() {
+                    // Channel was cleared while it was being created.
+                    state.channels.remove(&header.param1);
+                    drop(state);
+                    let mut clear = Header::new(command::CLEAR_CHANNEL);
+                    clear.param1 = header.param2;
+                    clear.param2 = header.param1;
+                    self.send(&Message::new(clear));
+                    return;
+                }
+                let access = state
+                    .access
+                    .remove(&header.param1)
+                    .unwrap_or(AccessRights::new(true, true));
+                drop(state);
+                let conn = Connection {
+                    circuit: self.clone(),
+                    sid: header.param2,
+                    field_type: header.data_type,
+                    count: header.data_count,
+                    access,
+                };
+                channel.connected(conn);
+            }
+            command::CREATE_CH_FAIL | command::SERVER_DISCONN => {
+                self.channel_failed(header.param1);
+            }
+            command::READ_NOTIFY | command::WRITE_NOTIFY => {
+                let io = self.state.lock().unwrap().ios.remove(&header.param2);
+                if let Some(io) = io {
+                    io.complete(header.param1 as i32, Some(message));
+                }
+            }
+            command::EVENT_ADD => {
+                let sub = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .subscriptions
+                    .get(&header.param2)
+                    .cloned();
+                // Empty message confirms cancellation of subscription.
+                if let Some(sub) = sub.filter(|_| header.payload_size > 0) {
+                    sub.event(header.param1 as i32, message);
+                }
+            }
+            command::ERROR => self.handle_error(message),
+            _ => (),
+        }
+    }
+
+    fn handle_error(self: &Arc<Self>, message: &Message) {
+        let status = message.header.param2 as i32;
+        let (request, size) = match Header::decode(&message.payload) {
+            Some(request) => request,
+            None => return,
+        };
+        match request.command {
+            command::READ_NOTIFY | command::WRITE_NOTIFY => {
+                let io = self.state.lock().unwrap().ios.remove(&request.param2);
+                if let Some(io) = io {
+                    io.complete(status, None);
+                }
+            }
+            command::EVENT_ADD => {
+                let sub = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .subscriptions
+                    .get(&request.param2)
+                    .cloned();
+                if let Some(sub) = sub {
+                    sub.failed(status);
+                }
+            }
+            command::CREATE_CHAN => self.channel_failed(request.param1),
+            command::WRITE => {
+                let channel = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .channels
+                    .values()
+                    .find(|c| {
+                        c.connection()
+                            .is_some_and(|conn| conn.sid == request.param1)
+                    })
+                    .cloned();
+                if let Some(channel) = channel {
+                    let text = CStr::from_bytes_until_nul(&message.payload[size..])
+                        .map(CStr::to_owned)
+                        .unwrap_or_default();
+                    channel.exception(status, request, sys::CA_OP_PUT, text);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Server cannot serve the channel anymore.
+    fn channel_failed(&self, cid: u32) {
+        let channel = self.state.lock().unwrap().channels.remove(&cid);
+        if let Some(channel) = channel {
+            if channel.is_connected() {
+                channel.disconnected();
+            }
+            if let Some(client) = channel.client.upgrade() {
+                client.search(&channel);
+            }
+        }
+    }
+}
+
+/// Read or write request waiting for reply.
+pub(crate) struct PendingIo {
+    pub channel: Arc<ChannelState>,
+    pub id: RequestId,
+    pub count: usize,
+    pub func: caEventCallBackFunc,
+    pub usr: UserPtr,
+}
+
+impl PendingIo {
+    /// Call user callback with the result of operation.
+    fn complete(self, status: i32, message: Option<&Message>) {
+        let func = match self.func {
+            Some(func) => func,
+            None => return,
+        };
+        let channel = self.channel.clone();
+        let data = match message {
+            Some(message) if status == sys::ECA_NORMAL => decode(self.id, message),
+            _ => None,
+        };
+        channel.call(move |chid| {
+            let (dbr, count) = match &data {
+                Some((buffer, count)) => (buffer.as_ptr() as *const c_void, *count),
+                None => (ptr::null(), self.count),
+            };
+            unsafe {
+                func(event_handler_args {
+                    usr: self.usr.get(),
+                    chid,
+                    type_: self.id.raw() as c_long,
+                    count: count as c_long,
+                    dbr,
+                    status,
+                })
+            }
+        });
+    }
+}
+
+/// Convert reply payload to native request.
+fn decode(id: RequestId, message: &Message) -> Option<(AlignedBuffer, usize)> {
+    let layout = Layout::of(id)?;
+    let count = message.header.data_count as usize;
+    if layout.size(count) > message.payload.len() {
+        return None;
+    }
+    let mut buffer = AlignedBuffer::new(&message.payload, layout.alloc_size(count));
+    layout.swap(buffer.as_mut_slice(), count);
+    Some((buffer, count))
+}
+
+/// Encode request for sending.
+///
+/// # Safety
+///
+/// `value` must point to request of `id` type with `count` elements.
+pub(crate) unsafe fn encode(id: RequestId, count: usize, value: *const c_void) -> Option<Vec<u8>> {
+    let layout = Layout::of(id)?;
+    let size = layout.size(count);
+    let mut payload = std::slice::from_raw_parts(value as *const u8, size).to_vec();
+    layout.swap(&mut payload, count);
+    Some(payload)
+}
+
+/// Subscription to the channel.
+pub(crate) struct SubscriptionState {
+    pub subid: u32,
+    pub channel: Weak<ChannelState>,
+    pub id: RequestId,
+    pub count: usize,
+    pub mask: EventMask,
+    pub func: caEventCallBackFunc,
+    pub usr: UserPtr,
+    /// Becomes `false` when subscription is cleared. Locked while callback is running.
+    pub active: Mutex<bool>,
+}
+
+impl SubscriptionState {
+    fn event(self: &Arc<Self>, status: i32, message: &Message) {
+        let data = match status {
+            sys::ECA_NORMAL => decode(self.id, message),
+            _ => None,
+        };
+        self.call(status, data);
+    }
+    fn failed(self: &Arc<Self>, status: i32) {
+        self.call(status, None);
+    }
+    fn call(self: &Arc<Self>, status: i32, data: Option<(AlignedBuffer, usize)>) {
+        let (channel, func) = match (self.channel.upgrade(), self.func) {
+            (Some(channel), Some(func)) => (channel, func),
+            _ => return,
+        };
+        let status = match (status, &data) {
+            (sys::ECA_NORMAL, None) => sys::ECA_BADTYPE,
+            _ => status,
+        };
+        let this = self.clone();
+        channel.call(move |chid| {
+            let active = this.active.lock().unwrap();
+            if !*active {
+                return;
+            }
+            let (dbr, count) = match &data {
+                Some((buffer, count)) => (buffer.as_ptr() as *const c_void, *count),
+                None => (ptr::null(), 0),
+            };
+            unsafe {
+                func(event_handler_args {
+                    usr: this.usr.get(),
+                    chid,
+                    type_: this.id.raw() as c_long,
+                    count: count as c_long,
+                    dbr,
+                    status,
+                })
+            }
+        });
+    }
+}
+
+/// State of connected channel.
+#[derive(Clone)]
+pub(crate) struct Connection {
+    pub circuit: Arc<Circuit>,
+    pub sid: u32,
+    pub field_type: u16,
+    pub count: u32,
+    pub access: AccessRights,
+}
+
+/// Raw channel. Its pointer is used as `chid`.
+pub(crate) struct ChannelState {
+    pub cid: u32,
+    pub name: CString,
+    pub priority: u16,
+    pub puser: UserPtr,
+    pub conn_cb: caCh,
+    pub access_cb: Mutex<caArh>,
+    pub client: Weak<Client>,
+    /// Becomes `false` when channel is cleared. Locked while callback is running.
+    pub alive: Mutex<bool>,
+    pub conn: Mutex<Option<Connection>>,
+    pub subscriptions: Mutex<Vec<Arc<SubscriptionState>>>,
+}
+
+impl ChannelState {
+    fn raw(self: &Arc<Self>) -> chid {
+        Arc::as_ptr(self) as chid
+    }
+    pub fn connection(&self) -> Option<Connection> {
+        self.conn.lock().unwrap().clone()
+    }
+    fn is_connected(&self) -> bool {
+        self.conn.lock().unwrap().is_some()
+    }
+    pub fn host(&self) -> Option<&'static CStr> {
+        self.conn.lock().unwrap().as_ref().map(|c| c.circuit.host)
+    }
+
+    /// Call `f` if channel is still alive.
+    fn call<F: FnOnce(chid) + Send + 'static>(self: &Arc<Self>, f: F) {
+        let this = self.clone();
+        let task = move || {
+            let alive = this.alive.lock().unwrap();
+            if *alive {
+                f(this.raw());
+            }
+        };
+        match self.client.upgrade() {
+            Some(client) => client.dispatch(task),
+            None => task(),
+        }
+    }
+    /// Report exception caused by `request` to the handler of the client.
+    fn exception(self: &Arc<Self>, status: i32, request: Header, op: i32, text: CString) {
+        let (func, usr) = match self.client.upgrade() {
+            Some(client) => *client.exception_handler.lock().unwrap(),
+            None => return,
+        };
+        if let Some(func) = func {
+            self.call(move |chid| unsafe {
+                func(exception_handler_args {
+                    usr: usr.get(),
+                    chid,
+                    type_: request.data_type as c_long,
+                    count: request.data_count as c_long,
+                    addr: ptr::null_mut(),
+                    stat: status as c_long,
+                    op: op as c_long,
+                    ctx: text.as_ptr(),
+                    pFile: concat!(file!(), "\0").as_ptr() as *const c_char,
+                    lineNo: line!(),
+                })
+            });
+        }
+    }
+    pub fn notify_access(self: &Arc<Self>, access: AccessRights) {
+        if let Some(func) = *self.access_cb.lock().unwrap() {
+            self.call(move |chid| unsafe {
+                func(access_rights_handler_args {
+                    chid,
+                    ar: access.raw(),
+                })
+            });
+        }
+    }
+    fn notify_connection(self: &Arc<Self>, op: i32) {
+        if let Some(func) = self.conn_cb {
+            self.call(move |chid| unsafe {
+                func(connection_handler_args {
+                    chid,
+                    op: op as c_long,
+                })
+            });
+        }
+    }
+
+    fn set_access(self: &Arc<Self>, access: AccessRights) {
+        if let Some(conn) = self.conn.lock().unwrap().as_mut() {
+            conn.access = access;
+        }
+        self.notify_access(access);
+    }
+
+    fn connected(self: &Arc<Self>, conn: Connection) {
+        let access = conn.access;
+        let (circuit, sid) = (conn.circuit.clone(), conn.sid);
+        *self.conn.lock().unwrap() = Some(conn);
+        let subs = self.subscriptions.lock().unwrap().clone();
+        for sub in subs.iter() {
+            circuit.add_subscription(sid, sub);
+        }
+        self.notify_access(access);
+        self.notify_connection(sys::CA_OP_CONN_UP);
+    }
+
+    fn disconnected(self: &Arc<Self>) {
+        *self.conn.lock().unwrap() = None;
+        self.notify_access(AccessRights::default());
+        self.notify_connection(sys::CA_OP_CONN_DOWN);
+    }
+
+    pub fn subscribe(&self, sub: &Arc<SubscriptionState>) {
+        self.subscriptions.lock().unwrap().push(sub.clone());
+        if let Some(conn) = self.connection() {
+            conn.circuit.add_subscription(conn.sid, sub);
+        }
+    }
+    pub fn unsubscribe(&self, sub: &Arc<SubscriptionState>) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, sub));
+        if let Some(conn) = self.connection() {
+            conn.circuit.cancel_subscription(conn.sid, sub);
+        }
+    }
+
+    /// Channel is cleared by user.
+    pub fn clear(&self) {
+        *self.alive.lock().unwrap() = false;
+        for sub in self.subscriptions.lock().unwrap().drain(..) {
+            *sub.active.lock().unwrap() = false;
+        }
+        let conn = self.conn.lock().unwrap().take();
+        match conn {
+            Some(conn) => conn.circuit.clear_channel(conn.sid, self),
+            None => {
+                if let Some(client) = self.client.upgrade() {
+                    client.release(self);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Channel Access client implemented in Rust that is used when `pure-rust` feature is enabled.
+//!
+//! It talks to servers over UDP and TCP directly, so EPICS CA library is not needed.
+//! Servers are searched using the following environment variables:
+//!
+//! + `EPICS_CA_ADDR_LIST` - space-separated list of addresses to send search requests to.
+//! + `EPICS_CA_AUTO_ADDR_LIST` - if `NO` then broadcast address is not added to the list.
+//! + `EPICS_CA_SERVER_PORT` - default port of servers.
+//! + `EPICS_CA_REPEATER_PORT` - port of CA repeater to receive beacons from.
+
+mod client;
+pub(crate) mod raw;
+
+#[cfg(test)]
+mod tests;
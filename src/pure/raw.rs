@@ -0,0 +1,397 @@
+//! Implementation of Channel Access functions used by the crate.
+//!
+//! Functions have the same signatures as ones from [`sys`].
+//! Callbacks are called from the threads that receive messages from servers.
+
+#![allow(clippy::missing_safety_doc)]
+
+use super::client::{self, ChannelState, Client, Config, PendingIo, SubscriptionState, UserPtr};
+use crate::{
+    context::ContextConfig,
+    protocol::{command, Header},
+    types::{AccessRights, EventMask, RequestId},
+};
+use std::{
+    cell::Cell,
+    ffi::{c_void, CStr},
+    os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong},
+    ptr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use sys::{
+    caArh, caCh, caEventCallBackFunc, caExceptionHandler, ca_client_context,
+    ca_preemptive_callback_select, ca_real, capri, chid, chtype, evid,
+};
+
+struct ClientContext {
+    client: Arc<Client>,
+}
+
+impl Drop for ClientContext {
+    fn drop(&mut self) {
+        self.client.stop();
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<*mut ClientContext> = const { Cell::new(ptr::null_mut()) };
+}
+
+fn current() -> Option<&'static ClientContext> {
+    unsafe { CURRENT.with(|c| c.get()).as_ref() }
+}
+
+/// Borrow channel by its identifier.
+unsafe fn channel(chan: chid) -> Arc<ChannelState> {
+    let chan = chan as *const ChannelState;
+    Arc::increment_strong_count(chan);
+    Arc::from_raw(chan)
+}
+
+unsafe fn channel_ref<'a>(chan: chid) -> &'a ChannelState {
+    &*(chan as *const ChannelState)
+}
+
+/// Create context with specific settings, missing ones are taken from environment.
+pub unsafe fn context_create(
+    select: ca_preemptive_callback_select,
+    settings: &ContextConfig,
+) -> c_int {
+    if current().is_none() {
+        let preemptive = select == ca_preemptive_callback_select::ca_enable_preemptive_callback;
+        let client = match Client::new(Config::new(settings), preemptive) {
+            Ok(client) => client,
+            Err(_) => return sys::ECA_INTERNAL,
+        };
+        let ctx = Box::new(ClientContext { client });
+        CURRENT.with(|c| c.set(Box::into_raw(ctx)));
+    }
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_context_destroy() {
+    let ctx = CURRENT.with(|c| c.replace(ptr::null_mut()));
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+pub unsafe fn ca_pend_event(timeout: ca_real) -> c_int {
+    match current() {
+        Some(ctx) => {
+            ctx.client.poll(Duration::from_secs_f64(timeout.max(0.0)));
+            sys::ECA_TIMEOUT
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_add_exception_event(func: caExceptionHandler, usr: *mut c_void) -> c_int {
+    match current() {
+        Some(ctx) => {
+            *ctx.client.exception_handler.lock().unwrap() = (func, UserPtr::new(usr));
+            sys::ECA_NORMAL
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+/// Nothing is printed by this backend, so the handler is never called.
+#[cfg(feature = "tracing")]
+pub unsafe fn ca_replace_printf_handler(_func: sys::caPrintfFunc) -> c_int {
+    match current() {
+        Some(_) => sys::ECA_NORMAL,
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_current_context() -> *mut ca_client_context {
+    CURRENT.with(|c| c.get()) as *mut ca_client_context
+}
+
+pub unsafe fn ca_attach_context(context: *mut ca_client_context) -> c_int {
+    if current().is_some() {
+        return sys::ECA_ISATTACHED;
+    }
+    CURRENT.with(|c| c.set(context as *mut ClientContext));
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_detach_context() {
+    CURRENT.with(|c| c.set(ptr::null_mut()));
+}
+
+pub unsafe fn ca_flush_io() -> c_int {
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_client_status(level: c_uint) -> c_int {
+    match current() {
+        Some(ctx) => {
+            ctx.client.print_status(level);
+            sys::ECA_NORMAL
+        }
+        None => sys::ECA_NOCACTX,
+    }
+}
+
+pub unsafe fn ca_create_channel(
+    name: *const c_char,
+    conn_cb: caCh,
+    puser: *mut c_void,
+    priority: capri,
+    pchid: *mut chid,
+) -> c_int {
+    if priority > sys::CA_PRIORITY_MAX as capri {
+        return sys::ECA_BADPRIORITY;
+    }
+    let ctx = match current() {
+        Some(ctx) => ctx,
+        None => return sys::ECA_NOCACTX,
+    };
+    let name = CStr::from_ptr(name);
+    if name.to_bytes().is_empty() {
+        return sys::ECA_EMPTYSTR;
+    }
+    let chan = Arc::new(ChannelState {
+        cid: ctx.client.next_id(),
+        name: name.to_owned(),
+        priority: priority as u16,
+        puser: UserPtr::new(puser),
+        conn_cb,
+        access_cb: Mutex::new(None),
+        client: Arc::downgrade(&ctx.client),
+        alive: Mutex::new(true),
+        conn: Mutex::new(None),
+        subscriptions: Mutex::new(Vec::new()),
+    });
+    ctx.client.search(&chan);
+    *pchid = Arc::into_raw(chan) as chid;
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_clear_channel(chan: chid) -> c_int {
+    let chan = Arc::from_raw(chan as *const ChannelState);
+    chan.clear();
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_name(chan: chid) -> *const c_char {
+    channel_ref(chan).name.as_ptr()
+}
+
+pub unsafe fn ca_puser(chan: chid) -> *mut c_void {
+    channel_ref(chan).puser.get()
+}
+
+pub unsafe fn ca_host_name(chan: chid) -> *const c_char {
+    const DISCONN_HOST: &[u8] = b"<disconnected>\0";
+    match channel_ref(chan).host() {
+        Some(host) => host.as_ptr(),
+        None => DISCONN_HOST.as_ptr() as *const c_char,
+    }
+}
+
+pub unsafe fn ca_field_type(chan: chid) -> c_short {
+    match channel_ref(chan).connection() {
+        Some(conn) => conn.field_type as c_short,
+        None => sys::TYPENOTCONN as c_short,
+    }
+}
+
+pub unsafe fn ca_element_count(chan: chid) -> c_ulong {
+    match channel_ref(chan).connection() {
+        Some(conn) => conn.count as c_ulong,
+        None => 0,
+    }
+}
+
+unsafe fn access(chan: chid) -> AccessRights {
+    match channel_ref(chan).connection() {
+        Some(conn) => conn.access,
+        None => AccessRights::default(),
+    }
+}
+
+pub unsafe fn ca_read_access(chan: chid) -> c_uint {
+    access(chan).read_access() as c_uint
+}
+
+pub unsafe fn ca_write_access(chan: chid) -> c_uint {
+    access(chan).write_access() as c_uint
+}
+
+pub unsafe fn ca_replace_access_rights_event(chan: chid, func: caArh) -> c_int {
+    let chan = channel(chan);
+    *chan.access_cb.lock().unwrap() = func;
+    if let Some(conn) = chan.connection() {
+        chan.notify_access(conn.access);
+    }
+    sys::ECA_NORMAL
+}
+
+fn request_id(type_: chtype) -> Result<RequestId, c_int> {
+    RequestId::try_from_raw(type_ as i32).ok_or(sys::ECA_BADTYPE)
+}
+
+pub unsafe fn ca_array_get_callback(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+) -> c_int {
+    let chan = channel(chan);
+    let id = match request_id(type_) {
+        Ok(id) => id,
+        Err(eca) => return eca,
+    };
+    let conn = match chan.connection() {
+        Some(conn) => conn,
+        None => return sys::ECA_DISCONN,
+    };
+    if count > conn.count as c_ulong {
+        return sys::ECA_BADCOUNT;
+    }
+    if !conn.access.read_access() {
+        return sys::ECA_NORDACCESS;
+    }
+    let client = match chan.client.upgrade() {
+        Some(client) => client,
+        None => return sys::ECA_NOCACTX,
+    };
+    let mut header = Header::new(command::READ_NOTIFY);
+    header.data_type = id.raw() as u16;
+    header.data_count = count as u32;
+    header.param1 = conn.sid;
+    header.param2 = client.next_id();
+    let io = PendingIo {
+        channel: chan.clone(),
+        id,
+        count: count as usize,
+        func,
+        usr: UserPtr::new(usr),
+    };
+    match conn.circuit.request(header, Vec::new(), io) {
+        true => sys::ECA_NORMAL,
+        false => sys::ECA_DISCONN,
+    }
+}
+
+/// Check write request and encode its message.
+unsafe fn write_message(
+    command: u16,
+    type_: chtype,
+    count: c_ulong,
+    chan: &ChannelState,
+    value: *const c_void,
+) -> Result<(client::Connection, RequestId, Header, Vec<u8>), c_int> {
+    let id = request_id(type_)?;
+    let conn = chan.connection().ok_or(sys::ECA_DISCONN)?;
+    if count > conn.count as c_ulong {
+        return Err(sys::ECA_BADCOUNT);
+    }
+    if !conn.access.write_access() {
+        return Err(sys::ECA_NOWTACCESS);
+    }
+    let client = chan.client.upgrade().ok_or(sys::ECA_NOCACTX)?;
+    let payload = client::encode(id, count as usize, value).ok_or(sys::ECA_BADTYPE)?;
+    let mut header = Header::new(command);
+    header.data_type = id.raw() as u16;
+    header.data_count = count as u32;
+    header.param1 = conn.sid;
+    header.param2 = client.next_id();
+    Ok((conn, id, header, payload))
+}
+
+pub unsafe fn ca_array_put(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    value: *const c_void,
+) -> c_int {
+    let chan = channel_ref(chan);
+    let (conn, _, header, payload) = match write_message(command::WRITE, type_, count, chan, value)
+    {
+        Ok(message) => message,
+        Err(eca) => return eca,
+    };
+    match conn.circuit.write(header, payload) {
+        true => sys::ECA_NORMAL,
+        false => sys::ECA_DISCONN,
+    }
+}
+
+pub unsafe fn ca_array_put_callback(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    value: *const c_void,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+) -> c_int {
+    let chan = channel(chan);
+    let (conn, id, header, payload) =
+        match write_message(command::WRITE_NOTIFY, type_, count, &chan, value) {
+            Ok(message) => message,
+            Err(eca) => return eca,
+        };
+    let io = PendingIo {
+        channel: chan.clone(),
+        id,
+        count: count as usize,
+        func,
+        usr: UserPtr::new(usr),
+    };
+    match conn.circuit.request(header, payload, io) {
+        true => sys::ECA_NORMAL,
+        false => sys::ECA_DISCONN,
+    }
+}
+
+pub unsafe fn ca_create_subscription(
+    type_: chtype,
+    count: c_ulong,
+    chan: chid,
+    mask: c_long,
+    func: caEventCallBackFunc,
+    usr: *mut c_void,
+    pevid: *mut evid,
+) -> c_int {
+    let chan = channel(chan);
+    let id = match request_id(type_) {
+        Ok(id) => id,
+        Err(eca) => return eca,
+    };
+    let mask = match EventMask::try_from_raw(mask as i32) {
+        Some(mask) if !mask.is_empty() => mask,
+        _ => return sys::ECA_BADMASK,
+    };
+    let client = match chan.client.upgrade() {
+        Some(client) => client,
+        None => return sys::ECA_NOCACTX,
+    };
+    let sub = Arc::new(SubscriptionState {
+        subid: client.next_id(),
+        channel: Arc::downgrade(&chan),
+        id,
+        count: count as usize,
+        mask,
+        func,
+        usr: UserPtr::new(usr),
+        active: Mutex::new(true),
+    });
+    chan.subscribe(&sub);
+    *pevid = Arc::into_raw(sub) as evid;
+    sys::ECA_NORMAL
+}
+
+pub unsafe fn ca_clear_subscription(evid: evid) -> c_int {
+    let sub = Arc::from_raw(evid as *const SubscriptionState);
+    *sub.active.lock().unwrap() = false;
+    if let Some(chan) = sub.channel.upgrade() {
+        chan.unsubscribe(&sub);
+    }
+    sys::ECA_NORMAL
+}
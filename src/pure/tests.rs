@@ -0,0 +1,232 @@
+use crate::{
+    context::ContextBuilder,
+    protocol::{
+        command, max_payload_size, Header, Message, ACCESS_READ, ACCESS_WRITE, MINOR_VERSION,
+    },
+    Context,
+};
+use async_std::test as async_test;
+use cstr::cstr;
+use futures::{pin_mut, FutureExt, StreamExt};
+use serial_test::serial;
+use std::{
+    env,
+    io::BufReader,
+    net::{Ipv4Addr, Shutdown, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const PV_NAME: &[u8] = b"pure:test:value";
+const DBR_DOUBLE: u16 = 6;
+const SID: u32 = 1;
+
+/// Minimal Channel Access server that serves single `DOUBLE` PV.
+struct Server {
+    value: Mutex<f64>,
+    /// Subscriptions of the connected client.
+    subscriptions: Mutex<Vec<u32>>,
+    stream: Mutex<Option<TcpStream>>,
+    /// Whether echo requests are replied.
+    responsive: AtomicBool,
+}
+
+impl Server {
+    /// Start server and point client to it.
+    fn start() -> Arc<Self> {
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let tcp = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        env::set_var("EPICS_CA_ADDR_LIST", udp.local_addr().unwrap().to_string());
+        env::set_var("EPICS_CA_AUTO_ADDR_LIST", "NO");
+
+        let server = Arc::new(Self {
+            value: Mutex::new(0.0),
+            subscriptions: Mutex::new(Vec::new()),
+            stream: Mutex::new(None),
+            responsive: AtomicBool::new(true),
+        });
+        let port = tcp.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buf = vec![0; 0x10000];
+            while let Ok((len, src)) = udp.recv_from(&mut buf) {
+                for message in Message::decode_all(&buf[..len]) {
+                    if message.header.command != command::SEARCH
+                        || !message.payload.starts_with(PV_NAME)
+                    {
+                        continue;
+                    }
+                    let mut reply = Header::new(command::SEARCH);
+                    reply.data_type = port;
+                    reply.param1 = u32::MAX;
+                    reply.param2 = message.header.param1;
+                    let payload = MINOR_VERSION.to_be_bytes().to_vec();
+                    let bytes = Message::with_payload(reply, payload).to_bytes();
+                    udp.send_to(&bytes, src).unwrap();
+                }
+            }
+        });
+        let this = server.clone();
+        thread::spawn(move || {
+            for stream in tcp.incoming() {
+                let stream = stream.unwrap();
+                *this.stream.lock().unwrap() = Some(stream.try_clone().unwrap());
+                let this = this.clone();
+                thread::spawn(move || this.serve(stream));
+            }
+        });
+        server
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(stream) = self.stream.lock().unwrap().as_mut() {
+            let _ = message.write_to(stream);
+        }
+    }
+
+    fn value_message(&self, mut header: Header) -> Message {
+        header.data_type = DBR_DOUBLE;
+        header.data_count = 1;
+        let value = *self.value.lock().unwrap();
+        Message::with_payload(header, value.to_be_bytes().to_vec())
+    }
+
+    fn set_value(&self, value: f64) {
+        *self.value.lock().unwrap() = value;
+        let subscriptions = self.subscriptions.lock().unwrap().clone();
+        for subid in subscriptions {
+            let mut header = Header::new(command::EVENT_ADD);
+            header.param1 = sys::ECA_NORMAL as u32;
+            header.param2 = subid;
+            self.send(self.value_message(header));
+        }
+    }
+
+    fn serve(&self, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        while let Ok(message) = Message::read_from(&mut reader, max_payload_size(None)) {
+            let header = message.header;
+            match header.command {
+                command::CREATE_CHAN => {
+                    let mut access = Header::new(command::ACCESS_RIGHTS);
+                    access.param1 = header.param1;
+                    access.param2 = ACCESS_READ | ACCESS_WRITE;
+                    self.send(Message::new(access));
+                    let mut reply = Header::new(command::CREATE_CHAN);
+                    reply.data_type = DBR_DOUBLE;
+                    reply.data_count = 1;
+                    reply.param1 = header.param1;
+                    reply.param2 = SID;
+                    self.send(Message::new(reply));
+                }
+                command::READ_NOTIFY => {
+                    assert_eq!(header.data_type, DBR_DOUBLE);
+                    let mut reply = Header::new(command::READ_NOTIFY);
+                    reply.param1 = sys::ECA_NORMAL as u32;
+                    reply.param2 = header.param2;
+                    self.send(self.value_message(reply));
+                }
+                command::WRITE_NOTIFY => {
+                    assert_eq!(header.data_type, DBR_DOUBLE);
+                    let value = f64::from_be_bytes(message.payload[..8].try_into().unwrap());
+                    let mut reply = Header::new(command::WRITE_NOTIFY);
+                    reply.data_type = DBR_DOUBLE;
+                    reply.data_count = 1;
+                    reply.param1 = sys::ECA_NORMAL as u32;
+                    reply.param2 = header.param2;
+                    self.send(Message::new(reply));
+                    self.set_value(value);
+                }
+                command::EVENT_ADD => {
+                    assert_eq!(header.param1, SID);
+                    self.subscriptions.lock().unwrap().push(header.param2);
+                    let mut reply = Header::new(command::EVENT_ADD);
+                    reply.param1 = sys::ECA_NORMAL as u32;
+                    reply.param2 = header.param2;
+                    self.send(self.value_message(reply));
+                }
+                command::ECHO if self.responsive.load(Ordering::Acquire) => {
+                    self.send(Message::new(header));
+                }
+                command::EVENT_CANCEL => {
+                    self.subscriptions
+                        .lock()
+                        .unwrap()
+                        .retain(|subid| *subid != header.param2);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Close connection to the client.
+    fn disconnect(&self) {
+        self.subscriptions.lock().unwrap().clear();
+        if let Some(stream) = self.stream.lock().unwrap().take() {
+            stream.shutdown(Shutdown::Both).unwrap();
+        }
+    }
+}
+
+#[async_test]
+#[serial]
+async fn read_write_monitor() {
+    let server = Server::start();
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<f64>(cstr!("pure:test:value")).await.unwrap();
+    assert_eq!(channel.element_count().unwrap(), 1);
+    assert!(channel.access_rights().write_access());
+    assert_eq!(channel.get().await.unwrap(), 0.0);
+
+    let monitor = channel.subscribe();
+    pin_mut!(monitor);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), 0.0);
+
+    channel.put(1.5).unwrap().await.unwrap();
+    assert_eq!(monitor.next().await.unwrap().unwrap(), 1.5);
+    assert_eq!(channel.get().await.unwrap(), 1.5);
+
+    server.set_value(-2.0);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), -2.0);
+}
+
+#[async_test]
+#[serial]
+async fn reconnect() {
+    let server = Server::start();
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<f64>(cstr!("pure:test:value")).await.unwrap();
+
+    let events = channel.connection_events();
+    pin_mut!(events);
+    server.disconnect();
+    assert!(!events.next().await.unwrap().is_connected());
+    assert!(events.next().await.unwrap().is_connected());
+    assert_eq!(channel.get().await.unwrap(), 0.0);
+}
+
+#[async_test]
+#[serial]
+async fn echo_timeout() {
+    let server = Server::start();
+    let ctx = ContextBuilder::new()
+        .conn_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let channel = ctx.connect::<f64>(cstr!("pure:test:value")).await.unwrap();
+
+    let events = channel.connection_events();
+    pin_mut!(events);
+    // Idle circuit is kept while server replies to echo requests.
+    async_std::task::sleep(Duration::from_millis(500)).await;
+    assert!(events.next().now_or_never().is_none());
+
+    server.responsive.store(false, Ordering::Release);
+    assert!(!events.next().await.unwrap().is_connected());
+    server.responsive.store(true, Ordering::Release);
+    assert!(events.next().await.unwrap().is_connected());
+    assert_eq!(channel.get().await.unwrap(), 0.0);
+}
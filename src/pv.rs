@@ -0,0 +1,296 @@
+//! Process variable that is shared by mock database and server.
+
+use crate::{
+    convert::{self, Buffer, Header, Meta},
+    error::{self, Error},
+    request::Limits,
+    types::{
+        AccessRights, Alarm, DynArray, DynValue, EpicsTimeStamp, EventMask, FieldId, RequestId,
+    },
+};
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+type ReadHandler = dyn Fn(&Pv) + Send + Sync;
+type WriteHandler = dyn Fn(&Pv, &DynValue) -> Result<(), Error> + Send + Sync;
+
+/// Receiver of PV changes: channel of mock backend or channel of server client.
+pub(crate) trait Observer: Send + Sync {
+    /// PV is changed, `mask` tells what kinds of changes happened.
+    fn on_change(self: Arc<Self>, state: &PvState, mask: EventMask);
+    /// Access rights of the PV are changed.
+    fn on_access(self: Arc<Self>, access: AccessRights);
+    /// PV is removed.
+    fn on_remove(self: Arc<Self>);
+}
+
+/// Process variable stored in mock database or published by server.
+///
+/// Handle is cheap to clone. All clones refer to the same PV.
+#[derive(Clone)]
+pub struct Pv {
+    inner: Arc<PvInner>,
+}
+
+struct PvInner {
+    name: CString,
+    field: FieldId,
+    max_len: usize,
+    state: Mutex<PvState>,
+    read_handler: Mutex<Option<Arc<ReadHandler>>>,
+    write_handler: Mutex<Option<Arc<WriteHandler>>>,
+}
+
+pub(crate) struct PvState {
+    value: DynArray,
+    alarm: Alarm,
+    stamp: EpicsTimeStamp,
+    meta: Meta,
+    access: AccessRights,
+    observers: Vec<Weak<dyn Observer>>,
+}
+
+impl PvState {
+    /// Make response to the read request of `count` elements (`0` means current length).
+    pub(crate) fn encode(&self, id: RequestId, count: usize) -> Result<Buffer, Error> {
+        let header = Header {
+            alarm: self.alarm,
+            stamp: self.stamp,
+            meta: &self.meta,
+        };
+        convert::encode(id, count, &self.value, &header)
+    }
+    pub(crate) fn access(&self) -> AccessRights {
+        self.access
+    }
+
+    fn observers(&mut self) -> Vec<Arc<dyn Observer>> {
+        self.observers.retain(|o| o.strong_count() > 0);
+        self.observers.iter().filter_map(|o| o.upgrade()).collect()
+    }
+    fn post(&mut self, mask: EventMask) {
+        for observer in self.observers() {
+            observer.on_change(self, mask);
+        }
+    }
+}
+
+impl Pv {
+    /// Create PV that can store up to `max_len` elements.
+    pub(crate) fn new(name: CString, mut value: DynArray, max_len: usize) -> Self {
+        if value.len() > max_len {
+            convert::resize(&mut value, max_len);
+        }
+        Self {
+            inner: Arc::new(PvInner {
+                name,
+                field: value.field_id(),
+                max_len,
+                state: Mutex::new(PvState {
+                    value,
+                    alarm: Alarm::default(),
+                    stamp: EpicsTimeStamp::now(),
+                    meta: Meta::default(),
+                    access: AccessRights::new(true, true),
+                    observers: Vec::new(),
+                }),
+                read_handler: Mutex::new(None),
+                write_handler: Mutex::new(None),
+            }),
+        }
+    }
+    pub(crate) fn state(&self) -> MutexGuard<'_, PvState> {
+        self.inner.state.lock().unwrap()
+    }
+
+    pub fn name(&self) -> &CStr {
+        &self.inner.name
+    }
+    /// Native field type.
+    pub fn field_type(&self) -> FieldId {
+        self.inner.field
+    }
+    /// Maximum number of elements.
+    pub fn max_len(&self) -> usize {
+        self.inner.max_len
+    }
+
+    /// Current value. PV of single element returns scalar.
+    pub fn value(&self) -> DynValue {
+        convert::from_array(&self.state().value, self.max_len() == 1)
+    }
+    /// Store new value and post it to subscribers.
+    ///
+    /// Value is converted to the native field type of the PV.
+    /// Write handler is not called.
+    pub fn set_value(&self, value: impl Into<DynValue>) -> Result<(), Error> {
+        self.store(convert::into_array(value.into()))
+    }
+    fn store(&self, value: DynArray) -> Result<(), Error> {
+        if value.len() > self.max_len() {
+            return Err(error::BADCOUNT);
+        }
+        let mut state = self.state();
+        state.value = convert::convert(&value, self.field_type(), &state.meta.enum_strs)?;
+        state.stamp = EpicsTimeStamp::now();
+        state.post(EventMask::VALUE | EventMask::ARCHIVE);
+        Ok(())
+    }
+
+    pub fn alarm(&self) -> Alarm {
+        self.state().alarm
+    }
+    pub fn set_alarm(&self, alarm: Alarm) {
+        let mut state = self.state();
+        if state.alarm != alarm {
+            state.alarm = alarm;
+            state.post(EventMask::ALARM);
+        }
+    }
+
+    pub fn stamp(&self) -> EpicsTimeStamp {
+        self.state().stamp
+    }
+    /// Set timestamp of the current value.
+    ///
+    /// Timestamp is also updated on each write, so this should be called after [`Self::set_value`].
+    pub fn set_stamp(&self, stamp: EpicsTimeStamp) {
+        self.state().stamp = stamp;
+    }
+
+    pub fn set_units(&self, units: &str) {
+        self.update_meta(|meta| meta.units = units.to_owned());
+    }
+    pub fn set_precision(&self, precision: i16) {
+        self.update_meta(|meta| meta.precision = precision);
+    }
+    pub fn set_limits(&self, limits: Limits<f64>) {
+        self.update_meta(|meta| meta.limits = limits);
+    }
+    pub fn set_enum_strs<S: AsRef<str>>(&self, strs: &[S]) {
+        self.update_meta(|meta| {
+            meta.enum_strs = strs.iter().map(|s| s.as_ref().to_owned()).collect()
+        });
+    }
+    fn update_meta<F: FnOnce(&mut Meta)>(&self, f: F) {
+        let mut state = self.state();
+        f(&mut state.meta);
+        state.post(EventMask::PROPERTY);
+    }
+
+    pub fn access_rights(&self) -> AccessRights {
+        self.state().access
+    }
+    /// Change access rights of all channels connected to the PV.
+    pub fn set_access_rights(&self, access: AccessRights) {
+        let mut state = self.state();
+        state.access = access;
+        for observer in state.observers() {
+            observer.on_access(access);
+        }
+    }
+
+    /// Set function that is called before value is read by channel.
+    ///
+    /// It may be used to update value on demand. Subscriptions don't call it.
+    pub fn on_read<F: Fn(&Pv) + Send + Sync + 'static>(&self, f: F) {
+        *self.inner.read_handler.lock().unwrap() = Some(Arc::new(f));
+    }
+    /// Set function that is called when channel writes to the PV.
+    ///
+    /// It receives value converted to the native field type.
+    /// Value is stored only if handler succeeds, otherwise error is returned to the channel.
+    pub fn on_write<F>(&self, f: F)
+    where
+        F: Fn(&Pv, &DynValue) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        *self.inner.write_handler.lock().unwrap() = Some(Arc::new(f));
+    }
+
+    /// Handle read request of channel.
+    pub(crate) fn read(&self, id: RequestId, count: usize) -> Result<Buffer, Error> {
+        if count > self.max_len() {
+            return Err(error::BADCOUNT);
+        }
+        let handler = self.inner.read_handler.lock().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(self);
+        }
+        let state = self.state();
+        if !state.access.read_access() {
+            return Err(error::NORDACCESS);
+        }
+        state.encode(id, count)
+    }
+    /// Handle write request of channel.
+    ///
+    /// Access rights and number of elements are checked by caller.
+    /// Value that cannot be converted or stored is reported as failed put, like IOC does.
+    pub(crate) fn write(&self, value: DynArray) -> Result<(), Error> {
+        let value = {
+            let state = self.state();
+            convert::convert(&value, self.field_type(), &state.meta.enum_strs)
+                .map_err(|_| error::PUTFAIL)?
+        };
+        let handler = self.inner.write_handler.lock().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(self, &convert::from_array(&value, self.max_len() == 1))?;
+        }
+        self.store(value).map_err(|_| error::PUTFAIL)
+    }
+
+    /// Notify `observer` about changes of the PV.
+    ///
+    /// Returns locked state, so observer can be initialized without missing any change.
+    pub(crate) fn attach(&self, observer: Weak<dyn Observer>) -> MutexGuard<'_, PvState> {
+        let mut state = self.state();
+        state.observers.push(observer);
+        state
+    }
+    pub(crate) fn detach(&self, observer: &dyn Observer) {
+        let observer = observer as *const dyn Observer as *const ();
+        self.state()
+            .observers
+            .retain(|o| o.strong_count() > 0 && !ptr::eq(o.as_ptr() as *const (), observer));
+    }
+    /// Detach all observers and tell them that PV is removed.
+    pub(crate) fn detach_all(&self) {
+        let observers = {
+            let mut state = self.state();
+            let observers = state.observers();
+            state.observers.clear();
+            observers
+        };
+        for observer in observers {
+            observer.on_remove();
+        }
+    }
+}
+
+/// Add the same PVs as test IOC from `ioc/` has using `add_array`.
+///
+/// Writes to output PVs are forwarded to input ones, like `FLNK` of output record does.
+#[cfg(any(test, feature = "server"))]
+pub(crate) fn add_test_records<F: Fn(&str, DynArray, usize) -> Pv>(add_array: F) {
+    use crate::types::{EpicsEnum, EpicsString};
+
+    let records = [
+        ("ai", "ao", DynArray::Double(vec![0.0]), 1),
+        ("bi", "bo", DynArray::Enum(vec![EpicsEnum(0)]), 1),
+        ("aai", "aao", DynArray::Long(Vec::new()), 64),
+        (
+            "stringin",
+            "stringout",
+            DynArray::String(vec![EpicsString::default()]),
+            1,
+        ),
+    ];
+    for (input, output, value, max_len) in records {
+        let input = add_array(&format!("ca:test:{}", input), value.clone(), max_len);
+        let output = add_array(&format!("ca:test:{}", output), value, max_len);
+        output.on_write(move |_, value| input.set_value(value.clone()));
+    }
+}
@@ -0,0 +1,249 @@
+use super::{
+    CtrlEnum, CtrlFloat, CtrlInt, CtrlString, ReadRequest, Sts, Time, TypedRequest, Units,
+    MAX_ENUM_STRING_SIZE,
+};
+use crate::types::{
+    Alarm, DynField, DynValue, EpicsEnum, EpicsString, EpicsTimeStamp, FieldId, RequestId,
+    StaticCString,
+};
+
+/// Kind of metadata requested along with the value of dynamically typed channel.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum DynKind {
+    /// Value only.
+    #[default]
+    Base,
+    /// Value and alarm.
+    Sts,
+    /// Value, alarm and timestamp.
+    Time,
+    /// Value, alarm and control information (units, limits, enum strings, etc.).
+    Ctrl,
+}
+
+impl DynKind {
+    /// Identifier of the request of this kind for specific field type.
+    pub fn request_id(self, field: FieldId) -> RequestId {
+        match self {
+            DynKind::Base => RequestId::Base(field),
+            DynKind::Sts => RequestId::Sts(field),
+            DynKind::Time => RequestId::Time(field),
+            DynKind::Ctrl => RequestId::Ctrl(field),
+        }
+    }
+}
+
+/// Display, alarm and control limits of numeric channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits<T> {
+    pub upper_disp: T,
+    pub lower_disp: T,
+    pub upper_alarm: T,
+    pub upper_warning: T,
+    pub lower_warning: T,
+    pub lower_alarm: T,
+    pub upper_ctrl: T,
+    pub lower_ctrl: T,
+}
+
+/// Control information of dynamically typed channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynCtrl {
+    String,
+    Short {
+        units: Units,
+        limits: Limits<i16>,
+    },
+    Float {
+        precision: i16,
+        units: Units,
+        limits: Limits<f32>,
+    },
+    Enum {
+        strs: Vec<StaticCString<MAX_ENUM_STRING_SIZE>>,
+    },
+    Char {
+        units: Units,
+        limits: Limits<u8>,
+    },
+    Long {
+        units: Units,
+        limits: Limits<i32>,
+    },
+    Double {
+        precision: i16,
+        units: Units,
+        limits: Limits<f64>,
+    },
+}
+
+/// Response to the request of dynamically typed channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynRequest {
+    Base(DynValue),
+    Sts {
+        alarm: Alarm,
+        value: DynValue,
+    },
+    Time {
+        alarm: Alarm,
+        stamp: EpicsTimeStamp,
+        value: DynValue,
+    },
+    Ctrl {
+        alarm: Alarm,
+        ctrl: DynCtrl,
+        value: DynValue,
+    },
+}
+
+impl DynRequest {
+    pub fn kind(&self) -> DynKind {
+        match self {
+            Self::Base(..) => DynKind::Base,
+            Self::Sts { .. } => DynKind::Sts,
+            Self::Time { .. } => DynKind::Time,
+            Self::Ctrl { .. } => DynKind::Ctrl,
+        }
+    }
+    pub fn value(&self) -> &DynValue {
+        match self {
+            Self::Base(value)
+            | Self::Sts { value, .. }
+            | Self::Time { value, .. }
+            | Self::Ctrl { value, .. } => value,
+        }
+    }
+    pub fn into_value(self) -> DynValue {
+        match self {
+            Self::Base(value)
+            | Self::Sts { value, .. }
+            | Self::Time { value, .. }
+            | Self::Ctrl { value, .. } => value,
+        }
+    }
+    /// Alarm, if present in the request.
+    pub fn alarm(&self) -> Option<Alarm> {
+        match self {
+            Self::Base(..) => None,
+            Self::Sts { alarm, .. } | Self::Time { alarm, .. } | Self::Ctrl { alarm, .. } => {
+                Some(*alarm)
+            }
+        }
+    }
+    /// Timestamp, if present in the request.
+    pub fn stamp(&self) -> Option<EpicsTimeStamp> {
+        match self {
+            Self::Time { stamp, .. } => Some(*stamp),
+            _ => None,
+        }
+    }
+    /// Control information, if present in the request.
+    pub fn ctrl(&self) -> Option<&DynCtrl> {
+        match self {
+            Self::Ctrl { ctrl, .. } => Some(ctrl),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_base<T: DynField>(req: &[T], scalar: bool) -> Self {
+        Self::Base(DynValue::from_items(req, scalar))
+    }
+    pub(crate) fn from_sts<T: DynField>(req: &Sts<[T]>, scalar: bool) -> Self {
+        Self::Sts {
+            alarm: req.alarm,
+            value: DynValue::from_items(&req.value, scalar),
+        }
+    }
+    pub(crate) fn from_time<T: DynField>(req: &Time<[T]>, scalar: bool) -> Self {
+        Self::Time {
+            alarm: req.alarm,
+            stamp: req.stamp,
+            value: DynValue::from_items(&req.value, scalar),
+        }
+    }
+    pub(crate) fn from_ctrl<T: DynCtrlField>(req: &T::Ctrl, scalar: bool) -> Self {
+        let (alarm, ctrl) = T::split_ctrl(req);
+        Self::Ctrl {
+            alarm,
+            ctrl,
+            value: DynValue::from_items(req.value(), scalar),
+        }
+    }
+}
+
+/// Field which control request can be converted to [`DynCtrl`].
+pub trait DynCtrlField: DynField {
+    type Ctrl: TypedRequest<Value = [Self]> + ReadRequest + ?Sized;
+    fn split_ctrl(req: &Self::Ctrl) -> (Alarm, DynCtrl);
+}
+
+macro_rules! limits {
+    ($req:expr) => {
+        Limits {
+            upper_disp: $req.upper_disp_limit,
+            lower_disp: $req.lower_disp_limit,
+            upper_alarm: $req.upper_alarm_limit,
+            upper_warning: $req.upper_warning_limit,
+            lower_warning: $req.lower_warning_limit,
+            lower_alarm: $req.lower_alarm_limit,
+            upper_ctrl: $req.upper_ctrl_limit,
+            lower_ctrl: $req.lower_ctrl_limit,
+        }
+    };
+}
+
+macro_rules! impl_dyn_ctrl_int {
+    ($type:ty, $variant:ident) => {
+        impl DynCtrlField for $type {
+            type Ctrl = CtrlInt<[$type]>;
+            fn split_ctrl(req: &Self::Ctrl) -> (Alarm, DynCtrl) {
+                let ctrl = DynCtrl::$variant {
+                    units: req.units,
+                    limits: limits!(req),
+                };
+                (req.alarm, ctrl)
+            }
+        }
+    };
+}
+
+macro_rules! impl_dyn_ctrl_float {
+    ($type:ty, $variant:ident) => {
+        impl DynCtrlField for $type {
+            type Ctrl = CtrlFloat<[$type]>;
+            fn split_ctrl(req: &Self::Ctrl) -> (Alarm, DynCtrl) {
+                let ctrl = DynCtrl::$variant {
+                    precision: req.precision,
+                    units: req.units,
+                    limits: limits!(req),
+                };
+                (req.alarm, ctrl)
+            }
+        }
+    };
+}
+
+impl_dyn_ctrl_int!(i16, Short);
+impl_dyn_ctrl_int!(u8, Char);
+impl_dyn_ctrl_int!(i32, Long);
+impl_dyn_ctrl_float!(f32, Float);
+impl_dyn_ctrl_float!(f64, Double);
+
+impl DynCtrlField for EpicsEnum {
+    type Ctrl = CtrlEnum<[EpicsEnum]>;
+    fn split_ctrl(req: &Self::Ctrl) -> (Alarm, DynCtrl) {
+        let count = usize::min(req.no_str as usize, req.strs.len());
+        let ctrl = DynCtrl::Enum {
+            strs: req.strs[..count].to_vec(),
+        };
+        (req.alarm, ctrl)
+    }
+}
+
+impl DynCtrlField for EpicsString {
+    type Ctrl = CtrlString<[EpicsString]>;
+    fn split_ctrl(req: &Self::Ctrl) -> (Alarm, DynCtrl) {
+        (req.alarm, DynCtrl::String)
+    }
+}
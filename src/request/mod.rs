@@ -6,9 +6,11 @@
 //!
 
 mod base;
+mod dynamic;
 mod typed;
 
 pub use base::*;
+pub use dynamic::*;
 pub use typed::*;
 
 #[cfg(test)]
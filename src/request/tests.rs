@@ -1,5 +1,5 @@
 use super::*;
-use crate::types::{EpicsEnum, EpicsString};
+use crate::types::{EpicsEnum, EpicsString, FieldId, RequestId};
 use std::mem::{align_of, size_of};
 
 fn assert_layout<R: Request>() {
@@ -81,3 +81,9 @@ fn stsack_string() {
 fn class_name() {
     assert_layout::<ClassName>();
 }
+
+#[test]
+fn enum_ids() {
+    assert_eq!(GrEnum::<EpicsEnum>::ID, RequestId::Gr(FieldId::Enum));
+    assert_eq!(CtrlEnum::<EpicsEnum>::ID, RequestId::Ctrl(FieldId::Enum));
+}
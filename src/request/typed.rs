@@ -230,7 +230,7 @@ pub struct GrEnum<V: Value<Item = EpicsEnum> + ?Sized> {
 }
 unsafe impl<V: Value<Item = EpicsEnum> + ?Sized> Request for GrEnum<V> {
     type Raw = <V::Item as Field>::GrRaw;
-    const ID: RequestId = RequestId::Sts(<V::Item as Field>::ID);
+    const ID: RequestId = RequestId::Gr(<V::Item as Field>::ID);
     impl_request_methods!();
 }
 impl<V: Value<Item = EpicsEnum> + ?Sized> TypedRequest for GrEnum<V> {
@@ -347,7 +347,7 @@ pub struct CtrlEnum<V: Value<Item = EpicsEnum> + ?Sized> {
 }
 unsafe impl<V: Value<Item = EpicsEnum> + ?Sized> Request for CtrlEnum<V> {
     type Raw = <V::Item as Field>::CtrlRaw;
-    const ID: RequestId = RequestId::Sts(<V::Item as Field>::ID);
+    const ID: RequestId = RequestId::Ctrl(<V::Item as Field>::ID);
     impl_request_methods!();
 }
 impl<V: Value<Item = EpicsEnum> + ?Sized> TypedRequest for CtrlEnum<V> {
@@ -0,0 +1,421 @@
+use super::ServerInner;
+use crate::{
+    convert::{self, Buffer},
+    error::{self, Error},
+    protocol::{
+        command, max_payload_size, AlignedBuffer, Header, Layout, Message, ACCESS_READ,
+        ACCESS_WRITE, MINOR_VERSION,
+    },
+    pv::{Observer, Pv, PvState},
+    types::{AccessRights, DynArray, EventMask, RequestId},
+};
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr},
+    io::BufReader,
+    net::{Shutdown, TcpStream},
+    slice,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Client that doesn't accept data for this time is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Virtual circuit of single client.
+pub(crate) struct Connection {
+    server: Weak<ServerInner>,
+    stream: TcpStream,
+    /// Messages written by separate thread, so sending never blocks.
+    outgoing: Sender<Message>,
+    state: Mutex<ConnectionState>,
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    next_sid: u32,
+    channels: HashMap<u32, Arc<Channel>>,
+}
+
+/// Client channel connected to the PV.
+struct Channel {
+    conn: Weak<Connection>,
+    cid: u32,
+    pv: Pv,
+    monitors: Mutex<Vec<Monitor>>,
+}
+
+struct Monitor {
+    subid: u32,
+    id: RequestId,
+    count: usize,
+    mask: EventMask,
+}
+
+impl Observer for Channel {
+    /// Send event to monitors which mask intersects `mask`.
+    ///
+    /// Events are only queued to connection, so PV lock held here doesn't wait for network.
+    fn on_change(self: Arc<Self>, state: &PvState, mask: EventMask) {
+        let conn = match self.conn.upgrade() {
+            Some(conn) => conn,
+            None => return,
+        };
+        if !state.access().read_access() {
+            return;
+        }
+        for monitor in self.monitors.lock().unwrap().iter() {
+            if monitor.mask.intersects(mask) {
+                let result = state.encode(monitor.id, monitor.count);
+                conn.send_event(monitor.subid, monitor.id, result);
+            }
+        }
+    }
+    fn on_access(self: Arc<Self>, access: AccessRights) {
+        if let Some(conn) = self.conn.upgrade() {
+            conn.send_access(self.cid, access);
+        }
+    }
+    fn on_remove(self: Arc<Self>) {
+        if let Some(conn) = self.conn.upgrade() {
+            conn.disconnect(&self);
+        }
+    }
+}
+
+impl Channel {
+    /// Add monitor and send current value to it.
+    ///
+    /// Like reads, monitors require read access. While it is revoked no events are posted.
+    fn monitor(&self, conn: &Connection, subid: u32, id: RequestId, count: usize, mask: EventMask) {
+        // PV is locked until monitor is added, so no change is missed.
+        let state = self.pv.state();
+        let result = if count > self.pv.max_len() {
+            Err(error::BADCOUNT)
+        } else if !state.access().read_access() {
+            Err(error::NORDACCESS)
+        } else {
+            state.encode(id, count)
+        };
+        let ok = result.is_ok();
+        conn.send_event(subid, id, result);
+        if ok {
+            self.monitors.lock().unwrap().push(Monitor {
+                subid,
+                id,
+                count,
+                mask,
+            });
+        }
+    }
+}
+
+/// Make network payload from the response to the read request.
+///
+/// Returns payload and number of elements in it.
+fn payload(id: RequestId, buffer: Buffer) -> Result<(Vec<u8>, usize), Error> {
+    let layout = Layout::of(id).ok_or(error::BADTYPE)?;
+    let size = layout.size(buffer.count);
+    let mut payload = unsafe { slice::from_raw_parts(buffer.as_ptr() as *const u8, size) }.to_vec();
+    layout.swap(&mut payload, buffer.count);
+    Ok((payload, buffer.count))
+}
+
+/// Decode value written by client.
+fn decode(id: RequestId, count: usize, payload: &[u8]) -> Result<DynArray, Error> {
+    let field = match id {
+        RequestId::Base(field) => field,
+        _ => return Err(error::BADTYPE),
+    };
+    let layout = Layout::of(id).ok_or(error::BADTYPE)?;
+    if payload.len() < layout.size(count) {
+        return Err(error::BADCOUNT);
+    }
+    let mut buffer = AlignedBuffer::new(payload, layout.alloc_size(count));
+    layout.swap(buffer.as_mut_slice(), count);
+    Ok(unsafe { convert::decode(field, buffer.as_ptr() as *const c_void, count) })
+}
+
+/// Handle write request of client.
+fn write(pv: &Pv, id: RequestId, count: usize, payload: &[u8]) -> Result<(), Error> {
+    if !pv.access_rights().write_access() {
+        return Err(error::NOWTACCESS);
+    }
+    if count > pv.max_len() {
+        return Err(error::BADCOUNT);
+    }
+    pv.write(decode(id, count, payload)?)
+}
+
+fn status(result: Result<(), Error>) -> u32 {
+    match result {
+        Ok(()) => sys::ECA_NORMAL as u32,
+        Err(err) => err.into_raw() as u32,
+    }
+}
+
+impl Connection {
+    pub fn new(server: Weak<ServerInner>, stream: &TcpStream) -> std::io::Result<Self> {
+        let writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let (outgoing, messages) = mpsc::channel();
+        thread::Builder::new()
+            .name("cas-writer".into())
+            .spawn(move || write_loop(writer, messages))?;
+        Ok(Self {
+            server,
+            stream: stream.try_clone()?,
+            outgoing,
+            state: Mutex::new(ConnectionState::default()),
+        })
+    }
+
+    /// Queue message to be sent to client.
+    fn send(&self, message: Message) {
+        // Fails only if connection is broken, that is detected by reading thread.
+        let _ = self.outgoing.send(message);
+    }
+
+    pub fn send_access(&self, cid: u32, access: AccessRights) {
+        let mut header = Header::new(command::ACCESS_RIGHTS);
+        header.param1 = cid;
+        if access.read_access() {
+            header.param2 |= ACCESS_READ;
+        }
+        if access.write_access() {
+            header.param2 |= ACCESS_WRITE;
+        }
+        self.send(Message::new(header));
+    }
+
+    fn send_event(&self, subid: u32, id: RequestId, result: Result<Buffer, Error>) {
+        let mut header = Header::new(command::EVENT_ADD);
+        header.data_type = id.raw() as u16;
+        header.param2 = subid;
+        let message = match result.and_then(|buffer| payload(id, buffer)) {
+            Ok((payload, count)) => {
+                header.data_count = count as u32;
+                header.param1 = sys::ECA_NORMAL as u32;
+                Message::with_payload(header, payload)
+            }
+            Err(err) => self.error_message(header, err),
+        };
+        self.send(message);
+    }
+
+    /// Error reply that contains original `request` header.
+    fn error_message(&self, request: Header, err: Error) -> Message {
+        let mut header = Header::new(command::ERROR);
+        header.param1 = request.param1;
+        header.param2 = err.into_raw() as u32;
+        let mut payload = Vec::new();
+        Message::new(request).encode(&mut payload);
+        Message::with_payload(header, payload)
+    }
+
+    /// Shutdown connection. Reading thread exits and cleans up.
+    pub fn close(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+
+    /// Handle messages until connection is closed.
+    pub fn serve(self: &Arc<Self>, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        let max_payload_size = max_payload_size(None);
+        while let Ok(message) = Message::read_from(&mut reader, max_payload_size) {
+            self.handle(&message);
+        }
+        let channels = self
+            .state
+            .lock()
+            .unwrap()
+            .channels
+            .drain()
+            .map(|(_, c)| c)
+            .collect::<Vec<_>>();
+        for channel in channels {
+            self.release(&channel);
+        }
+    }
+
+    fn release(&self, channel: &Channel) {
+        channel.pv.detach(channel);
+    }
+
+    /// PV of the channel is not served anymore.
+    fn disconnect(&self, channel: &Arc<Channel>) {
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            let len = state.channels.len();
+            state.channels.retain(|_, c| !Arc::ptr_eq(c, channel));
+            state.channels.len() != len
+        };
+        if removed {
+            let mut header = Header::new(command::SERVER_DISCONN);
+            header.param1 = channel.cid;
+            self.send(Message::new(header));
+        }
+    }
+
+    fn channel(&self, sid: u32) -> Option<Arc<Channel>> {
+        self.state.lock().unwrap().channels.get(&sid).cloned()
+    }
+
+    fn handle(self: &Arc<Self>, message: &Message) {
+        let header = message.header;
+        match header.command {
+            command::VERSION => {
+                let mut reply = Header::new(command::VERSION);
+                reply.data_count = MINOR_VERSION as u32;
+                self.send(Message::new(reply));
+            }
+            command::ECHO => self.send(Message::new(header)),
+            command::CREATE_CHAN => {
+                let name = CStr::from_bytes_until_nul(&message.payload).unwrap_or_default();
+                self.create_channel(header, name)
+            }
+            command::CLEAR_CHANNEL => {
+                let channel = self.state.lock().unwrap().channels.remove(&header.param1);
+                if let Some(channel) = channel {
+                    self.release(&channel);
+                }
+                self.send(Message::new(header));
+            }
+            command::READ_NOTIFY => {
+                let result = match (self.channel(header.param1), request_id(header)) {
+                    (Some(channel), Some(id)) => channel
+                        .pv
+                        .read(id, header.data_count as usize)
+                        .and_then(|buffer| payload(id, buffer)),
+                    (None, _) => Err(error::BADCHID),
+                    (_, None) => Err(error::BADTYPE),
+                };
+                let mut reply = header;
+                match result {
+                    Ok((payload, count)) => {
+                        reply.data_count = count as u32;
+                        reply.param1 = sys::ECA_NORMAL as u32;
+                        self.send(Message::with_payload(reply, payload));
+                    }
+                    Err(err) => {
+                        reply.payload_size = 0;
+                        reply.param1 = err.into_raw() as u32;
+                        self.send(Message::new(reply));
+                    }
+                }
+            }
+            command::WRITE | command::WRITE_NOTIFY => {
+                let result = match (self.channel(header.param1), request_id(header)) {
+                    (Some(channel), Some(id)) => write(
+                        &channel.pv,
+                        id,
+                        header.data_count as usize,
+                        &message.payload,
+                    ),
+                    (None, _) => Err(error::BADCHID),
+                    (_, None) => Err(error::BADTYPE),
+                };
+                if header.command == command::WRITE_NOTIFY {
+                    let mut reply = header;
+                    reply.payload_size = 0;
+                    reply.param1 = status(result);
+                    self.send(Message::new(reply));
+                } else if let Err(err) = result {
+                    let mut request = header;
+                    request.payload_size = 0;
+                    self.send(self.error_message(request, err));
+                }
+            }
+            command::EVENT_ADD => self.add_event(header, &message.payload),
+            command::EVENT_CANCEL => {
+                let sid = header.param1;
+                if let Some(channel) = self.channel(sid) {
+                    let mut monitors = channel.monitors.lock().unwrap();
+                    monitors.retain(|m| m.subid != header.param2);
+                }
+                // Empty event confirms cancellation.
+                let mut reply = Header::new(command::EVENT_ADD);
+                reply.data_type = header.data_type;
+                reply.data_count = header.data_count;
+                reply.param1 = sid;
+                reply.param2 = header.param2;
+                self.send(Message::new(reply));
+            }
+            _ => (),
+        }
+    }
+
+    fn create_channel(self: &Arc<Self>, header: Header, name: &CStr) {
+        let pv = self.server.upgrade().and_then(|server| server.find(name));
+        let pv = match pv {
+            Some(pv) => pv,
+            None => {
+                let mut reply = Header::new(command::CREATE_CH_FAIL);
+                reply.param1 = header.param1;
+                self.send(Message::new(reply));
+                return;
+            }
+        };
+        let cid = header.param1;
+        let channel = Arc::new(Channel {
+            conn: Arc::downgrade(self),
+            cid,
+            pv: pv.clone(),
+            monitors: Mutex::new(Vec::new()),
+        });
+        let sid = {
+            let mut state = self.state.lock().unwrap();
+            state.next_sid += 1;
+            let sid = state.next_sid;
+            state.channels.insert(sid, channel.clone());
+            sid
+        };
+        {
+            let state = pv.attach(Arc::downgrade(&channel) as Weak<dyn Observer>);
+            self.send_access(cid, state.access());
+        }
+        let mut reply = Header::new(command::CREATE_CHAN);
+        reply.data_type = pv.field_type().raw() as u16;
+        reply.data_count = pv.max_len() as u32;
+        reply.param1 = cid;
+        reply.param2 = sid;
+        self.send(Message::new(reply));
+    }
+
+    fn add_event(self: &Arc<Self>, header: Header, payload: &[u8]) {
+        let (sid, subid) = (header.param1, header.param2);
+        // Mask follows low, high and timeout values which are not used.
+        let mask = payload
+            .get(12..14)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+            .and_then(EventMask::from_bits)
+            .filter(|mask| !mask.is_empty());
+        match (self.channel(sid), request_id(header), mask) {
+            (None, _, _) => self.send(self.error_message(header, error::BADCHID)),
+            (_, None, _) => self.send(self.error_message(header, error::BADTYPE)),
+            (_, _, None) => self.send(self.error_message(header, error::BADMASK)),
+            (Some(channel), Some(id), Some(mask)) => {
+                channel.monitor(self, subid, id, header.data_count as usize, mask)
+            }
+        }
+    }
+}
+
+/// Write queued messages until connection is dropped or client stops accepting them.
+fn write_loop(mut stream: TcpStream, messages: Receiver<Message>) {
+    for message in messages {
+        if message.write_to(&mut stream).is_err() {
+            // Wake up reading thread to clean up the connection.
+            let _ = stream.shutdown(Shutdown::Both);
+            break;
+        }
+    }
+}
+
+fn request_id(header: Header) -> Option<RequestId> {
+    RequestId::try_from_raw(header.data_type as i32)
+}
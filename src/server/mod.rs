@@ -0,0 +1,263 @@
+//! Channel Access server that publishes PVs from Rust process.
+//!
+//! PVs are added to [`Server`] and become available to any Channel Access client in the network.
+//! Client reads and writes can be intercepted by [handlers](`Pv::on_write`),
+//! and changes of value, alarm or metadata are posted to monitors according to their [`EventMask`](`crate::types::EventMask`).
+//!
+//! ```no_run
+//! # use epics_ca::server::{Server, DEFAULT_PORT};
+//! let server = Server::bind(DEFAULT_PORT).unwrap();
+//! let pv = server.add("rust:value", 0.0);
+//! pv.set_units("V");
+//! pv.on_write(|_pv, value| {
+//!     println!("Written: {:?}", value);
+//!     Ok(())
+//! });
+//! pv.set_value(1.5).unwrap();
+//! ```
+
+mod connection;
+
+pub use crate::pv::Pv;
+
+use crate::{
+    convert,
+    protocol::{command, Header, Message, DEFAULT_REPEATER_PORT, DONT_REPLY, MINOR_VERSION},
+    types::{DynArray, DynValue},
+};
+use connection::Connection;
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Standard Channel Access server port.
+pub const DEFAULT_PORT: u16 = crate::protocol::DEFAULT_SERVER_PORT;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const BEACON_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Channel Access server.
+///
+/// Serves PVs until dropped.
+pub struct Server {
+    inner: Arc<ServerInner>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+pub(crate) struct ServerInner {
+    port: u16,
+    pvs: Mutex<HashMap<CString, Pv>>,
+    connections: Mutex<Vec<Weak<Connection>>>,
+    stopped: AtomicBool,
+}
+
+impl ServerInner {
+    pub(crate) fn find(&self, name: &CStr) -> Option<Pv> {
+        self.pvs.lock().unwrap().get(name).cloned()
+    }
+}
+
+impl Server {
+    /// Start server on the `port` of all network interfaces.
+    ///
+    /// If `port` is `0` then it is chosen by OS.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        let port = listener.local_addr()?.port();
+        let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        udp.set_broadcast(true)?;
+        udp.set_read_timeout(Some(POLL_INTERVAL))?;
+        let inner = Arc::new(ServerInner {
+            port,
+            pvs: Mutex::new(HashMap::new()),
+            connections: Mutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+        });
+
+        let udp_inner = inner.clone();
+        let tcp_inner = inner.clone();
+        let threads = vec![
+            thread::Builder::new()
+                .name("cas-udp".into())
+                .spawn(move || udp_loop(udp_inner, udp))?,
+            thread::Builder::new()
+                .name("cas-tcp".into())
+                .spawn(move || tcp_loop(tcp_inner, listener))?,
+        ];
+        Ok(Self { inner, threads })
+    }
+
+    /// Port the server listens to.
+    pub fn port(&self) -> u16 {
+        self.inner.port
+    }
+
+    /// Add PV with initial `value`.
+    ///
+    /// Scalar value makes PV of single element and vector makes array PV of the vector length.
+    /// Existing PV with the same name is [removed](`Self::remove`).
+    pub fn add(&self, name: &str, value: impl Into<DynValue>) -> Pv {
+        let value = value.into();
+        let max_len = usize::max(value.len(), 1);
+        self.add_array(name, convert::into_array(value), max_len)
+    }
+
+    /// Add array PV that can store up to `max_len` elements.
+    pub fn add_array(&self, name: &str, value: DynArray, max_len: usize) -> Pv {
+        let name = CString::new(name).unwrap();
+        let pv = Pv::new(name.clone(), value, max_len);
+        let old = self.inner.pvs.lock().unwrap().insert(name, pv.clone());
+        if let Some(old) = old {
+            old.detach_all();
+        }
+        pv
+    }
+
+    /// Find PV by name.
+    pub fn get(&self, name: &str) -> Option<Pv> {
+        self.inner.find(&CString::new(name).ok()?)
+    }
+
+    /// Stop serving PV.
+    ///
+    /// Connected clients are notified that channel is disconnected.
+    pub fn remove(&self, name: &str) -> Option<Pv> {
+        let name = CString::new(name).ok()?;
+        let pv = self.inner.pvs.lock().unwrap().remove(&name)?;
+        pv.detach_all();
+        Some(pv)
+    }
+
+    /// Add the same PVs as test IOC from `ioc/` has.
+    #[doc(hidden)]
+    pub fn add_test_records(&self) {
+        crate::pv::add_test_records(|name, value, max_len| self.add_array(name, value, max_len));
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.inner.stopped.store(true, Ordering::Release);
+        // Wake up thread waiting for incoming connections.
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, self.inner.port));
+        let connections = self
+            .inner
+            .connections
+            .lock()
+            .unwrap()
+            .drain(..)
+            .collect::<Vec<_>>();
+        for conn in connections.into_iter().filter_map(|c| c.upgrade()) {
+            conn.close();
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn udp_loop(server: Arc<ServerInner>, udp: UdpSocket) {
+    let mut buf = vec![0; 0x10000];
+    let mut beacon_id = 0;
+    let mut next_beacon = Instant::now();
+    while !server.stopped.load(Ordering::Acquire) {
+        if Instant::now() >= next_beacon {
+            send_beacon(&server, &udp, beacon_id);
+            beacon_id += 1;
+            next_beacon += BEACON_INTERVAL;
+        }
+        if let Ok((len, src)) = udp.recv_from(&mut buf) {
+            let reply = handle_search(&server, &buf[..len]);
+            if !reply.is_empty() {
+                let _ = udp.send_to(&reply, src);
+            }
+        }
+    }
+}
+
+/// Announce server to clients via CA repeaters.
+fn send_beacon(server: &ServerInner, udp: &UdpSocket, id: u32) {
+    let mut header = Header::new(command::RSRV_IS_UP);
+    header.data_type = server.port;
+    header.data_count = MINOR_VERSION as u32;
+    header.param1 = id;
+    let beacon = Message::new(header).to_bytes();
+    for ip in [Ipv4Addr::LOCALHOST, Ipv4Addr::BROADCAST] {
+        // Beacons are optional, clients find server by search requests anyway.
+        let _ = udp.send_to(
+            &beacon,
+            SocketAddr::from(SocketAddrV4::new(ip, DEFAULT_REPEATER_PORT)),
+        );
+    }
+}
+
+/// Make reply datagram to the search requests.
+fn handle_search(server: &ServerInner, data: &[u8]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    for message in Message::decode_all(data) {
+        let header = message.header;
+        if header.command != command::SEARCH {
+            continue;
+        }
+        let found = CStr::from_bytes_until_nul(&message.payload)
+            .ok()
+            .and_then(|name| server.find(name))
+            .is_some();
+        if reply.is_empty() && (found || header.data_type != DONT_REPLY) {
+            let mut version = Header::new(command::VERSION);
+            version.data_count = MINOR_VERSION as u32;
+            Message::new(version).encode(&mut reply);
+        }
+        if found {
+            let mut found = Header::new(command::SEARCH);
+            found.data_type = server.port;
+            // Client uses source address of the datagram.
+            found.param1 = u32::MAX;
+            found.param2 = header.param1;
+            Message::with_payload(found, MINOR_VERSION.to_be_bytes().to_vec()).encode(&mut reply);
+        } else if header.data_type != DONT_REPLY {
+            let mut not_found = header;
+            not_found.command = command::NOT_FOUND;
+            not_found.payload_size = 0;
+            Message::new(not_found).encode(&mut reply);
+        }
+    }
+    reply
+}
+
+fn tcp_loop(server: Arc<ServerInner>, listener: TcpListener) {
+    for stream in listener.incoming() {
+        if server.stopped.load(Ordering::Acquire) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let _ = stream.set_nodelay(true);
+        let conn = match Connection::new(Arc::downgrade(&server), &stream) {
+            Ok(conn) => Arc::new(conn),
+            Err(_) => continue,
+        };
+        {
+            let mut connections = server.connections.lock().unwrap();
+            connections.retain(|c| c.strong_count() > 0);
+            connections.push(Arc::downgrade(&conn));
+        }
+        let _ = thread::Builder::new()
+            .name("cas-client".into())
+            .spawn(move || conn.serve(stream));
+    }
+}
+
+#[cfg(all(test, any(feature = "libca", feature = "pure-rust")))]
+mod tests;
@@ -0,0 +1,269 @@
+use super::Server;
+use crate::{
+    error,
+    protocol::{command, max_payload_size, Header, Message},
+    request::{DynCtrl, DynKind},
+    types::{
+        AccessRights, Alarm, AlarmSeverity, DynArray, DynScalar, DynValue, EventMask, FieldId,
+    },
+    Context, LocalContext,
+};
+use async_std::{future::timeout, test as async_test};
+use cstr::cstr;
+use futures::{pin_mut, StreamExt};
+use serial_test::serial;
+use std::{
+    env,
+    io::BufReader,
+    net::{Ipv4Addr, TcpStream},
+    time::Duration,
+};
+
+/// Start server on random port and point clients to it.
+fn start() -> Server {
+    let server = Server::bind(0).unwrap();
+    env::set_var("EPICS_CA_ADDR_LIST", format!("127.0.0.1:{}", server.port()));
+    env::set_var("EPICS_CA_AUTO_ADDR_LIST", "NO");
+    server
+}
+
+#[async_test]
+#[serial]
+async fn read_write_monitor() {
+    let server = start();
+    let pv = server.add("server:test:value", 0.0);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<f64>(cstr!("server:test:value"))
+        .await
+        .unwrap();
+    assert_eq!(channel.get().await.unwrap(), 0.0);
+
+    let monitor = channel.subscribe();
+    pin_mut!(monitor);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), 0.0);
+
+    channel.put(1.5).unwrap().await.unwrap();
+    assert_eq!(monitor.next().await.unwrap().unwrap(), 1.5);
+    assert_eq!(pv.value(), DynValue::from(1.5));
+
+    pv.set_value(-2).unwrap();
+    assert_eq!(monitor.next().await.unwrap().unwrap(), -2.0);
+}
+
+#[async_test]
+#[serial]
+async fn metadata() {
+    let server = start();
+    let pv = server.add("server:test:meta", 1.0);
+    pv.set_units("mm");
+    pv.set_precision(2);
+    pv.set_alarm(Alarm {
+        severity: AlarmSeverity::Minor,
+        ..Default::default()
+    });
+    let ctx = Context::new().unwrap();
+    let channel = ctx.connect::<f64>(cstr!("server:test:meta")).await.unwrap();
+
+    let ctrl = channel.get_dyn(DynKind::Ctrl).unwrap().await.unwrap();
+    assert_eq!(ctrl.value(), &DynValue::from(1.0));
+    assert_eq!(ctrl.alarm().unwrap().severity, AlarmSeverity::Minor);
+    match ctrl.ctrl().unwrap() {
+        DynCtrl::Double {
+            precision, units, ..
+        } => {
+            assert_eq!(*precision, 2);
+            assert_eq!(units.0.to_str().unwrap(), "mm");
+        }
+        other => panic!("unexpected ctrl: {:?}", other),
+    }
+}
+
+#[async_test]
+#[serial]
+async fn array() {
+    let server = start();
+    let pv = server.add_array("server:test:array", DynArray::Long(Vec::new()), 8);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<[i32]>(cstr!("server:test:array"))
+        .await
+        .unwrap();
+    assert_eq!(channel.element_count().unwrap(), 8);
+
+    channel.put_ref(&[1, 2, 3]).unwrap().await.unwrap();
+    assert_eq!(pv.value(), DynValue::from(vec![1, 2, 3]));
+    assert_eq!(channel.get_vec().await.unwrap(), [1, 2, 3]);
+}
+
+#[async_test]
+#[serial]
+async fn handlers() {
+    let server = start();
+    let pv = server.add("server:test:handlers", 0i32);
+    pv.on_write(|_, value| match value {
+        DynValue::Scalar(DynScalar::Long(x)) if *x > 0 => Ok(()),
+        _ => Err(error::PUTFAIL),
+    });
+    pv.on_read(|pv| {
+        let value = pv.value();
+        pv.set_alarm(Alarm {
+            severity: match value == 1i32.into() {
+                true => AlarmSeverity::Major,
+                false => AlarmSeverity::None,
+            },
+            ..Default::default()
+        });
+    });
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<i32>(cstr!("server:test:handlers"))
+        .await
+        .unwrap();
+
+    channel.put(1).unwrap().await.unwrap();
+    assert_eq!(channel.put(-1).unwrap().await.err(), Some(error::PUTFAIL));
+    let sts = channel.get_dyn(DynKind::Sts).unwrap().await.unwrap();
+    assert_eq!(sts.value(), &DynValue::from(1i32));
+    assert_eq!(sts.alarm().unwrap().severity, AlarmSeverity::Major);
+}
+
+#[async_test]
+#[serial]
+async fn access_and_removal() {
+    let server = start();
+    let pv = server.add("server:test:access", 0i16);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<i16>(cstr!("server:test:access"))
+        .await
+        .unwrap();
+
+    let access = channel.access_rights_events();
+    pin_mut!(access);
+    pv.set_access_rights(AccessRights::new(true, false));
+    while access.next().await.unwrap().write_access() {}
+    assert_eq!(channel.put(1).err(), Some(error::NOWTACCESS));
+
+    let events = channel.connection_events();
+    pin_mut!(events);
+    server.remove("server:test:access").unwrap();
+    assert!(!events.next().await.unwrap().is_connected());
+    server.add("server:test:access", 5i16);
+    assert!(events.next().await.unwrap().is_connected());
+    assert_eq!(channel.get().await.unwrap(), 5);
+}
+
+#[async_test]
+#[serial]
+async fn context_builder() {
+    let server = start();
+    server.add("server:test:builder", 1i32);
+    // Make environment point to nowhere.
+    env::set_var("EPICS_CA_ADDR_LIST", "127.0.0.1:1");
+    let ctx = Context::builder()
+        .addr_list([format!("127.0.0.1:{}", server.port())])
+        .auto_addr_list(false)
+        .build()
+        .unwrap();
+    let channel = ctx
+        .connect::<i32>(cstr!("server:test:builder"))
+        .await
+        .unwrap();
+    assert_eq!(channel.get().await.unwrap(), 1);
+    assert_eq!(env::var("EPICS_CA_ADDR_LIST").unwrap(), "127.0.0.1:1");
+}
+
+#[test]
+#[serial]
+fn non_preemptive() {
+    let server = start();
+    let pv = server.add("server:test:poll", 0i32);
+    let ctx = LocalContext::new().unwrap();
+    let channel = ctx
+        .block_on(ctx.connect::<i32>(cstr!("server:test:poll")))
+        .unwrap();
+    ctx.block_on(channel.put(2).unwrap()).unwrap();
+    assert_eq!(pv.value(), DynValue::from(2i32));
+
+    let monitor = channel.subscribe();
+    pin_mut!(monitor);
+    assert_eq!(ctx.block_on(monitor.next()).unwrap().unwrap(), 2);
+    pv.set_value(3).unwrap();
+    assert_eq!(ctx.block_on(monitor.next()).unwrap().unwrap(), 3);
+}
+
+#[async_test]
+#[serial]
+async fn monitor_read_access() {
+    let server = start();
+    let pv = server.add("server:test:noread", 0i32);
+    let ctx = Context::new().unwrap();
+    let channel = ctx
+        .connect::<i32>(cstr!("server:test:noread"))
+        .await
+        .unwrap();
+
+    let monitor = channel.subscribe_buffered();
+    pin_mut!(monitor);
+    assert_eq!(monitor.next().await.unwrap().unwrap(), 0);
+
+    let access = channel.access_rights_events();
+    pin_mut!(access);
+    pv.set_access_rights(AccessRights::new(false, true));
+    while access.next().await.unwrap().read_access() {}
+    pv.set_value(1).unwrap();
+    assert!(timeout(Duration::from_millis(200), monitor.next())
+        .await
+        .is_err());
+
+    let other = channel.subscribe();
+    pin_mut!(other);
+    assert_eq!(other.next().await.unwrap(), Err(error::NORDACCESS));
+}
+
+fn add_event(writer: &mut TcpStream, sid: u32, mask: u16) {
+    let mut header = Header::new(command::EVENT_ADD);
+    header.data_type = FieldId::Long.raw() as u16;
+    header.data_count = 1;
+    header.param1 = sid;
+    header.param2 = 1;
+    let mut payload = vec![0; 16];
+    payload[12..14].copy_from_slice(&mask.to_be_bytes());
+    Message::with_payload(header, payload)
+        .write_to(writer)
+        .unwrap();
+}
+
+#[test]
+#[serial]
+fn add_event_errors() {
+    let server = start();
+    server.add("server:test:raw", 0i32);
+    let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, server.port())).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    let mut receive = || Message::read_from(&mut reader, max_payload_size(None)).unwrap();
+
+    add_event(&mut writer, 42, EventMask::VALUE.raw() as u16);
+    let reply = receive();
+    assert_eq!(reply.header.command, command::ERROR);
+    assert_eq!(reply.header.param2, error::BADCHID.into_raw() as u32);
+
+    let mut header = Header::new(command::CREATE_CHAN);
+    header.param1 = 7;
+    Message::with_payload(header, b"server:test:raw\0".to_vec())
+        .write_to(&mut writer)
+        .unwrap();
+    let sid = loop {
+        let reply = receive();
+        if reply.header.command == command::CREATE_CHAN {
+            break reply.header.param2;
+        }
+    };
+
+    add_event(&mut writer, sid, 0);
+    let reply = receive();
+    assert_eq!(reply.header.command, command::ERROR);
+    assert_eq!(reply.header.param2, error::BADMASK.into_raw() as u32);
+}
@@ -0,0 +1,152 @@
+//! Reporting to [`tracing`](https://docs.rs/tracing) when `tracing` feature is enabled.
+//!
+//! Without the feature spans are zero-sized stubs and events are omitted.
+
+use crate::error::Error;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Stub of span used when `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub fn enter(&self) -> Entered {
+        Entered
+    }
+    pub fn in_scope<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        f()
+    }
+}
+
+/// Stub of entered span guard.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
+
+/// Emit event of given level, e.g. `event!(debug, channel = ?name, "channel created")`.
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)*);
+    }};
+}
+pub(crate) use event;
+
+/// Make span of request to the channel.
+macro_rules! request_span {
+    ($name:literal, $channel:expr, $id:expr) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!($name, channel = ?$channel.name(), request = ?$id);
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::trace::Span;
+        span
+    }};
+}
+pub(crate) use request_span;
+
+/// Report that the request is initiated (or failed to) in the current span.
+pub(crate) fn started(result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(()) => tracing::trace!("started"),
+        Err(err) => tracing::debug!(error = ?err, "failed to start"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = result;
+}
+
+/// Report result of the request in the current span.
+pub(crate) fn finished<T>(result: &Result<T, Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(_) => tracing::trace!("done"),
+        Err(err) => tracing::debug!(error = ?err, "failed"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = result;
+}
+
+/// Report update of the subscription in the current span.
+pub(crate) fn updated<T>(result: &Result<T, Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(_) => tracing::trace!("update"),
+        Err(err) => tracing::debug!(error = ?err, "update failed"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = result;
+}
+
+/// Print diagnostic text the way EPICS CA library does.
+///
+/// With `tracing` feature the text goes to the same target as library messages, otherwise to stdout.
+#[cfg(any(feature = "mock", feature = "pure-rust", feature = "tracing"))]
+pub(crate) fn print(text: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(target: "epics_ca::libca", "{}", text);
+    #[cfg(not(feature = "tracing"))]
+    println!("{}", text);
+}
+
+/// Handler of messages that EPICS CA library prints.
+#[cfg(feature = "tracing")]
+pub(crate) use printf::HANDLER as PRINTF_HANDLER;
+
+/// Handler receives `va_list` and forwards it to `vsnprintf` as a pointer.
+/// This is valid only on targets where `va_list` is a pointer or an array passed as a pointer.
+#[cfg(all(
+    feature = "tracing",
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(
+            target_arch = "aarch64",
+            any(target_vendor = "apple", target_os = "windows")
+        )
+    )
+))]
+mod printf {
+    use super::print;
+    use std::{
+        ffi::CStr,
+        os::raw::{c_char, c_int},
+    };
+
+    pub const HANDLER: sys::caPrintfFunc = Some(handler);
+
+    /// Messages don't have severity, so all of them are reported at the same level.
+    unsafe extern "C" fn handler(format: *const c_char, args: sys::va_list) -> c_int {
+        // `args` can be consumed only once, so longer messages are truncated.
+        let mut buf = [0u8; 1024];
+        let len = sys::vsnprintf(buf.as_mut_ptr() as *mut _, buf.len(), format, args);
+        if len < 0 {
+            return len;
+        }
+        let text = CStr::from_bytes_until_nul(&buf).unwrap_or_default();
+        let text = text.to_string_lossy();
+        let text = text.trim_end();
+        if !text.is_empty() {
+            print(text);
+        }
+        len
+    }
+}
+
+/// On other targets library prints messages itself.
+#[cfg(all(
+    feature = "tracing",
+    not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(
+            target_arch = "aarch64",
+            any(target_vendor = "apple", target_os = "windows")
+        )
+    ))
+))]
+mod printf {
+    pub const HANDLER: sys::caPrintfFunc = None;
+}
@@ -0,0 +1,188 @@
+use super::{EpicsEnum, EpicsString, Field, FieldId};
+
+/// Single item of arbitrary field type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynScalar {
+    String(EpicsString),
+    Short(i16),
+    Float(f32),
+    Enum(EpicsEnum),
+    Char(u8),
+    Long(i32),
+    Double(f64),
+}
+
+/// Array of items of arbitrary field type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynArray {
+    String(Vec<EpicsString>),
+    Short(Vec<i16>),
+    Float(Vec<f32>),
+    Enum(Vec<EpicsEnum>),
+    Char(Vec<u8>),
+    Long(Vec<i32>),
+    Double(Vec<f64>),
+}
+
+/// Value of the channel which type is known only at runtime.
+///
+/// Scalar values correspond to channels with single element, arrays - to all other channels.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynValue {
+    Scalar(DynScalar),
+    Array(DynArray),
+}
+
+impl DynScalar {
+    /// Field type of the item.
+    pub fn field_id(&self) -> FieldId {
+        match self {
+            Self::String(_) => FieldId::String,
+            Self::Short(_) => FieldId::Short,
+            Self::Float(_) => FieldId::Float,
+            Self::Enum(_) => FieldId::Enum,
+            Self::Char(_) => FieldId::Char,
+            Self::Long(_) => FieldId::Long,
+            Self::Double(_) => FieldId::Double,
+        }
+    }
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl DynArray {
+    /// Field type of the array items.
+    pub fn field_id(&self) -> FieldId {
+        match self {
+            Self::String(_) => FieldId::String,
+            Self::Short(_) => FieldId::Short,
+            Self::Float(_) => FieldId::Float,
+            Self::Enum(_) => FieldId::Enum,
+            Self::Char(_) => FieldId::Char,
+            Self::Long(_) => FieldId::Long,
+            Self::Double(_) => FieldId::Double,
+        }
+    }
+    /// Number of items in the array.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::String(v) => v.len(),
+            Self::Short(v) => v.len(),
+            Self::Float(v) => v.len(),
+            Self::Enum(v) => v.len(),
+            Self::Char(v) => v.len(),
+            Self::Long(v) => v.len(),
+            Self::Double(v) => v.len(),
+        }
+    }
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl DynValue {
+    /// Field type of the value items.
+    pub fn field_id(&self) -> FieldId {
+        match self {
+            Self::Scalar(scalar) => scalar.field_id(),
+            Self::Array(array) => array.field_id(),
+        }
+    }
+    /// Number of items in the value.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Scalar(_) => 1,
+            Self::Array(array) => array.len(),
+        }
+    }
+    pub fn is_array(&self) -> bool {
+        matches!(self, Self::Array(_))
+    }
+}
+
+/// Field that can be stored in dynamic value.
+pub trait DynField: Field {
+    fn into_dyn_scalar(self) -> DynScalar;
+    fn into_dyn_array(items: Vec<Self>) -> DynArray;
+}
+
+macro_rules! impl_dyn_field {
+    ($type:ty, $variant:ident) => {
+        impl DynField for $type {
+            fn into_dyn_scalar(self) -> DynScalar {
+                DynScalar::$variant(self)
+            }
+            fn into_dyn_array(items: Vec<Self>) -> DynArray {
+                DynArray::$variant(items)
+            }
+        }
+
+        impl From<$type> for DynScalar {
+            fn from(value: $type) -> Self {
+                value.into_dyn_scalar()
+            }
+        }
+        impl From<Vec<$type>> for DynArray {
+            fn from(items: Vec<$type>) -> Self {
+                <$type>::into_dyn_array(items)
+            }
+        }
+        impl From<$type> for DynValue {
+            fn from(value: $type) -> Self {
+                DynValue::Scalar(value.into())
+            }
+        }
+        impl From<Vec<$type>> for DynValue {
+            fn from(items: Vec<$type>) -> Self {
+                DynValue::Array(items.into())
+            }
+        }
+    };
+}
+
+impl_dyn_field!(EpicsString, String);
+impl_dyn_field!(i16, Short);
+impl_dyn_field!(f32, Float);
+impl_dyn_field!(EpicsEnum, Enum);
+impl_dyn_field!(u8, Char);
+impl_dyn_field!(i32, Long);
+impl_dyn_field!(f64, Double);
+
+impl DynValue {
+    /// Make value from items received from channel.
+    ///
+    /// If `scalar` is `true` and there is exactly one item then scalar value is created.
+    pub fn from_items<T: DynField>(items: &[T], scalar: bool) -> Self {
+        if scalar && items.len() == 1 {
+            DynValue::Scalar(items[0].into_dyn_scalar())
+        } else {
+            DynValue::Array(T::into_dyn_array(items.to_vec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_items() {
+        assert_eq!(
+            DynValue::from_items(&[1.0f64], true),
+            DynValue::Scalar(DynScalar::Double(1.0))
+        );
+        assert_eq!(
+            DynValue::from_items(&[1.0f64], false),
+            DynValue::Array(DynArray::Double(vec![1.0]))
+        );
+        let value = DynValue::from_items::<i32>(&[], true);
+        assert_eq!(value, DynValue::Array(DynArray::Long(Vec::new())));
+        assert_eq!(value.field_id(), FieldId::Long);
+        assert_eq!(value.len(), 0);
+    }
+
+    #[test]
+    fn from_value() {
+        assert_eq!(DynValue::from(EpicsEnum(2)).field_id(), FieldId::Enum);
+        assert_eq!(DynValue::from(vec![0u8; 4]).len(), 4);
+        assert!(DynValue::from(vec![0i16]).is_array());
+        assert!(!DynValue::from(0i16).is_array());
+    }
+}
@@ -25,15 +25,32 @@ impl EpicsTimeStamp {
         self.0.nsec
     }
 
-    pub fn to_system(self) -> SystemTime {
+    fn epics_epoch() -> SystemTime {
         let unix_epoch = Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
         let epics_epoch = Utc.with_ymd_and_hms(1990, 1, 1, 0, 0, 0).unwrap();
-        let diff = (epics_epoch - unix_epoch).to_std().unwrap();
-        SystemTime::UNIX_EPOCH
-            + diff
+        SystemTime::UNIX_EPOCH + (epics_epoch - unix_epoch).to_std().unwrap()
+    }
+
+    pub fn to_system(self) -> SystemTime {
+        Self::epics_epoch()
             + (Duration::from_secs(self.0.secPastEpoch as u64)
                 + Duration::from_nanos(self.0.nsec as u64))
     }
+
+    /// Convert from system time. Time before EPICS epoch (1990-01-01) is clamped to it.
+    pub fn from_system(time: SystemTime) -> Self {
+        let since = time
+            .duration_since(Self::epics_epoch())
+            .unwrap_or(Duration::ZERO);
+        Self(sys::epicsTimeStamp {
+            secPastEpoch: since.as_secs() as u32,
+            nsec: since.subsec_nanos(),
+        })
+    }
+
+    pub fn now() -> Self {
+        Self::from_system(SystemTime::now())
+    }
 }
 
 impl PartialEq for EpicsTimeStamp {
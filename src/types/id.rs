@@ -28,6 +28,21 @@ impl FieldId {
         }
     }
 
+    /// Whether field stores numeric value.
+    ///
+    /// Enum is treated as numeric because its value is an index.
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, FieldId::String)
+    }
+
+    /// Whether Channel Access can convert value of this field type to `target` type.
+    ///
+    /// Any field can be converted to string and any numeric field can be converted to any other numeric one.
+    /// Conversion from string to number is done only if the string can be parsed, so it isn't considered here.
+    pub fn can_convert_to(&self, target: FieldId) -> bool {
+        *self == target || target == FieldId::String || (self.is_numeric() && target.is_numeric())
+    }
+
     pub fn raw(&self) -> i32 {
         match self {
             FieldId::String => sys::DBF_STRING,
@@ -193,13 +208,25 @@ impl EventMask {
 }
 
 /// Channel access rights.
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
 pub struct AccessRights {
     read_access: bool,
     write_access: bool,
 }
 
 impl AccessRights {
+    pub fn new(read_access: bool, write_access: bool) -> Self {
+        Self {
+            read_access,
+            write_access,
+        }
+    }
+    pub fn from_raw(raw: sys::ca_access_rights) -> Self {
+        Self {
+            read_access: raw & sys::CA_READ_ACCESS != 0,
+            write_access: raw & sys::CA_WRITE_ACCESS != 0,
+        }
+    }
     pub fn raw(self) -> sys::ca_access_rights {
         let mut raw = 0;
         if self.read_access {
@@ -210,16 +237,46 @@ impl AccessRights {
         }
         raw
     }
+
+    /// Whether channel can be read or subscribed to.
+    pub fn read_access(&self) -> bool {
+        self.read_access
+    }
+    /// Whether channel can be written.
+    pub fn write_access(&self) -> bool {
+        self.write_access
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(feature = "libca")]
     fn dbf_size(dbf: FieldId) -> usize {
         unsafe { *(sys::dbr_size.as_ptr().offset(dbf.raw() as isize)) as usize }
     }
 
+    #[test]
+    fn conversion() {
+        assert!(FieldId::Long.can_convert_to(FieldId::Double));
+        assert!(FieldId::Enum.can_convert_to(FieldId::Short));
+        assert!(FieldId::Double.can_convert_to(FieldId::String));
+        assert!(FieldId::String.can_convert_to(FieldId::String));
+        assert!(!FieldId::String.can_convert_to(FieldId::Double));
+    }
+
+    #[test]
+    fn access_rights() {
+        for (read, write) in [(false, false), (true, false), (false, true), (true, true)] {
+            let rights = AccessRights::new(read, write);
+            assert_eq!(rights.read_access(), read);
+            assert_eq!(rights.write_access(), write);
+            assert_eq!(AccessRights::from_raw(rights.raw()), rights);
+        }
+    }
+
+    #[cfg(feature = "libca")]
     #[test]
     fn dbr_sizes() {
         assert_eq!(dbf_size(FieldId::String), sys::MAX_STRING_SIZE as usize);
@@ -1,9 +1,11 @@
 mod alarm;
+mod dynamic;
 mod epics;
 mod id;
 mod value;
 
 pub use alarm::*;
+pub use dynamic::*;
 pub use epics::*;
 pub use id::*;
 pub use value::*;
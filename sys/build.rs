@@ -11,15 +11,17 @@ fn epics_target(target: &str) -> String {
 }
 
 fn main() {
-    println!("cargo:rustc-link-lib=dylib=ca");
-    println!("cargo:rustc-link-lib=dylib=Com");
-    if let Ok(epics_base) = env::var("EPICS_BASE") {
-        let target = env::var("TARGET").unwrap();
-        println!(
-            "cargo:rustc-link-search={}/lib/{}",
-            epics_base,
-            epics_target(&target),
-        );
+    if env::var("CARGO_FEATURE_LINK").is_ok() {
+        println!("cargo:rustc-link-lib=dylib=ca");
+        println!("cargo:rustc-link-lib=dylib=Com");
+        if let Ok(epics_base) = env::var("EPICS_BASE") {
+            let target = env::var("TARGET").unwrap();
+            println!(
+                "cargo:rustc-link-search={}/lib/{}",
+                epics_base,
+                epics_target(&target),
+            );
+        }
     }
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-env-changed=EPICS_BASE");
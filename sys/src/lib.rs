@@ -107,7 +107,7 @@ pub const CA_READ_ACCESS: u32 = 1 << 31;
 #[cfg(target_endian = "little")]
 pub const CA_READ_ACCESS: u32 = 1 << 0;
 #[cfg(target_endian = "big")]
-pub const CA_READ_ACCESS: u32 = 1 << 30;
+pub const CA_WRITE_ACCESS: u32 = 1 << 30;
 #[cfg(target_endian = "little")]
 pub const CA_WRITE_ACCESS: u32 = 1 << 1;
 
@@ -134,3 +134,32 @@ pub unsafe fn ca_get_callback(
 ) -> libc::c_int {
     ca_array_get_callback(type_, 1, chanId, pFunc, pArg)
 }
+
+/// Variable argument list passed to a function.
+///
+/// It is opaque and can only be forwarded to C functions that take `va_list`, e.g. [`vsnprintf`].
+/// This is valid only on targets where `va_list` is passed as a pointer (e.g. x86, x86_64, aarch64 on macOS and Windows).
+/// On other targets (e.g. aarch64 Linux) it is a structure which layout is not represented here.
+pub type va_list = *mut libc::c_void;
+pub type caPrintfFunc = ::core::option::Option<
+    unsafe extern "C" fn(pformat: *const libc::c_char, args: va_list) -> libc::c_int,
+>;
+extern "C" {
+    pub fn ca_replace_printf_handler(ca_printf_func: caPrintfFunc) -> libc::c_int;
+}
+#[cfg(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(
+        target_arch = "aarch64",
+        any(target_vendor = "apple", target_os = "windows")
+    )
+))]
+extern "C" {
+    pub fn vsnprintf(
+        s: *mut libc::c_char,
+        n: libc::size_t,
+        format: *const libc::c_char,
+        args: va_list,
+    ) -> libc::c_int;
+}
@@ -0,0 +1,64 @@
+use clap::Parser;
+use epics_ca::{
+    error::Error,
+    request::{DynKind, DynRequest},
+    Context,
+};
+use epics_ca_tools::{connect, enum_strs, print_error, print_request, Options};
+use futures::future::join_all;
+use std::{process::ExitCode, time::Duration};
+
+/// Read values of Channel Access PVs.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    options: Options,
+    /// Print timestamp and alarm along with the value.
+    #[arg(short = 'a', conflicts_with = "ctrl")]
+    time: bool,
+    /// Print alarm and control information (units, limits, enum strings).
+    #[arg(short = 'd')]
+    ctrl: bool,
+    /// PV names.
+    #[arg(required = true)]
+    names: Vec<String>,
+}
+
+async fn get(
+    ctx: &Context,
+    name: &str,
+    kind: DynKind,
+    timeout: Duration,
+) -> Result<(DynRequest, Option<Vec<String>>), Error> {
+    let chan = connect(ctx, name, timeout).await?;
+    let strs = enum_strs(&chan, timeout).await?;
+    let req = chan.get_dyn(kind)?.timeout(timeout).await?;
+    Ok((req, strs))
+}
+
+#[async_std::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let ctx = Context::new().expect("Cannot create context");
+    let kind = if args.ctrl {
+        DynKind::Ctrl
+    } else if args.time {
+        DynKind::Time
+    } else {
+        DynKind::Base
+    };
+    let timeout = args.options.timeout();
+
+    let results = join_all(args.names.iter().map(|name| get(&ctx, name, kind, timeout))).await;
+    let mut code = ExitCode::SUCCESS;
+    for (name, result) in args.names.iter().zip(results) {
+        match result {
+            Ok((req, strs)) => print_request(name, &req, strs.as_deref(), args.options.json),
+            Err(err) => {
+                print_error(name, err, args.options.json);
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    code
+}
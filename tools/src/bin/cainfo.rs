@@ -0,0 +1,65 @@
+use clap::Parser;
+use epics_ca::{error::Error, Channel, Context};
+use epics_ca_tools::{connect, print_error, Options};
+use futures::future::join_all;
+use serde_json::json;
+use std::process::ExitCode;
+
+/// Print information about Channel Access PVs.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    options: Options,
+    /// PV names.
+    #[arg(required = true)]
+    names: Vec<String>,
+}
+
+fn print_info(name: &str, chan: &Channel, json: bool) -> Result<(), Error> {
+    let host = chan.host_name()?.to_string_lossy();
+    let field_type = format!("DBF_{:?}", chan.field_type()?).to_uppercase();
+    let count = chan.element_count()?;
+    let access = chan.access_rights();
+    if json {
+        let info = json!({
+            "name": name,
+            "connected": chan.is_connected(),
+            "host": host,
+            "access": { "read": access.read_access(), "write": access.write_access() },
+            "field_type": field_type,
+            "element_count": count,
+        });
+        println!("{}", info);
+    } else {
+        let access = match (access.read_access(), access.write_access()) {
+            (true, true) => "read, write",
+            (true, false) => "read, no write",
+            (false, true) => "no read, write",
+            (false, false) => "no read, no write",
+        };
+        println!("{}", name);
+        println!("    State:            connected");
+        println!("    Host:             {}", host);
+        println!("    Access:           {}", access);
+        println!("    Native data type: {}", field_type);
+        println!("    Element count:    {}", count);
+    }
+    Ok(())
+}
+
+#[async_std::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let ctx = Context::new().expect("Cannot create context");
+    let timeout = args.options.timeout();
+
+    let results = join_all(args.names.iter().map(|name| connect(&ctx, name, timeout))).await;
+    let mut code = ExitCode::SUCCESS;
+    for (name, result) in args.names.iter().zip(results) {
+        if let Err(err) = result.and_then(|chan| print_info(name, &chan, args.options.json)) {
+            print_error(name, err, args.options.json);
+            code = ExitCode::FAILURE;
+        }
+    }
+    code
+}
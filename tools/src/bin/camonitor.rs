@@ -0,0 +1,77 @@
+use clap::Parser;
+use epics_ca::{request::DynKind, Context};
+use epics_ca_tools::{connect, enum_strs, print_error, print_request, Options};
+use futures::{
+    future::join_all,
+    stream::{select_all, StreamExt},
+};
+use std::process::ExitCode;
+
+/// Monitor values of Channel Access PVs.
+///
+/// Each update is printed with its timestamp and alarm.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    options: Options,
+    /// Exit after receiving this number of updates from each PV.
+    #[arg(short = 'c', long)]
+    count: Option<usize>,
+    /// PV names.
+    #[arg(required = true)]
+    names: Vec<String>,
+}
+
+#[async_std::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let ctx = Context::new().expect("Cannot create context");
+    let timeout = args.options.timeout();
+    let json = args.options.json;
+
+    let ctx = &ctx;
+    let results = join_all(args.names.iter().map(|name| async move {
+        let chan = connect(ctx, name, timeout).await?;
+        let strs = enum_strs(&chan, timeout).await?;
+        Ok((name, chan, strs))
+    }))
+    .await;
+    let mut code = ExitCode::SUCCESS;
+    let mut channels = Vec::new();
+    for (name, result) in args.names.iter().zip(results) {
+        match result {
+            Ok(channel) => channels.push(channel),
+            Err(err) => {
+                print_error(name, err, json);
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut monitors = Vec::new();
+    for (name, chan, strs) in channels.iter() {
+        match chan.subscribe_dyn_buffered(DynKind::Time) {
+            Ok(monitor) => {
+                let monitor = monitor.map(move |result| (name.as_str(), strs.as_deref(), result));
+                let monitor = match args.count {
+                    Some(count) => monitor.take(count).left_stream(),
+                    None => monitor.right_stream(),
+                };
+                monitors.push(monitor);
+            }
+            Err(err) => {
+                print_error(name, err, json);
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let mut updates = select_all(monitors);
+    while let Some((name, strs, result)) = updates.next().await {
+        match result {
+            Ok(req) => print_request(name, &req, strs, json),
+            Err(err) => print_error(name, err, json),
+        }
+    }
+    code
+}
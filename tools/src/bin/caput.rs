@@ -0,0 +1,88 @@
+use clap::Parser;
+use epics_ca::{
+    error::{self, Error},
+    request::{DynKind, DynRequest},
+    types::EpicsString,
+    Context,
+};
+use epics_ca_tools::{connect, enum_strs, format_request, json_request, print_error, Options};
+use serde_json::json;
+use std::{ffi::CString, process::ExitCode};
+
+/// Write value to Channel Access PV.
+///
+/// Values are passed to the server as strings and converted to the native type of the PV.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    options: Options,
+    /// Write each of values as an array element. Otherwise values are joined with spaces.
+    #[arg(short = 'a')]
+    array: bool,
+    /// PV name.
+    name: String,
+    /// Values to write.
+    #[arg(required = true)]
+    values: Vec<String>,
+}
+
+fn epics_string(value: &str) -> Result<EpicsString, Error> {
+    CString::new(value)
+        .ok()
+        .and_then(|s| EpicsString::from_cstr(&s))
+        .ok_or(error::BADSTR)
+}
+
+async fn put(
+    ctx: &Context,
+    args: &Args,
+) -> Result<(DynRequest, DynRequest, Option<Vec<String>>), Error> {
+    let timeout = args.options.timeout();
+    let values = if args.array {
+        args.values
+            .iter()
+            .map(|value| epics_string(value))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![epics_string(&args.values.join(" "))?]
+    };
+
+    let chan = connect(ctx, &args.name, timeout).await?;
+    let strs = enum_strs(&chan, timeout).await?;
+    let old = chan.get_dyn(DynKind::Base)?.timeout(timeout).await?;
+    let chan = chan
+        .into_converting::<[EpicsString]>()
+        .map_err(|(err, _)| err)?;
+    chan.put_ref::<[EpicsString]>(&values)?
+        .timeout(timeout)
+        .await?;
+    let new = chan.get_dyn(DynKind::Base)?.timeout(timeout).await?;
+    Ok((old, new, strs))
+}
+
+#[async_std::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let ctx = Context::new().expect("Cannot create context");
+    match put(&ctx, &args).await {
+        Ok((old, new, strs)) => {
+            let strs = strs.as_deref();
+            if args.options.json {
+                let old = json_request(&args.name, &old, strs);
+                let new = json_request(&args.name, &new, strs);
+                println!(
+                    "{}",
+                    json!({ "name": args.name, "old": old["value"], "new": new["value"] })
+                );
+            } else {
+                println!("Old : {}", format_request(&args.name, &old, strs));
+                println!("New : {}", format_request(&args.name, &new, strs));
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            print_error(&args.name, err, args.options.json);
+            ExitCode::FAILURE
+        }
+    }
+}
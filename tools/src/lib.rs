@@ -0,0 +1,372 @@
+//! Common code of Channel Access command-line tools.
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use epics_ca::{
+    error::{self, Error},
+    request::{DynCtrl, DynKind, DynRequest, Limits},
+    types::{Alarm, DynArray, DynScalar, DynValue, EpicsTimeStamp, FieldId},
+    Channel, Context,
+};
+use serde_json::{json, Map, Value as Json};
+use std::{ffi::CString, fmt::Display, time::Duration};
+
+/// Options common for all tools.
+#[derive(clap::Args, Debug)]
+pub struct Options {
+    /// Wait time in seconds for channel connection and operations.
+    #[arg(short = 'w', long = "wait", default_value_t = 1.0, value_parser = parse_wait)]
+    pub timeout: f64,
+    /// Print output as JSON, one object per line.
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl Options {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.timeout)
+    }
+}
+
+/// Accept only wait times that can be converted to [`Duration`].
+fn parse_wait(arg: &str) -> Result<f64, String> {
+    let secs = arg.parse::<f64>().map_err(|err| err.to_string())?;
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid wait time: {}", arg))?;
+    Ok(secs)
+}
+
+/// Create channel and wait for it to connect.
+pub async fn connect(ctx: &Context, name: &str, timeout: Duration) -> Result<Channel, Error> {
+    let name = CString::new(name).map_err(|_| error::BADSTR)?;
+    let chan = Channel::new(ctx, &name)?;
+    chan.connected_timeout(timeout).await?;
+    Ok(chan)
+}
+
+/// Get enum state strings if channel is of enum type.
+pub async fn enum_strs(chan: &Channel, timeout: Duration) -> Result<Option<Vec<String>>, Error> {
+    if chan.field_type()? != FieldId::Enum {
+        return Ok(None);
+    }
+    let req = chan.get_dyn(DynKind::Ctrl)?.timeout(timeout).await?;
+    Ok(match req.ctrl() {
+        Some(DynCtrl::Enum { strs }) => Some(
+            strs.iter()
+                .map(|s| s.to_string_lossy().into_owned())
+                .collect(),
+        ),
+        _ => None,
+    })
+}
+
+pub fn format_stamp(stamp: EpicsTimeStamp) -> String {
+    DateTime::<Local>::from(stamp.to_system())
+        .format("%Y-%m-%d %H:%M:%S%.6f")
+        .to_string()
+}
+
+pub fn json_stamp(stamp: EpicsTimeStamp) -> Json {
+    DateTime::<Utc>::from(stamp.to_system())
+        .to_rfc3339_opts(SecondsFormat::Micros, true)
+        .into()
+}
+
+/// Alarm status and severity names.
+pub fn alarm_names(alarm: Alarm) -> (String, String) {
+    (
+        format!("{:?}", alarm.condition).to_uppercase(),
+        format!("{:?}", alarm.severity).to_uppercase(),
+    )
+}
+
+fn json_alarm(alarm: Alarm) -> Json {
+    let (status, severity) = alarm_names(alarm);
+    json!({ "status": status, "severity": severity })
+}
+
+fn enum_name(index: u16, strs: Option<&[String]>) -> Option<&str> {
+    strs.and_then(|strs| strs.get(index as usize))
+        .map(|s| s.as_str())
+}
+
+fn format_items<T: Display>(items: &[T]) -> String {
+    let mut text = items.len().to_string();
+    for item in items {
+        text += &format!(" {}", item);
+    }
+    text
+}
+
+pub fn format_scalar(scalar: &DynScalar, strs: Option<&[String]>) -> String {
+    match scalar {
+        DynScalar::String(s) => s.to_string_lossy().into_owned(),
+        DynScalar::Short(x) => x.to_string(),
+        DynScalar::Float(x) => x.to_string(),
+        DynScalar::Enum(e) => match enum_name(e.0, strs) {
+            Some(name) => name.to_string(),
+            None => e.0.to_string(),
+        },
+        DynScalar::Char(x) => x.to_string(),
+        DynScalar::Long(x) => x.to_string(),
+        DynScalar::Double(x) => x.to_string(),
+    }
+}
+
+/// Format value the way `caget` does: scalar as is, array prefixed with element count.
+pub fn format_value(value: &DynValue, strs: Option<&[String]>) -> String {
+    match value {
+        DynValue::Scalar(scalar) => format_scalar(scalar, strs),
+        DynValue::Array(array) => match array {
+            DynArray::String(v) => format_items(
+                &v.iter()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>(),
+            ),
+            DynArray::Short(v) => format_items(v),
+            DynArray::Float(v) => format_items(v),
+            DynArray::Enum(v) => format_items(
+                &v.iter()
+                    .map(|e| format_scalar(&DynScalar::Enum(*e), strs))
+                    .collect::<Vec<_>>(),
+            ),
+            DynArray::Char(v) => format_items(v),
+            DynArray::Long(v) => format_items(v),
+            DynArray::Double(v) => format_items(v),
+        },
+    }
+}
+
+fn json_scalar(scalar: &DynScalar, strs: Option<&[String]>) -> Json {
+    match scalar {
+        DynScalar::String(s) => s.to_string_lossy().into(),
+        DynScalar::Short(x) => (*x).into(),
+        DynScalar::Float(x) => (*x).into(),
+        DynScalar::Enum(e) => match enum_name(e.0, strs) {
+            Some(name) => name.into(),
+            None => e.0.into(),
+        },
+        DynScalar::Char(x) => (*x).into(),
+        DynScalar::Long(x) => (*x).into(),
+        DynScalar::Double(x) => (*x).into(),
+    }
+}
+
+pub fn json_value(value: &DynValue, strs: Option<&[String]>) -> Json {
+    match value {
+        DynValue::Scalar(scalar) => json_scalar(scalar, strs),
+        DynValue::Array(array) => match array {
+            DynArray::String(v) => v.iter().map(|s| s.to_string_lossy()).collect(),
+            DynArray::Short(v) => v.as_slice().into(),
+            DynArray::Float(v) => v.as_slice().into(),
+            DynArray::Enum(v) => v
+                .iter()
+                .map(|e| json_scalar(&DynScalar::Enum(*e), strs))
+                .collect(),
+            DynArray::Char(v) => v.as_slice().into(),
+            DynArray::Long(v) => v.as_slice().into(),
+            DynArray::Double(v) => v.as_slice().into(),
+        },
+    }
+}
+
+fn limits_lines<T: Display>(limits: &Limits<T>) -> Vec<String> {
+    vec![
+        format!("Lo disp limit:  {}", limits.lower_disp),
+        format!("Hi disp limit:  {}", limits.upper_disp),
+        format!("Lo alarm limit: {}", limits.lower_alarm),
+        format!("Lo warn limit:  {}", limits.lower_warning),
+        format!("Hi warn limit:  {}", limits.upper_warning),
+        format!("Hi alarm limit: {}", limits.upper_alarm),
+        format!("Lo ctrl limit:  {}", limits.lower_ctrl),
+        format!("Hi ctrl limit:  {}", limits.upper_ctrl),
+    ]
+}
+
+fn json_limits<T: Into<Json> + Copy>(limits: &Limits<T>) -> Json {
+    json!({
+        "lower_disp": limits.lower_disp.into(),
+        "upper_disp": limits.upper_disp.into(),
+        "lower_alarm": limits.lower_alarm.into(),
+        "lower_warning": limits.lower_warning.into(),
+        "upper_warning": limits.upper_warning.into(),
+        "upper_alarm": limits.upper_alarm.into(),
+        "lower_ctrl": limits.lower_ctrl.into(),
+        "upper_ctrl": limits.upper_ctrl.into(),
+    })
+}
+
+fn ctrl_lines(ctrl: &DynCtrl) -> Vec<String> {
+    let units =
+        |units: &epics_ca::request::Units| format!("Units:          {}", units.0.to_string_lossy());
+    match ctrl {
+        DynCtrl::String => Vec::new(),
+        DynCtrl::Enum { strs } => {
+            let mut lines = vec![format!("Enums:          ({})", strs.len())];
+            for (i, s) in strs.iter().enumerate() {
+                lines.push(format!("                [{:2}] {}", i, s.to_string_lossy()));
+            }
+            lines
+        }
+        DynCtrl::Short { units: u, limits } => [vec![units(u)], limits_lines(limits)].concat(),
+        DynCtrl::Char { units: u, limits } => [vec![units(u)], limits_lines(limits)].concat(),
+        DynCtrl::Long { units: u, limits } => [vec![units(u)], limits_lines(limits)].concat(),
+        DynCtrl::Float {
+            precision,
+            units: u,
+            limits,
+        } => [
+            vec![units(u), format!("Precision:      {}", precision)],
+            limits_lines(limits),
+        ]
+        .concat(),
+        DynCtrl::Double {
+            precision,
+            units: u,
+            limits,
+        } => [
+            vec![units(u), format!("Precision:      {}", precision)],
+            limits_lines(limits),
+        ]
+        .concat(),
+    }
+}
+
+fn json_ctrl(ctrl: &DynCtrl) -> Json {
+    let units = |units: &epics_ca::request::Units| Json::from(units.0.to_string_lossy());
+    match ctrl {
+        DynCtrl::String => json!({}),
+        DynCtrl::Enum { strs } => {
+            json!({ "enums": strs.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>() })
+        }
+        DynCtrl::Short { units: u, limits } => {
+            json!({ "units": units(u), "limits": json_limits(limits) })
+        }
+        DynCtrl::Char { units: u, limits } => {
+            json!({ "units": units(u), "limits": json_limits(limits) })
+        }
+        DynCtrl::Long { units: u, limits } => {
+            json!({ "units": units(u), "limits": json_limits(limits) })
+        }
+        DynCtrl::Float {
+            precision,
+            units: u,
+            limits,
+        } => {
+            json!({ "units": units(u), "precision": precision, "limits": json_limits(limits) })
+        }
+        DynCtrl::Double {
+            precision,
+            units: u,
+            limits,
+        } => {
+            json!({ "units": units(u), "precision": precision, "limits": json_limits(limits) })
+        }
+    }
+}
+
+/// Format response as a text, the same way as EPICS base tools do.
+pub fn format_request(name: &str, req: &DynRequest, strs: Option<&[String]>) -> String {
+    let value = format_value(req.value(), strs);
+    match req {
+        DynRequest::Base(..) => format!("{} {}", name, value),
+        DynRequest::Sts { alarm, .. } => {
+            let (status, severity) = alarm_names(*alarm);
+            format!("{} {} {} {}", name, value, status, severity)
+        }
+        DynRequest::Time { alarm, stamp, .. } => {
+            let (status, severity) = alarm_names(*alarm);
+            format!(
+                "{} {} {} {} {}",
+                name,
+                format_stamp(*stamp),
+                value,
+                status,
+                severity
+            )
+        }
+        DynRequest::Ctrl { alarm, ctrl, .. } => {
+            let (status, severity) = alarm_names(*alarm);
+            let mut lines = vec![
+                name.to_string(),
+                format!("Value:          {}", value),
+                format!("Status:         {}", status),
+                format!("Severity:       {}", severity),
+            ];
+            lines.extend(ctrl_lines(ctrl));
+            lines.join("\n    ")
+        }
+    }
+}
+
+/// Convert response to JSON object.
+pub fn json_request(name: &str, req: &DynRequest, strs: Option<&[String]>) -> Json {
+    let mut object = Map::new();
+    object.insert("name".into(), name.into());
+    object.insert("value".into(), json_value(req.value(), strs));
+    if let Some(alarm) = req.alarm() {
+        object.insert("alarm".into(), json_alarm(alarm));
+    }
+    if let Some(stamp) = req.stamp() {
+        object.insert("timestamp".into(), json_stamp(stamp));
+    }
+    if let Some(ctrl) = req.ctrl() {
+        object.insert("ctrl".into(), json_ctrl(ctrl));
+    }
+    Json::Object(object)
+}
+
+/// Print response either as text or as JSON.
+pub fn print_request(name: &str, req: &DynRequest, strs: Option<&[String]>, json: bool) {
+    if json {
+        println!("{}", json_request(name, req, strs));
+    } else {
+        println!("{}", format_request(name, req, strs));
+    }
+}
+
+/// Print error that occurred with the channel.
+pub fn print_error(name: &str, err: Error, json: bool) {
+    if json {
+        println!(
+            "{}",
+            json!({ "name": name, "error": format!("{:?}", err.kind) })
+        );
+    } else {
+        eprintln!("{}: {:?}", name, err.kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epics_ca::types::EpicsEnum;
+
+    #[test]
+    fn wait() {
+        assert_eq!(parse_wait("0.5"), Ok(0.5));
+        for arg in ["-1", "NaN", "inf", "abc"] {
+            assert!(parse_wait(arg).is_err());
+        }
+    }
+
+    #[test]
+    fn value() {
+        assert_eq!(format_value(&DynValue::from(1.5f64), None), "1.5");
+        assert_eq!(
+            format_value(&DynValue::from(vec![1, 2, 3]), None),
+            "3 1 2 3"
+        );
+        let strs = ["Off".to_string(), "On".to_string()];
+        assert_eq!(
+            format_value(&DynValue::from(EpicsEnum(1)), Some(&strs)),
+            "On"
+        );
+        assert_eq!(
+            json_value(&DynValue::from(vec![1i16, 2]), None),
+            json!([1, 2])
+        );
+        assert_eq!(
+            json_value(&DynValue::from(EpicsEnum(0)), Some(&strs)),
+            json!("Off")
+        );
+    }
+}